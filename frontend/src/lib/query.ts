@@ -1,5 +1,5 @@
 import { queryOptions } from "@tanstack/react-query"
-import { ListRequest, ListResponseSchema, MirrorHealthResponseSchema, MirrorResponseSchema, ViewResponseSchema } from "./types"
+import { HealthWindow, ListRequest, ListResponseSchema, MirrorHealthResponseSchema, MirrorResponseSchema, RequestLogResponseSchema, ViewResponseSchema } from "./types"
 import { ApiUrl } from "./url"
 
 export const mirrorQueryOptions = queryOptions({
@@ -44,12 +44,26 @@ export const viewQueryOptions = (mirror: string, id: number) => {
     })
 }
 
-export const healthQueryOptions = queryOptions({
-    queryKey: ['health'],
-    retry: 3,
-    retryDelay: 2000,
-    queryFn: async () => {
-        const response = await fetch(`${ApiUrl}/api/health`)
-        return MirrorHealthResponseSchema.parse(await response.json())
-    }
-})
+export const healthQueryOptions = (window: HealthWindow) => {
+    return queryOptions({
+        queryKey: ['health', window],
+        retry: 3,
+        retryDelay: 2000,
+        queryFn: async () => {
+            const response = await fetch(`${ApiUrl}/api/health?window=${window}`)
+            return MirrorHealthResponseSchema.parse(await response.json())
+        }
+    })
+}
+
+export const requestLogQueryOptions = (page: number) => {
+    return queryOptions({
+        queryKey: ['health', 'requests', page],
+        retry: 3,
+        retryDelay: 2000,
+        queryFn: async () => {
+            const response = await fetch(`${ApiUrl}/api/health/requests?p=${page}&per_page=100`)
+            return RequestLogResponseSchema.parse(await response.json())
+        }
+    })
+}
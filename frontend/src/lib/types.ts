@@ -157,6 +157,7 @@ export const ListItemSchema = z.object({
     comments: z.number().int().nonnegative(),
     trusted: z.boolean(),
     remake: z.boolean(),
+    scraped_at: z.string().datetime().optional(),
 });
 
 export const ListRequestSchema = z.object({
@@ -168,14 +169,72 @@ export const ListRequestSchema = z.object({
     q: z.string().optional(),
 });
 
+export const PostFilterSchema = z.object({
+    upstream_pages: z.number().int().nonnegative(),
+    truncated: z.boolean(),
+});
+
 export const ListResponseSchema = z.object({
     items: z.array(ListItemSchema),
+    post_filter: PostFilterSchema.optional(),
+});
+
+export const QueryErrorResponseSchema = z.object({
+    error: z.string(),
+    start: z.number().int().nonnegative(),
+    end: z.number().int().nonnegative(),
 });
 
+export type QueryErrorResponse = z.infer<typeof QueryErrorResponseSchema>;
+
 export type ListItem = z.infer<typeof ListItemSchema>;
 export type ListRequest = z.infer<typeof ListRequestSchema>;
 export type ListResponse = z.infer<typeof ListResponseSchema>;
 
+export const SearchSourceSchema = z.object({
+    mirror: z.string(),
+    id: z.number().int().nonnegative(),
+});
+
+export const SearchHitSchema = ListItemSchema.extend({
+    info_hash: z.string().nullable(),
+    sources: z.array(SearchSourceSchema),
+});
+
+export const SearchMirrorResultSchema = z.object({
+    mirror: z.string(),
+    ok: z.boolean(),
+    error: z.string().nullable(),
+    item_count: z.number().int().nonnegative(),
+    elapsed: z.number(),
+});
+
+export const SearchResponseSchema = z.object({
+    items: z.array(SearchHitSchema),
+    mirrors: z.array(SearchMirrorResultSchema),
+});
+
+export type SearchHit = z.infer<typeof SearchHitSchema>;
+export type SearchResponse = z.infer<typeof SearchResponseSchema>;
+
+export const UserProfileSchema = z.object({
+    name: z.string(),
+    level: z.string().nullable(),
+    trusted: z.boolean(),
+    badges: z.array(z.string()),
+    avatar: z.string().nullable(),
+    torrent_count: z.number().int().nonnegative().nullable(),
+});
+
+export type UserProfile = z.infer<typeof UserProfileSchema>;
+
+export const UserResponseSchema = z.object({
+    user: UserProfileSchema,
+    items: z.array(ListItemSchema),
+});
+
+export type UserResponse = z.infer<typeof UserResponseSchema>;
+
 
 export const ViewCommentSchema = z.object({
     id: z.number().int().nonnegative(),
@@ -191,26 +250,69 @@ export type ViewComment = z.infer<typeof ViewCommentSchema>;
 export const ViewFileSchema = z.object({
     id: z.number().int().nonnegative(),
     name: z.string(),
+    path: z.string(),
     size: z.number().int().nonnegative(),
 });
 
 export type ViewFile = z.infer<typeof ViewFileSchema>;
 
+export type ViewFileNode =
+    | { type: "folder"; name: string; children: ViewFileNode[] }
+    | { type: "file"; name: string; size: number };
+
+export const ViewFileNodeSchema: z.ZodType<ViewFileNode> = z.lazy(() =>
+    z.discriminatedUnion("type", [
+        z.object({
+            type: z.literal("folder"),
+            name: z.string(),
+            children: z.array(ViewFileNodeSchema),
+        }),
+        z.object({
+            type: z.literal("file"),
+            name: z.string(),
+            size: z.number().int().nonnegative(),
+        }),
+    ])
+);
+
+export const ExternalIdsSchema = z.object({
+    myanimelist: z.number().int().nonnegative().nullable(),
+    anilist: z.number().int().nonnegative().nullable(),
+    anidb: z.number().int().nonnegative().nullable(),
+    kitsu: z.string().nullable(),
+    imdb: z.string().nullable(),
+    tvdb: z.string().nullable(),
+    tmdb: z.string().nullable(),
+});
+
+export type ExternalIds = z.infer<typeof ExternalIdsSchema>;
+
 export const ViewResponseSchema = z.object({
     id: z.number().int().nonnegative(),
     title: z.string(),
     pub_date: z.string().datetime(),
     description_md: z.string(),
+    format: z.enum(["md", "html", "text"]),
+    description: z.string(),
     category: z.string(),
     size: z.number().int().nonnegative(),
     seeders: z.number().int().nonnegative(),
     leechers: z.number().int().nonnegative(),
     downloads: z.number().int().nonnegative(),
+    scraped_at: z.string().datetime().optional(),
     trusted: z.boolean(),
     remake: z.boolean(),
     magnet_link: z.string().nullable().optional(),
+    submitter: z.string(),
     comments: z.array(ViewCommentSchema),
     files: z.array(ViewFileSchema),
+    file_tree: z.array(ViewFileNodeSchema),
+    information: z.string().nullable(),
+    external_ids: ExternalIdsSchema,
+    deleted: z.boolean(),
+    deleted_at: z.string().datetime().optional(),
+    archived_at: z.string().datetime().optional(),
+    upstream_error: z.string().optional(),
 });
 
 export type ViewResponse = z.infer<typeof ViewResponseSchema>;
@@ -246,11 +348,302 @@ export const MirrorViewRouteParamsSchema = z.object({
     id: z.coerce.number().int().nonnegative(),
 });
 
+export const HealthWindowSchema = z.enum(["5m", "1h", "24h", "7d"]);
+
+export type HealthWindow = z.infer<typeof HealthWindowSchema>;
+
+export const HealthStatusSchema = z.enum(["up", "degraded", "down", "unknown"]);
+
+export type HealthStatus = z.infer<typeof HealthStatusSchema>;
+
+export const ProbeResultSchema = z.object({
+    timestamp: z.string().datetime(),
+    success: z.boolean(),
+    elapsed_time: z.number(),
+    upstream_status: z.number().int().nullable(),
+    error_kind: z.string().nullable(),
+    item_count: z.number().int().nonnegative().nullable(),
+});
+
+export type ProbeResult = z.infer<typeof ProbeResultSchema>;
+
+export const MirrorHealthSchema = z.object({
+    id: z.string(),
+    name: z.string(),
+    status: HealthStatusSchema,
+    request_count: z.number().int().nonnegative(),
+    upstream_request_count: z.number().int().nonnegative(),
+    success_rate: z.number().nullable(),
+    cache_hit_ratio: z.number().nullable(),
+    latency_p50: z.number().nullable(),
+    latency_p95: z.number().nullable(),
+    latency_p99: z.number().nullable(),
+    last_success: z.string().datetime().nullable(),
+    last_failure: z.string().datetime().nullable(),
+    layout_drift_count: z.number().int().nonnegative(),
+    probe_count: z.number().int().nonnegative(),
+    probe_success_count: z.number().int().nonnegative(),
+    last_probe: ProbeResultSchema.nullable(),
+});
+
+export type MirrorHealth = z.infer<typeof MirrorHealthSchema>;
+
 export const MirrorHealthResponseSchema = z.object({
-    mirrors: z.array(
+    window: HealthWindowSchema,
+    since: z.string().datetime(),
+    mirrors: z.array(MirrorHealthSchema),
+});
+
+export type MirrorHealthResponse = z.infer<typeof MirrorHealthResponseSchema>;
+
+export const LayoutDriftSchema = z.object({
+    page: z.enum(["list", "view"]),
+    issues: z.array(z.object({ kind: z.string() }).passthrough()),
+});
+
+export type LayoutDrift = z.infer<typeof LayoutDriftSchema>;
+
+export const RequestLogEntrySchema = z.object({
+    timestamp: z.string().datetime(),
+    mirror_id: z.string(),
+    path: z.string(),
+    success: z.boolean(),
+    cache_hit: z.boolean(),
+    elapsed_time: z.number(),
+    request_id: z.string().nullable(),
+    upstream_status: z.number().int().nullable(),
+    response_bytes: z.number().int().nonnegative().nullable(),
+    content_type: z.string().nullable(),
+    error_kind: z.string().nullable(),
+    item_count: z.number().int().nonnegative().nullable(),
+    layout_drift: LayoutDriftSchema.nullable(),
+});
+
+export type RequestLogEntry = z.infer<typeof RequestLogEntrySchema>;
+
+export const RequestLogResponseSchema = z.object({
+    page: z.number().int().positive(),
+    per_page: z.number().int().positive(),
+    total: z.number().int().nonnegative(),
+    items: z.array(RequestLogEntrySchema),
+});
+
+export type RequestLogResponse = z.infer<typeof RequestLogResponseSchema>;
+
+export const SavedSearchSchema = z.object({
+    id: z.number().int(),
+    mirror_id: z.string(),
+    name: z.string(),
+    query: ListRequestSchema.partial(),
+    interval: z.string(),
+    created_at: z.string().datetime(),
+    last_checked_at: z.string().datetime().nullable(),
+    last_error: z.string().nullable(),
+});
+
+export type SavedSearch = z.infer<typeof SavedSearchSchema>;
+
+export const SavedSearchesResponseSchema = z.object({
+    items: z.array(SavedSearchSchema),
+});
+
+export type SavedSearchesResponse = z.infer<typeof SavedSearchesResponseSchema>;
+
+export const SendSourceSchema = z.enum(["magnet", "torrent"]);
+
+export type SendSource = z.infer<typeof SendSourceSchema>;
+
+export const SendRequestSchema = z.object({
+    client: z.string().optional(),
+    category: z.string().optional(),
+    save_path: z.string().optional(),
+    paused: z.boolean().optional(),
+    source: SendSourceSchema.optional(),
+});
+
+export type SendRequest = z.infer<typeof SendRequestSchema>;
+
+export const SendResponseSchema = z.object({
+    client: z.string(),
+    source: SendSourceSchema,
+});
+
+export type SendResponse = z.infer<typeof SendResponseSchema>;
+
+export const RuleFilterSchema = z.object({
+    include: z.array(z.string()).optional(),
+    exclude: z.array(z.string()).optional(),
+    min_size: z.number().int().nonnegative().optional(),
+    max_size: z.number().int().nonnegative().optional(),
+    min_seeders: z.number().int().nonnegative().optional(),
+    trusted_only: z.boolean(),
+});
+
+export type RuleFilter = z.infer<typeof RuleFilterSchema>;
+
+export const RuleActionSchema = z.discriminatedUnion("type", [
+    z.object({ type: z.literal("watch_dir") }),
     z.object({
-        id: z.string(),
-        name: z.string(),
-        requests: z.array(z.tuple([z.string().datetime(), z.string(), z.boolean(), z.boolean(), z.number()])),
-    })
-)});
+        type: z.literal("download_client"),
+        client: z.string().optional(),
+        category: z.string().optional(),
+        save_path: z.string().optional(),
+        paused: z.boolean().optional(),
+        source: SendSourceSchema,
+    }),
+]);
+
+export type RuleAction = z.infer<typeof RuleActionSchema>;
+
+export const RuleSchema = z.object({
+    id: z.number().int(),
+    mirror_id: z.string(),
+    name: z.string(),
+    enabled: z.boolean(),
+    dry_run: z.boolean(),
+    query: ListRequestSchema.partial(),
+    filter: RuleFilterSchema,
+    track_episodes: z.boolean(),
+    action: RuleActionSchema,
+    interval: z.string(),
+    created_at: z.string().datetime(),
+    last_checked_at: z.string().datetime().nullable(),
+    last_error: z.string().nullable(),
+});
+
+export type Rule = z.infer<typeof RuleSchema>;
+
+export const RulesResponseSchema = z.object({
+    items: z.array(RuleSchema),
+});
+
+export type RulesResponse = z.infer<typeof RulesResponseSchema>;
+
+export const GrabSchema = z.object({
+    id: z.number().int(),
+    rule_id: z.number().int(),
+    mirror_id: z.string(),
+    torrent_id: z.number().int(),
+    title: z.string(),
+    episode: z.string().nullable(),
+    version: z.number().int().nullable(),
+    resolution: z.number().int().nullable(),
+    action: z.string(),
+    dry_run: z.boolean(),
+    reason: z.string(),
+    error: z.string().nullable(),
+    grabbed_at: z.string().datetime(),
+});
+
+export type Grab = z.infer<typeof GrabSchema>;
+
+export const RuleHistoryResponseSchema = z.object({
+    items: z.array(GrabSchema),
+});
+
+export type RuleHistoryResponse = z.infer<typeof RuleHistoryResponseSchema>;
+
+export const ReleaseSchema = z.object({
+    show: z.string(),
+    season: z.number().int(),
+    episode: z.number().int(),
+    version: z.number().int(),
+    resolution: z.number().int().nullable(),
+});
+
+export type Release = z.infer<typeof ReleaseSchema>;
+
+export const RulePreviewResponseSchema = z.object({
+    items: z.array(
+        z.object({
+            id: z.number().int(),
+            title: z.string(),
+            grab: z.boolean(),
+            reason: z.string(),
+            release: ReleaseSchema.nullable(),
+        }),
+    ),
+});
+
+export type RulePreviewResponse = z.infer<typeof RulePreviewResponseSchema>;
+
+export const LiveEventSchema = z.discriminatedUnion("type", [
+    z.object({
+        type: z.literal("new_torrent"),
+        id: z.number().int(),
+        mirror_id: z.string(),
+        at: z.string().datetime(),
+        item: ListItemSchema,
+    }),
+    z.object({
+        type: z.literal("swarm_change"),
+        id: z.number().int(),
+        mirror_id: z.string(),
+        at: z.string().datetime(),
+        item: ListItemSchema,
+        previous_seeders: z.number().int().nonnegative(),
+        previous_leechers: z.number().int().nonnegative(),
+    }),
+    z.object({ type: z.literal("resync") }),
+]);
+
+export type LiveEvent = z.infer<typeof LiveEventSchema>;
+
+export const DeletionSchema = z.object({
+    deleted_at: z.string().datetime(),
+    reason: z.enum(["not_found", "missing_from_list"]),
+});
+
+export const ListedTorrentSchema = ListItemSchema.extend({
+    info_hash: z.string().nullable(),
+    first_seen_at: z.string().datetime(),
+    updated_at: z.string().datetime(),
+    deletion: DeletionSchema.optional(),
+});
+
+export const ChangesResponseSchema = z.object({
+    items: z.array(ListedTorrentSchema),
+    next: z.string(),
+    has_more: z.boolean(),
+});
+
+export type ListedTorrent = z.infer<typeof ListedTorrentSchema>;
+export type ChangesResponse = z.infer<typeof ChangesResponseSchema>;
+
+export const SwarmSampleSchema = z.object({
+    at: z.string().datetime(),
+    seeders: z.number().int().nonnegative(),
+    leechers: z.number().int().nonnegative(),
+    downloads: z.number().int().nonnegative(),
+});
+
+export const SwarmStatsResponseSchema = z.object({
+    id: z.number().int().nonnegative(),
+    resolution: z.number().int().positive(),
+    items: z.array(SwarmSampleSchema),
+});
+
+export type SwarmSample = z.infer<typeof SwarmSampleSchema>;
+export type SwarmStatsResponse = z.infer<typeof SwarmStatsResponseSchema>;
+
+export const TrendingWindowSchema = z.enum(["1h", "24h", "7d"]);
+export const TrendingMetricSchema = z.enum(["downloads", "seeders"]);
+
+export const SwarmGrowthSchema = ListItemSchema.extend({
+    info_hash: z.string().nullable(),
+    seeder_growth: z.number().int(),
+    download_growth: z.number().int(),
+    observed_since: z.string().datetime(),
+});
+
+export const TrendingResponseSchema = z.object({
+    window: TrendingWindowSchema,
+    by: TrendingMetricSchema,
+    computed_at: z.string().datetime(),
+    items: z.array(SwarmGrowthSchema),
+});
+
+export type TrendingWindow = z.infer<typeof TrendingWindowSchema>;
+export type TrendingMetric = z.infer<typeof TrendingMetricSchema>;
+export type SwarmGrowth = z.infer<typeof SwarmGrowthSchema>;
+export type TrendingResponse = z.infer<typeof TrendingResponseSchema>;
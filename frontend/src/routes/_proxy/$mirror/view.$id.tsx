@@ -108,7 +108,9 @@ function Comments({ comments }: { comments: ViewComment[] }) {
                   </p>
                 </div>
               </div>
-              <p className="whitespace-pre-line">{comment.content}</p>
+              <Markdown remarkPlugins={[remarkGfm, remarkBreaks]} components={markdownComponents}>
+                {comment.content}
+              </Markdown>
             </div>
           ))}
         </div>
@@ -131,7 +133,7 @@ function Files({ files }: { files: ViewFile[] }) {
           <tbody>
             {files.map(file => (
               <tr key={file.id} className="border-b last:border-0">
-                <td className="p-2">{file.name}</td>
+                <td className="p-2">{file.path}</td>
                 <td className="p-2 text-right">{formatFileSize(file.size)}</td>
               </tr>
             ))}
@@ -1,56 +1,131 @@
 import { ErrorCard } from '@/components/error';
 import { Table, TableBody, TableHead, TableHeader, TableRow } from '@/components/ui/table';
-import { healthQueryOptions } from '@/lib/query';
+import { healthQueryOptions, requestLogQueryOptions } from '@/lib/query';
+import { HealthStatus, HealthWindowSchema } from '@/lib/types';
 import { queryClient } from '@/main';
 import { useSuspenseQuery } from '@tanstack/react-query';
-import { createFileRoute, ErrorComponentProps } from '@tanstack/react-router'
+import { createFileRoute, ErrorComponentProps, Link } from '@tanstack/react-router'
+import { zodValidator } from '@tanstack/zod-adapter';
 import { Loader2 } from 'lucide-react';
+import { z } from 'zod';
 import nyaaLogoUrl from '@/assets/nyaa.png';
 
+const HealthSearchSchema = z.object({
+    window: HealthWindowSchema.optional().default("1h"),
+    p: z.number().optional().default(1),
+});
+
 export const Route = createFileRoute('/health')({
     component: RouteComponent,
     pendingComponent: PendingComponent,
     errorComponent: ErrorComponent,
     notFoundComponent: NotFoundComponent,
-    loader: async () => {
-        return await queryClient.ensureQueryData(healthQueryOptions)
+    validateSearch: zodValidator(HealthSearchSchema),
+    loaderDeps: ({ search }) => ({ search }),
+    loader: async ({ deps: { search } }) => {
+        return await Promise.all([
+            queryClient.ensureQueryData(healthQueryOptions(search.window)),
+            queryClient.ensureQueryData(requestLogQueryOptions(search.p)),
+        ])
     },
 })
 
-function RouteComponent() {
-    const { data } = useSuspenseQuery(healthQueryOptions)
+const statusColor: Record<HealthStatus, string> = {
+    up: 'text-green-500',
+    degraded: 'text-yellow-500',
+    down: 'text-red-500',
+    unknown: 'text-gray-500',
+}
 
+function formatRatio(value: number | null) {
+    return value === null ? '-' : `${(value * 100.0).toFixed(1)}%`
+}
+
+function formatLatency(value: number | null) {
+    return value === null ? '-' : `${(value * 1000.0).toFixed(0)} ms`
+}
+
+function formatDate(value: string | null) {
+    return value === null ? '-' : new Date(value).toLocaleString()
+}
+
+function RouteComponent() {
+    const search = Route.useSearch()
+    const { data } = useSuspenseQuery(healthQueryOptions(search.window))
+    const { data: log } = useSuspenseQuery(requestLogQueryOptions(search.p))
+    const pages = Math.max(1, Math.ceil(log.total / log.per_page))
 
     return (
-        <div className="flex justify-center items-center h-screen">
-            {data.mirrors.map((mirror) => (
-                <div key={mirror.id} className="flex flex-col items-center">
-                    <img src={nyaaLogoUrl} alt="Logo" className="h-12 w-12" />
-                    <div className="text-green-500">{mirror.name}</div>
-                    <Table>
-                        <TableHeader>
-                            <TableRow>
-                                <TableHead>Date</TableHead>
-                                <TableHead>Path</TableHead>
-                                <TableHead>Success</TableHead>
-                                <TableHead>Cached</TableHead>
-                                <TableHead>Response Time</TableHead>
-                            </TableRow>
-                        </TableHeader>
-                        <TableBody>
-                            {mirror.requests.map((x) => (
-                                <TableRow key={x[0]}>
-                                    <TableHead>{new Date(x[0]).toLocaleString()}</TableHead>
-                                    <TableHead>{x[1]}</TableHead>
-                                    <TableHead>{x[2] ? 'Yes' : 'No'}</TableHead>
-                                    <TableHead>{x[3] ? 'Yes' : 'No'}</TableHead>
-                                    <TableHead>{(x[4] * 1000.0).toFixed(2)} ms</TableHead>
-                                </TableRow>
-                            ))}
-                        </TableBody>
-                    </Table>
-                </div>
-            ))}
+        <div className="flex flex-col items-center gap-8 p-8">
+            <img src={nyaaLogoUrl} alt="Logo" className="h-12 w-12" />
+            <div className="flex gap-4">
+                {HealthWindowSchema.options.map((window) => (
+                    <Link key={window} to="/health" search={{ ...search, window }} className={window === data.window ? 'font-bold' : ''}>
+                        {window}
+                    </Link>
+                ))}
+            </div>
+            <Table>
+                <TableHeader>
+                    <TableRow>
+                        <TableHead>Mirror</TableHead>
+                        <TableHead>Status</TableHead>
+                        <TableHead>Requests</TableHead>
+                        <TableHead>Success Rate</TableHead>
+                        <TableHead>Cache Hits</TableHead>
+                        <TableHead>p50</TableHead>
+                        <TableHead>p95</TableHead>
+                        <TableHead>p99</TableHead>
+                        <TableHead>Last Success</TableHead>
+                        <TableHead>Last Failure</TableHead>
+                    </TableRow>
+                </TableHeader>
+                <TableBody>
+                    {data.mirrors.map((mirror) => (
+                        <TableRow key={mirror.id}>
+                            <TableHead>{mirror.name}</TableHead>
+                            <TableHead className={statusColor[mirror.status]}>{mirror.status}</TableHead>
+                            <TableHead>{mirror.request_count}</TableHead>
+                            <TableHead>{formatRatio(mirror.success_rate)}</TableHead>
+                            <TableHead>{formatRatio(mirror.cache_hit_ratio)}</TableHead>
+                            <TableHead>{formatLatency(mirror.latency_p50)}</TableHead>
+                            <TableHead>{formatLatency(mirror.latency_p95)}</TableHead>
+                            <TableHead>{formatLatency(mirror.latency_p99)}</TableHead>
+                            <TableHead>{formatDate(mirror.last_success)}</TableHead>
+                            <TableHead>{formatDate(mirror.last_failure)}</TableHead>
+                        </TableRow>
+                    ))}
+                </TableBody>
+            </Table>
+            <Table>
+                <TableHeader>
+                    <TableRow>
+                        <TableHead>Date</TableHead>
+                        <TableHead>Mirror</TableHead>
+                        <TableHead>Path</TableHead>
+                        <TableHead>Success</TableHead>
+                        <TableHead>Cached</TableHead>
+                        <TableHead>Response Time</TableHead>
+                    </TableRow>
+                </TableHeader>
+                <TableBody>
+                    {log.items.map((x, index) => (
+                        <TableRow key={`${x.timestamp}-${index}`}>
+                            <TableHead>{new Date(x.timestamp).toLocaleString()}</TableHead>
+                            <TableHead>{x.mirror_id}</TableHead>
+                            <TableHead>{x.path}</TableHead>
+                            <TableHead>{x.success ? 'Yes' : 'No'}</TableHead>
+                            <TableHead>{x.cache_hit ? 'Yes' : 'No'}</TableHead>
+                            <TableHead>{(x.elapsed_time * 1000.0).toFixed(2)} ms</TableHead>
+                        </TableRow>
+                    ))}
+                </TableBody>
+            </Table>
+            <div className="flex gap-4">
+                {search.p > 1 && <Link to="/health" search={{ ...search, p: search.p - 1 }}>Previous</Link>}
+                <span>Page {search.p} of {pages}</span>
+                {search.p < pages && <Link to="/health" search={{ ...search, p: search.p + 1 }}>Next</Link>}
+            </div>
         </div>
     )
 }
@@ -0,0 +1,186 @@
+use axum::{Extension, Json, extract::Path, http::StatusCode, response::IntoResponse};
+
+use crate::{
+    MirrorExt,
+    api::mirror::list::QueryErrorResponse,
+    auto_download::{self, Action, Decision, Grab, Matcher, Rule, RuleSpec, Rules},
+    download_client::DownloadClients,
+    saved_search,
+};
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct RulesResponse {
+    pub items: Vec<Rule>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct HistoryResponse {
+    pub items: Vec<Grab>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct PreviewResponse {
+    pub items: Vec<Decision>,
+}
+
+fn database_error(err: rusqlite::Error) -> axum::response::Response {
+    tracing::error!("auto-download database error: {}", err);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Auto-download database error".to_string(),
+    )
+        .into_response()
+}
+
+fn not_found() -> axum::response::Response {
+    (StatusCode::NOT_FOUND, "Rule not found".to_string()).into_response()
+}
+
+/// Checks a rule against the configuration and compiles its patterns.
+fn validate(
+    mext: &MirrorExt,
+    clients: &DownloadClients,
+    rules: &Rules,
+    spec: &mut RuleSpec,
+) -> Result<Matcher, Box<axum::response::Response>> {
+    let bad_request =
+        |message: String| Box::new((StatusCode::BAD_REQUEST, message).into_response());
+    if mext.find_by_id(&spec.mirror_id).is_none() {
+        return Err(bad_request("Mirror not found".to_string()));
+    }
+    spec.name = spec.name.trim().to_string();
+    if spec.name.is_empty() {
+        return Err(bad_request("Name is required".to_string()));
+    }
+    if spec.interval < saved_search::MIN_INTERVAL {
+        return Err(bad_request(format!(
+            "Interval must be at least {} seconds",
+            saved_search::MIN_INTERVAL.as_secs()
+        )));
+    }
+    match &spec.action {
+        Action::WatchDir if rules.watch_dir().is_none() => {
+            return Err(bad_request("No watch directory is configured".to_string()));
+        }
+        Action::DownloadClient { client, .. } if clients.find(client.as_deref()).is_none() => {
+            return Err(bad_request("Download client not found".to_string()));
+        }
+        _ => {}
+    }
+    if let Err(err) = nyaa_parser::dsl::parse(spec.query.query.as_deref().unwrap_or_default()) {
+        return Err(Box::new(
+            (
+                StatusCode::BAD_REQUEST,
+                Json(QueryErrorResponse {
+                    error: err.message,
+                    start: err.start,
+                    end: err.end,
+                }),
+            )
+                .into_response(),
+        ));
+    }
+    spec.matcher()
+        .map_err(|err| bad_request(format!("Invalid title pattern: {}", err)))
+}
+
+#[axum::debug_handler]
+pub async fn list(Extension(rules): Extension<Rules>) -> impl IntoResponse {
+    match rules.list().await {
+        Ok(items) => Json(RulesResponse { items }).into_response(),
+        Err(err) => database_error(err),
+    }
+}
+
+#[axum::debug_handler]
+pub async fn create(
+    Extension(mext): Extension<MirrorExt>,
+    Extension(clients): Extension<DownloadClients>,
+    Extension(rules): Extension<Rules>,
+    Json(mut spec): Json<RuleSpec>,
+) -> impl IntoResponse {
+    if let Err(response) = validate(&mext, &clients, &rules, &mut spec) {
+        return *response;
+    }
+    match rules.create(spec).await {
+        Ok(rule) => (StatusCode::CREATED, Json(rule)).into_response(),
+        Err(err) => database_error(err),
+    }
+}
+
+#[axum::debug_handler]
+pub async fn get(Extension(rules): Extension<Rules>, Path(id): Path<i64>) -> impl IntoResponse {
+    match rules.get(id).await {
+        Ok(Some(rule)) => Json(rule).into_response(),
+        Ok(None) => not_found(),
+        Err(err) => database_error(err),
+    }
+}
+
+#[axum::debug_handler]
+pub async fn update(
+    Extension(mext): Extension<MirrorExt>,
+    Extension(clients): Extension<DownloadClients>,
+    Extension(rules): Extension<Rules>,
+    Path(id): Path<i64>,
+    Json(mut spec): Json<RuleSpec>,
+) -> impl IntoResponse {
+    if let Err(response) = validate(&mext, &clients, &rules, &mut spec) {
+        return *response;
+    }
+    match rules.update(id, spec).await {
+        Ok(Some(rule)) => Json(rule).into_response(),
+        Ok(None) => not_found(),
+        Err(err) => database_error(err),
+    }
+}
+
+#[axum::debug_handler]
+pub async fn delete(Extension(rules): Extension<Rules>, Path(id): Path<i64>) -> impl IntoResponse {
+    match rules.delete(id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => not_found(),
+        Err(err) => database_error(err),
+    }
+}
+
+#[axum::debug_handler]
+pub async fn history(Extension(rules): Extension<Rules>, Path(id): Path<i64>) -> impl IntoResponse {
+    match rules.get(id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return not_found(),
+        Err(err) => return database_error(err),
+    }
+    match rules.history(id).await {
+        Ok(items) => Json(HistoryResponse { items }).into_response(),
+        Err(err) => database_error(err),
+    }
+}
+
+/// Runs a rule once without saving it or grabbing anything, and returns
+/// what it would do with each listed torrent. History and the rule's
+/// creation time are not taken into account.
+#[axum::debug_handler]
+pub async fn preview(
+    Extension(mext): Extension<MirrorExt>,
+    Extension(clients): Extension<DownloadClients>,
+    Extension(rules): Extension<Rules>,
+    Json(mut spec): Json<RuleSpec>,
+) -> impl IntoResponse {
+    let matcher = match validate(&mext, &clients, &rules, &mut spec) {
+        Ok(matcher) => matcher,
+        Err(response) => return *response,
+    };
+    let mirror = mext
+        .find_by_id(&spec.mirror_id)
+        .expect("validated mirror exists");
+    let items = match saved_search::run(&*mirror.client.lock().await, &spec.query).await {
+        Ok(items) => items,
+        Err(err) => {
+            tracing::error!("Error fetching list: {:#}", err);
+            return (StatusCode::BAD_GATEWAY, format!("{:#}", err)).into_response();
+        }
+    };
+    let items = auto_download::evaluate(&spec, &matcher, &items, &[], None);
+    Json(PreviewResponse { items }).into_response()
+}
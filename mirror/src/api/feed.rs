@@ -0,0 +1,208 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use axum::{
+    Extension, Json,
+    extract::{Path, Query},
+    http::{HeaderMap, StatusCode, Uri, header},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+
+use crate::{
+    MirrorExt,
+    api::mirror::list::{ListRequest, QueryErrorResponse},
+    feed::{self, Feed, FeedFormat, FeedItem},
+};
+
+#[derive(Debug, Clone, Copy, Default, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LinkKind {
+    /// `.torrent` files proxied through this mirror.
+    #[default]
+    Torrent,
+    Magnet,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct FeedRequest {
+    #[serde(default)]
+    pub link: LinkKind,
+}
+
+/// Scheme and host this server was reached at, honoring the headers set by
+/// reverse proxies.
+fn base_url(headers: &HeaderMap) -> String {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split(',').next())
+            .map(|value| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    let scheme = header("x-forwarded-proto").unwrap_or_else(|| "http".to_string());
+    let host = header("x-forwarded-host")
+        .or_else(|| header(header::HOST.as_str()))
+        .unwrap_or_else(|| "localhost".to_string());
+    format!("{}://{}", scheme, host)
+}
+
+/// HTTP dates have one-second precision.
+fn http_date(date: DateTime<Utc>) -> String {
+    date.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+fn is_fresh(headers: &HeaderMap, etag: &str, last_modified: Option<DateTime<Utc>>) -> bool {
+    if let Some(if_none_match) = headers.get(header::IF_NONE_MATCH) {
+        let if_none_match = if_none_match.to_str().unwrap_or_default();
+        return if_none_match
+            .split(',')
+            .any(|tag| matches!(tag.trim(), "*") || tag.trim().trim_start_matches("W/") == etag);
+    }
+    let if_modified_since = headers
+        .get(header::IF_MODIFIED_SINCE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| DateTime::parse_from_rfc2822(value).ok());
+    match (if_modified_since, last_modified) {
+        (Some(since), Some(modified)) => modified.timestamp() <= since.timestamp(),
+        _ => false,
+    }
+}
+
+#[axum::debug_handler]
+pub async fn handler(
+    Extension(mext): Extension<MirrorExt>,
+    Path(file): Path<String>,
+    uri: Uri,
+    headers: HeaderMap,
+    Query(feed_request): Query<FeedRequest>,
+    Query(request): Query<ListRequest>,
+) -> impl IntoResponse {
+    let Some((format, mirror_id)) = file.rsplit_once('.').and_then(|(mirror_id, extension)| {
+        FeedFormat::from_extension(extension).map(|format| (format, mirror_id))
+    }) else {
+        return (
+            StatusCode::NOT_FOUND,
+            "Unknown feed format, expected .rss, .atom or .json".to_string(),
+        )
+            .into_response();
+    };
+    let Some(mirror) = mext.find_by_id(mirror_id) else {
+        tracing::error!("mirror not found");
+        return (StatusCode::NOT_FOUND, "Mirror not found".to_string()).into_response();
+    };
+
+    let term = request.query.clone().unwrap_or_default();
+    let expression = match nyaa_parser::dsl::parse(&term) {
+        Ok(expression) => expression,
+        Err(err) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(QueryErrorResponse {
+                    error: err.message,
+                    start: err.start,
+                    end: err.end,
+                }),
+            )
+                .into_response();
+        }
+    };
+    let query = request.into_query().term(expression.text.clone());
+
+    let result = {
+        let client = mirror.client.lock().await;
+        if expression.has_predicates() {
+            client
+                .list_filtered(&query, &expression)
+                .await
+                .map(|list| list.items)
+        } else {
+            client.list(&query).await
+        }
+    };
+    let items = match result {
+        Ok(items) => items,
+        Err(err) => {
+            tracing::error!("failed to fetch mirror list: {:?}", err);
+            return (
+                StatusCode::BAD_GATEWAY,
+                format!("Failed to fetch mirror list: {:?}", err),
+            )
+                .into_response();
+        }
+    };
+
+    let base = base_url(&headers);
+    let ty = mirror.ty();
+    let items = items
+        .into_iter()
+        .map(|item| {
+            let torrent = format!("{}/api/mirror/{}/download/{}", base, mirror.id(), item.id);
+            let link = match feed_request.link {
+                LinkKind::Torrent => torrent,
+                LinkKind::Magnet => item
+                    .magnet_link
+                    .clone()
+                    .or_else(|| {
+                        let hash = item.btih()?;
+                        let name = serde_urlencoded::to_string([("dn", &item.title)]).ok()?;
+                        Some(format!("magnet:?xt=urn:btih:{}&{}", hash, name))
+                    })
+                    .unwrap_or(torrent),
+            };
+            FeedItem {
+                view_url: format!("{}/{}/view/{}", base, mirror.id(), item.id),
+                link,
+                category_name: feed::category_name(&ty, &item.category),
+                item,
+            }
+        })
+        .collect();
+
+    let scope = if term.trim().is_empty() {
+        "Home".to_string()
+    } else {
+        format!("\"{}\"", term.trim())
+    };
+    let feed = Feed {
+        title: format!("{} - {} - Torrent File Feed", mirror.name(), scope),
+        description: format!("Feed for {}", scope),
+        home_url: format!("{}/{}", base, mirror.id()),
+        feed_url: format!(
+            "{}{}",
+            base,
+            uri.path_and_query()
+                .map(|path| path.as_str())
+                .unwrap_or(uri.path())
+        ),
+        items,
+    };
+
+    let body = feed.render(format);
+    let etag = {
+        let mut hasher = DefaultHasher::new();
+        body.hash(&mut hasher);
+        format!("\"{:016x}\"", hasher.finish())
+    };
+    let last_modified = feed.updated();
+
+    let mut response_headers = vec![
+        (header::ETAG, etag.clone()),
+        (header::CACHE_CONTROL, "no-cache".to_string()),
+    ];
+    if let Some(last_modified) = last_modified {
+        response_headers.push((header::LAST_MODIFIED, http_date(last_modified)));
+    }
+    let mut response = if is_fresh(&headers, &etag, last_modified) {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        response_headers.push((header::CONTENT_TYPE, format.content_type().to_string()));
+        body.into_response()
+    };
+    for (name, value) in response_headers {
+        if let Ok(value) = value.parse() {
+            response.headers_mut().insert(name, value);
+        }
+    }
+    response
+}
@@ -0,0 +1,157 @@
+use axum::{Extension, Json, extract::Query, response::IntoResponse};
+
+use crate::{MirrorExt, request_tracker};
+
+pub mod probe;
+pub mod requests;
+
+/// Minimum upstream success rate for a mirror to be reported as up.
+const UP_SUCCESS_RATE: f64 = 0.95;
+/// Minimum upstream success rate for a mirror to be reported as degraded
+/// rather than down.
+const DEGRADED_SUCCESS_RATE: f64 = 0.5;
+/// p95 latency (in seconds) above which a mirror is reported as degraded.
+const DEGRADED_LATENCY_P95: f64 = 10.0;
+
+#[derive(Debug, Clone, Copy, Default, serde::Deserialize, serde::Serialize)]
+pub enum HealthWindow {
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[default]
+    #[serde(rename = "1h")]
+    OneHour,
+    #[serde(rename = "24h")]
+    OneDay,
+    #[serde(rename = "7d")]
+    SevenDays,
+}
+
+impl HealthWindow {
+    pub fn duration(&self) -> chrono::Duration {
+        match self {
+            HealthWindow::FiveMinutes => chrono::Duration::minutes(5),
+            HealthWindow::OneHour => chrono::Duration::hours(1),
+            HealthWindow::OneDay => chrono::Duration::hours(24),
+            HealthWindow::SevenDays => chrono::Duration::days(7),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Up,
+    Degraded,
+    Down,
+    /// No upstream requests or probes were made in the window.
+    Unknown,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct HealthRequest {
+    #[serde(default)]
+    pub window: HealthWindow,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+struct HealthResponse {
+    window: HealthWindow,
+    since: chrono::DateTime<chrono::Utc>,
+    mirrors: Vec<MirrorHealth>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+struct MirrorHealth {
+    id: String,
+    name: String,
+    status: HealthStatus,
+    request_count: u64,
+    upstream_request_count: u64,
+    /// Share of upstream requests that succeeded; cache hits are excluded.
+    success_rate: Option<f64>,
+    cache_hit_ratio: Option<f64>,
+    /// Upstream latency percentiles, in seconds.
+    latency_p50: Option<f64>,
+    latency_p95: Option<f64>,
+    latency_p99: Option<f64>,
+    last_success: Option<chrono::DateTime<chrono::Utc>>,
+    last_failure: Option<chrono::DateTime<chrono::Utc>>,
+    /// Upstream responses that no longer matched the layout the parser expects.
+    layout_drift_count: u64,
+    /// Background probes in the window, kept apart from user traffic.
+    probe_count: u64,
+    probe_success_count: u64,
+    last_probe: Option<request_tracker::ProbeResult>,
+}
+
+impl MirrorHealth {
+    fn new(
+        id: &str,
+        name: &str,
+        summary: request_tracker::RequestSummary,
+        probes: request_tracker::ProbeSummary,
+    ) -> Self {
+        let ratio = |part: u64, total: u64| (total > 0).then(|| part as f64 / total as f64);
+        let success_rate = ratio(summary.upstream_success_count, summary.upstream_count);
+        // Probes count towards the status so idle mirrors are still judged.
+        let status_rate = ratio(
+            summary.upstream_success_count + probes.success_count,
+            summary.upstream_count + probes.count,
+        );
+        let status = match status_rate {
+            None => HealthStatus::Unknown,
+            Some(rate) if rate < DEGRADED_SUCCESS_RATE => HealthStatus::Down,
+            Some(rate) if rate < UP_SUCCESS_RATE => HealthStatus::Degraded,
+            Some(_) if summary.latency_p95.unwrap_or(0.0) > DEGRADED_LATENCY_P95 => {
+                HealthStatus::Degraded
+            }
+            Some(_) => HealthStatus::Up,
+        };
+
+        Self {
+            id: id.into(),
+            name: name.into(),
+            status,
+            request_count: summary.count,
+            upstream_request_count: summary.upstream_count,
+            success_rate,
+            cache_hit_ratio: ratio(summary.cache_hit_count, summary.count),
+            latency_p50: summary.latency_p50,
+            latency_p95: summary.latency_p95,
+            latency_p99: summary.latency_p99,
+            last_success: summary.last_success,
+            last_failure: summary.last_failure,
+            layout_drift_count: summary.layout_drift_count,
+            probe_count: probes.count,
+            probe_success_count: probes.success_count,
+            last_probe: probes.last,
+        }
+    }
+}
+
+#[axum::debug_handler]
+pub async fn handler(
+    Extension(mext): Extension<MirrorExt>,
+    Extension(request_tracker): Extension<request_tracker::RequestTracker>,
+    Query(request): Query<HealthRequest>,
+) -> impl IntoResponse {
+    let since = chrono::Utc::now() - request.window.duration();
+    let mut health = HealthResponse {
+        window: request.window,
+        since,
+        mirrors: vec![],
+    };
+
+    for mirror in mext.iter() {
+        let summary = request_tracker.summary(mirror.id(), since).await;
+        let probes = request_tracker.probe_summary(mirror.id(), since).await;
+        health.mirrors.push(MirrorHealth::new(
+            mirror.id(),
+            mirror.name(),
+            summary,
+            probes,
+        ));
+    }
+
+    Json(health).into_response()
+}
@@ -0,0 +1,146 @@
+use axum::{Extension, Json, http::StatusCode, response::IntoResponse};
+
+use crate::{MirrorExt, request_tracker};
+
+/// Number of probe intervals after which the last probe is considered stale.
+const STALE_PROBE_INTERVALS: u32 = 3;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+struct LiveResponse {
+    live: bool,
+    request_tracker: bool,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+struct ReadyResponse {
+    ready: bool,
+    mirrors: Vec<MirrorReadiness>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+struct MirrorReadiness {
+    id: String,
+    /// `None` when probing is disabled for this mirror.
+    ready: Option<bool>,
+    last_probe: Option<request_tracker::ProbeResult>,
+}
+
+/// Liveness: the server is handling requests and the request tracker writer
+/// is running.
+#[axum::debug_handler]
+pub async fn live(
+    Extension(request_tracker): Extension<request_tracker::RequestTracker>,
+) -> impl IntoResponse {
+    let request_tracker = request_tracker.is_alive();
+    let status = if request_tracker {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (
+        status,
+        Json(LiveResponse {
+            live: request_tracker,
+            request_tracker,
+        }),
+    )
+        .into_response()
+}
+
+/// Readiness: at least one probed mirror has a recent successful probe. When
+/// no mirror is probed the server is always ready.
+#[axum::debug_handler]
+pub async fn ready(
+    Extension(mext): Extension<MirrorExt>,
+    Extension(request_tracker): Extension<request_tracker::RequestTracker>,
+) -> impl IntoResponse {
+    let now = chrono::Utc::now();
+    let mut mirrors = Vec::new();
+    for mirror in mext.iter() {
+        let last_probe = request_tracker.probe_summary(mirror.id(), now).await.last;
+        let ready = mirror
+            .config
+            .probe_interval
+            .map(|interval| is_fresh(last_probe.as_ref(), interval, now));
+        mirrors.push(MirrorReadiness {
+            id: mirror.id().into(),
+            ready,
+            last_probe,
+        });
+    }
+
+    let ready = any_ready(&mirrors);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(ReadyResponse { ready, mirrors })).into_response()
+}
+
+/// Whether `last_probe` succeeded within [`STALE_PROBE_INTERVALS`] probe
+/// intervals of `now`.
+fn is_fresh(
+    last_probe: Option<&request_tracker::ProbeResult>,
+    interval: std::time::Duration,
+    now: chrono::DateTime<chrono::Utc>,
+) -> bool {
+    let stale_after = chrono::Duration::from_std(interval * STALE_PROBE_INTERVALS)
+        .unwrap_or(chrono::Duration::MAX);
+    last_probe.is_some_and(|probe| probe.success && now - probe.timestamp <= stale_after)
+}
+
+fn any_ready(mirrors: &[MirrorReadiness]) -> bool {
+    let probed = mirrors.iter().filter_map(|mirror| mirror.ready);
+    probed.clone().count() == 0 || probed.into_iter().any(|ready| ready)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn probe(seconds_ago: i64, success: bool) -> request_tracker::ProbeResult {
+        request_tracker::ProbeResult {
+            timestamp: now() - chrono::Duration::seconds(seconds_ago),
+            success,
+            elapsed_time: 0.1,
+            upstream_status: Some(200),
+            error_kind: None,
+            item_count: Some(75),
+        }
+    }
+
+    fn now() -> chrono::DateTime<Utc> {
+        Utc.with_ymd_and_hms(2025, 1, 1, 12, 0, 0).unwrap()
+    }
+
+    fn readiness(ready: Option<bool>) -> MirrorReadiness {
+        MirrorReadiness {
+            id: "nyaa".into(),
+            ready,
+            last_probe: None,
+        }
+    }
+
+    #[test]
+    fn test_is_fresh() {
+        let interval = Duration::from_secs(60);
+        assert!(is_fresh(Some(&probe(0, true)), interval, now()));
+        assert!(is_fresh(Some(&probe(180, true)), interval, now()));
+        assert!(!is_fresh(Some(&probe(181, true)), interval, now()));
+        assert!(!is_fresh(Some(&probe(10, false)), interval, now()));
+        assert!(!is_fresh(None, interval, now()));
+    }
+
+    #[test]
+    fn test_any_ready() {
+        assert!(any_ready(&[]));
+        assert!(any_ready(&[readiness(None)]));
+        assert!(!any_ready(&[readiness(None), readiness(Some(false))]));
+        assert!(any_ready(&[readiness(Some(false)), readiness(Some(true))]));
+    }
+}
@@ -0,0 +1,73 @@
+use axum::{Extension, Json, extract::Query, response::IntoResponse};
+
+use crate::request_tracker::{self, ErrorKind, RequestFilter, RequestLogEntry};
+
+const DEFAULT_PER_PAGE: usize = 100;
+const MAX_PER_PAGE: usize = 1000;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct RequestsRequest {
+    #[serde(default)]
+    pub mirror: Option<String>,
+    #[serde(default)]
+    pub success: Option<bool>,
+    #[serde(default)]
+    pub cache_hit: Option<bool>,
+    #[serde(default)]
+    pub error_kind: Option<ErrorKind>,
+    #[serde(default)]
+    pub request_id: Option<String>,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    #[serde(rename = "p")]
+    pub page: Option<usize>,
+    #[serde(default)]
+    pub per_page: Option<usize>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct RequestsResponse {
+    pub page: usize,
+    pub per_page: usize,
+    pub total: usize,
+    pub items: Vec<RequestLogEntry>,
+}
+
+#[axum::debug_handler]
+pub async fn handler(
+    Extension(request_tracker): Extension<request_tracker::RequestTracker>,
+    Query(request): Query<RequestsRequest>,
+) -> impl IntoResponse {
+    let page = request.page.unwrap_or(1).max(1);
+    let per_page = request
+        .per_page
+        .unwrap_or(DEFAULT_PER_PAGE)
+        .clamp(1, MAX_PER_PAGE);
+
+    let filter = RequestFilter {
+        mirror_id: request.mirror,
+        success: request.success,
+        cache_hit: request.cache_hit,
+        error_kind: request.error_kind,
+        request_id: request.request_id,
+        path: request.path.filter(|path| !path.is_empty()),
+        since: request.since,
+        until: request.until,
+        offset: (page - 1) * per_page,
+        limit: per_page,
+    };
+    let (total, items) = request_tracker.requests(filter).await;
+
+    Json(RequestsResponse {
+        page,
+        per_page,
+        total,
+        items,
+    })
+    .into_response()
+}
@@ -0,0 +1,116 @@
+use axum::{
+    Extension, Json,
+    extract::{Path, Query},
+    response::IntoResponse,
+};
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    MirrorExt,
+    api::mirror::list::ListItem,
+    index::{Cursor, Deletion, Index, ListedTorrent},
+};
+
+const DEFAULT_LIMIT: usize = 100;
+const MAX_LIMIT: usize = 1000;
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct ChangesRequest {
+    /// Cursor returned by a previous request. Starts from the beginning when
+    /// missing.
+    #[serde(default)]
+    pub since: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// A torrent seen in a list, with when it was first seen and last changed.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct ChangedTorrent {
+    #[serde(flatten)]
+    pub item: ListItem,
+    pub info_hash: Option<String>,
+    pub first_seen_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Set once the torrent is found deleted upstream, which counts as a
+    /// change.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deletion: Option<Deletion>,
+}
+
+impl From<&ListedTorrent> for ChangedTorrent {
+    fn from(torrent: &ListedTorrent) -> Self {
+        Self {
+            item: ListItem::from(torrent),
+            info_hash: torrent.info_hash.clone(),
+            first_seen_at: torrent.first_seen_at,
+            updated_at: torrent.updated_at,
+            deletion: torrent.deletion,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct ChangesResponse {
+    pub items: Vec<ChangedTorrent>,
+    /// Cursor to pass as `since` to get later changes.
+    pub next: String,
+    /// More changes are available right away.
+    pub has_more: bool,
+}
+
+/// Lists torrents of a mirror added or changed after a cursor, oldest change
+/// first. Only torrents seen in lists fetched through the mirror are known.
+#[axum::debug_handler]
+pub async fn handler(
+    Extension(mext): Extension<MirrorExt>,
+    Extension(index): Extension<Index>,
+    Path(mirror_id): Path<String>,
+    Query(request): Query<ChangesRequest>,
+) -> impl IntoResponse {
+    let Some(mirror) = mext.find_by_id(&mirror_id) else {
+        tracing::error!("mirror not found");
+        return (
+            axum::http::StatusCode::BAD_REQUEST,
+            "Mirror not found".to_string(),
+        )
+            .into_response();
+    };
+
+    let since = match request.since.as_deref().map(Cursor::decode) {
+        Some(Some(cursor)) => cursor,
+        Some(None) => {
+            return (
+                axum::http::StatusCode::BAD_REQUEST,
+                "Invalid cursor".to_string(),
+            )
+                .into_response();
+        }
+        None => Cursor::default(),
+    };
+    let limit = request.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    match index.changes(mirror.id(), since, limit + 1).await {
+        Ok(mut items) => {
+            let has_more = items.len() > limit;
+            items.truncate(limit);
+            let next = items.last().map_or(since, |torrent| torrent.cursor);
+            Json(ChangesResponse {
+                items: items.iter().map(ChangedTorrent::from).collect(),
+                next: next.encode(),
+                has_more,
+            })
+            .into_response()
+        }
+        Err(err) => {
+            tracing::error!("failed to query index: {:?}", err);
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to query index".to_string(),
+            )
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use axum::{
+    Extension,
+    extract::Path,
+    http::{self, header},
+    response::IntoResponse,
+};
+
+use crate::MirrorExt;
+
+#[axum::debug_handler]
+pub async fn handler(
+    Extension(mext): Extension<MirrorExt>,
+    Path((mirror_id, item_id)): Path<(String, usize)>,
+) -> impl IntoResponse {
+    let Some(mirror) = mext.find_by_id(&mirror_id) else {
+        tracing::error!("mirror not found");
+        return (
+            axum::http::StatusCode::BAD_REQUEST,
+            "Mirror not found".to_string(),
+        )
+            .into_response();
+    };
+
+    match mirror
+        .client
+        .lock()
+        .await
+        .torrent_file(&item_id.to_string())
+        .await
+    {
+        Ok(torrent) => (
+            [
+                (header::CONTENT_TYPE, "application/x-bittorrent".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}.torrent\"", item_id),
+                ),
+            ],
+            torrent,
+        )
+            .into_response(),
+        Err(err) => {
+            tracing::error!("Error fetching torrent file: {:?}", err);
+            (
+                http::StatusCode::BAD_GATEWAY,
+                "Failed to fetch torrent file",
+            )
+                .into_response()
+        }
+    }
+}
@@ -0,0 +1,184 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    Extension, Json,
+    extract::{
+        FromRequestParts, Path, Query, Request,
+        ws::{self, WebSocket, WebSocketUpgrade},
+    },
+    http::{StatusCode, header},
+    response::{
+        IntoResponse,
+        sse::{self, KeepAlive, Sse},
+    },
+};
+use futures_util::{Stream, StreamExt};
+use tokio::sync::broadcast::error::RecvError;
+
+use crate::{
+    MirrorExt,
+    events::{Envelope, EventFilter, Subscription},
+};
+
+use super::list::QueryErrorResponse;
+
+/// Sent instead of events that were lost, telling the client to reload the
+/// list.
+const RESYNC: &str = r#"{"type":"resync"}"#;
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct EventsRequest {
+    #[serde(default)]
+    #[serde(rename = "c")]
+    pub category: Option<String>,
+    /// Search expression in the list query language. Free text is matched
+    /// against titles locally.
+    #[serde(default)]
+    #[serde(rename = "q")]
+    pub query: Option<String>,
+    #[serde(default)]
+    pub trusted: Option<bool>,
+    /// Resume after this event. The `Last-Event-ID` header works too.
+    #[serde(default)]
+    pub last_event_id: Option<u64>,
+}
+
+enum Message {
+    Event(Arc<Envelope>),
+    Resync,
+}
+
+impl Message {
+    fn to_json(&self) -> String {
+        match self {
+            Message::Event(envelope) => {
+                serde_json::to_string(&**envelope).expect("event is serializable")
+            }
+            Message::Resync => RESYNC.to_string(),
+        }
+    }
+}
+
+/// Buffered events followed by live ones, with a resync wherever events
+/// were lost.
+fn messages(subscription: Subscription, filter: EventFilter) -> impl Stream<Item = Message> {
+    let Subscription {
+        backlog,
+        gap,
+        receiver,
+    } = subscription;
+    let buffered = gap
+        .then_some(Message::Resync)
+        .into_iter()
+        .chain(backlog.into_iter().map(Message::Event));
+    let live = futures_util::stream::unfold(receiver, |mut receiver| async move {
+        match receiver.recv().await {
+            Ok(envelope) => Some((Message::Event(envelope), receiver)),
+            Err(RecvError::Lagged(_)) => Some((Message::Resync, receiver)),
+            Err(RecvError::Closed) => None,
+        }
+    });
+    futures_util::stream::iter(buffered)
+        .chain(live)
+        .filter(move |message| {
+            std::future::ready(match message {
+                Message::Event(envelope) => filter.matches(envelope.item()),
+                Message::Resync => true,
+            })
+        })
+}
+
+async fn websocket(mut socket: WebSocket, messages: impl Stream<Item = Message>) {
+    let mut messages = std::pin::pin!(messages);
+    loop {
+        tokio::select! {
+            message = messages.next() => {
+                let Some(message) = message else { break };
+                if socket.send(ws::Message::Text(message.to_json().into())).await.is_err() {
+                    break;
+                }
+            }
+            incoming = socket.recv() => match incoming {
+                // Pings are answered by the library; anything else from the
+                // client is ignored.
+                Some(Ok(ws::Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+#[axum::debug_handler]
+pub async fn handler(
+    Extension(mext): Extension<MirrorExt>,
+    Path(mirror_id): Path<String>,
+    Query(request): Query<EventsRequest>,
+    req: Request,
+) -> impl IntoResponse {
+    let Some(mirror) = mext.find_by_id(&mirror_id) else {
+        tracing::error!("mirror not found");
+        return (StatusCode::BAD_REQUEST, "Mirror not found".to_string()).into_response();
+    };
+
+    let category = match request.category.as_deref().map(str::parse).transpose() {
+        Ok(category) => category.unwrap_or_default(),
+        Err(_) => return (StatusCode::BAD_REQUEST, "Invalid category".to_string()).into_response(),
+    };
+    let expression = match nyaa_parser::dsl::parse(request.query.as_deref().unwrap_or_default()) {
+        Ok(expression) => expression,
+        Err(err) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(QueryErrorResponse {
+                    error: err.message,
+                    start: err.start,
+                    end: err.end,
+                }),
+            )
+                .into_response();
+        }
+    };
+    let filter = EventFilter {
+        category,
+        expression,
+        trusted_only: request.trusted.unwrap_or(false),
+    };
+
+    let (mut parts, _) = req.into_parts();
+    let last_event_id = request.last_event_id.or_else(|| {
+        parts
+            .headers
+            .get("last-event-id")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+    });
+    let subscription = mirror.events.subscribe(last_event_id);
+    let messages = messages(subscription, filter);
+
+    let is_websocket = parts
+        .headers
+        .get(header::UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("websocket"));
+    if is_websocket {
+        return match WebSocketUpgrade::from_request_parts(&mut parts, &()).await {
+            Ok(upgrade) => upgrade
+                .on_upgrade(move |socket| websocket(socket, messages))
+                .into_response(),
+            Err(rejection) => rejection.into_response(),
+        };
+    }
+
+    let events = messages.map(|message| {
+        let event = match &message {
+            Message::Event(envelope) => sse::Event::default()
+                .id(envelope.id.to_string())
+                .event(envelope.name()),
+            Message::Resync => sse::Event::default().event("resync"),
+        };
+        Ok::<_, Infallible>(event.data(message.to_json()))
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
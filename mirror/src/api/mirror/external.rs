@@ -0,0 +1,60 @@
+use axum::{Extension, Json, extract::Path, response::IntoResponse};
+use nyaa_parser::ExternalSource;
+
+use crate::{
+    MirrorExt,
+    index::{Index, IndexedTorrent},
+};
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct ExternalResponse {
+    pub source: ExternalSource,
+    pub id: String,
+    pub items: Vec<IndexedTorrent>,
+}
+
+/// Lists the indexed torrents of a mirror linking to an entry of an external
+/// database. Only torrents whose view page was fetched are indexed.
+#[axum::debug_handler]
+pub async fn handler(
+    Extension(mext): Extension<MirrorExt>,
+    Extension(index): Extension<Index>,
+    Path((mirror_id, source, external_id)): Path<(String, String, String)>,
+) -> impl IntoResponse {
+    let Some(mirror) = mext.find_by_id(&mirror_id) else {
+        tracing::error!("mirror not found");
+        return (
+            axum::http::StatusCode::BAD_REQUEST,
+            "Mirror not found".to_string(),
+        )
+            .into_response();
+    };
+
+    let Ok(source) = source.parse::<ExternalSource>() else {
+        return (
+            axum::http::StatusCode::BAD_REQUEST,
+            format!("Unknown external source: {}", source),
+        )
+            .into_response();
+    };
+
+    match index
+        .find_by_external_id(mirror.id(), source, &external_id)
+        .await
+    {
+        Ok(items) => Json(ExternalResponse {
+            source,
+            id: external_id,
+            items,
+        })
+        .into_response(),
+        Err(err) => {
+            tracing::error!("failed to query index: {:?}", err);
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to query index".to_string(),
+            )
+                .into_response()
+        }
+    }
+}
@@ -4,7 +4,9 @@ use axum::{
     response::IntoResponse,
 };
 
-use crate::{MirrorExt, client::ListQuery};
+use nyaa_parser::SearchQuery;
+
+use crate::{MirrorExt, scrape::Scraper};
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct ListItem {
@@ -20,85 +22,113 @@ pub struct ListItem {
     pub comments: usize,
     pub trusted: bool,
     pub remake: bool,
+    /// Set when the counts were replaced with ones scraped from trackers.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scraped_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
-#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
 pub struct ListRequest {
     #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "p")]
     pub page: Option<usize>,
     #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "c")]
     pub category: Option<String>,
     #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "s")]
     pub sort: Option<String>,
     #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "o")]
     pub order: Option<String>,
     #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "f")]
     pub filter: Option<String>,
     #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "q")]
     pub query: Option<String>,
 }
 
+/// Parses an optional request parameter, falling back to the default with a
+/// warning when it is invalid.
+fn parse_or_default<T>(value: Option<&str>, name: &str) -> T
+where
+    T: std::str::FromStr + Default,
+{
+    match value.map(str::parse) {
+        Some(Ok(value)) => value,
+        Some(Err(_)) => {
+            tracing::warn!("invalid {} option, defaulting to None", name);
+            T::default()
+        }
+        None => T::default(),
+    }
+}
+
 impl ListRequest {
-    pub fn validate(self) -> Self {
+    pub fn into_query(self) -> SearchQuery {
         let page = match self.page {
-            Some(page) if page > 0 => Some(page),
-            None => None,
-            _ => {
+            Some(0) => {
                 tracing::warn!("invalid page number, defaulting to 1");
-                Some(1)
-            }
-        };
-        let category = self.category;
-        let sort = match self.sort {
-            Some(sort)
-                if ["id", "size", "seeders", "leechers", "downloads", "comments"]
-                    .contains(&sort.as_str()) =>
-            {
-                Some(sort)
-            }
-            None => None,
-            _ => {
-                tracing::warn!("invalid sort option, defaulting to None");
-                None
-            }
-        };
-        let order = match self.order {
-            Some(order) if ["asc", "desc"].contains(&order.as_str()) => Some(order),
-            None => None,
-            _ => {
-                tracing::warn!("invalid order option, defaulting to None");
-                None
-            }
-        };
-        let filter = match self.filter {
-            Some(filter) if ["0", "1", "2"].contains(&filter.as_str()) => Some(filter),
-            None => None,
-            _ => {
-                tracing::warn!("invalid filter option, defaulting to None");
-                None
-            }
-        };
-        let query = match self.query {
-            Some(query) if !query.is_empty() => Some(query),
-            None => None,
-            _ => {
-                tracing::warn!("invalid query, defaulting to None");
-                None
+                1
             }
+            page => page.unwrap_or(1),
         };
+        SearchQuery::new()
+            .page(page)
+            .category(parse_or_default(self.category.as_deref(), "category"))
+            .sort(
+                parse_or_default(self.sort.as_deref(), "sort"),
+                parse_or_default(self.order.as_deref(), "order"),
+            )
+            .filter(parse_or_default(self.filter.as_deref(), "filter"))
+            .term(self.query.unwrap_or_default())
+    }
+}
+
+impl From<&nyaa_parser::ListItem> for ListItem {
+    fn from(item: &nyaa_parser::ListItem) -> Self {
+        Self {
+            id: item.id,
+            title: item.title.clone(),
+            pub_date: item.pub_date,
+            description: item.description.clone(),
+            category: item.category.clone(),
+            size: item.size,
+            seeders: item.seeders,
+            leechers: item.leechers,
+            downloads: item.downloads,
+            comments: item.comments,
+            trusted: item.trusted,
+            remake: item.remake,
+            scraped_at: None,
+        }
+    }
+}
+
+impl From<&crate::index::ListedTorrent> for ListItem {
+    fn from(torrent: &crate::index::ListedTorrent) -> Self {
         Self {
-            page,
-            category,
-            sort,
-            order,
-            filter,
-            query,
+            id: torrent.id,
+            title: torrent.title.clone(),
+            pub_date: torrent.pub_date,
+            description: torrent.description.clone(),
+            category: torrent.category.clone(),
+            size: torrent.size,
+            seeders: torrent.seeders,
+            leechers: torrent.leechers,
+            downloads: torrent.downloads,
+            comments: torrent.comments,
+            trusted: torrent.trusted,
+            remake: torrent.remake,
+            scraped_at: None,
         }
     }
 }
@@ -106,24 +136,49 @@ impl ListRequest {
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct ListResponse {
     pub items: Vec<ListItem>,
+    /// Set when the query had predicates applied by the mirror.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_filter: Option<PostFilter>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct PostFilter {
+    pub upstream_pages: usize,
+    /// The page limit was hit, so later pages may still hold matches.
+    pub truncated: bool,
+}
+
+/// A query syntax error, with the character range it applies to.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct QueryErrorResponse {
+    pub error: String,
+    pub start: usize,
+    pub end: usize,
 }
 
 #[axum::debug_handler]
 pub async fn handler(
     Extension(mext): Extension<MirrorExt>,
+    Extension(scraper): Extension<Scraper>,
     Path(mirror_id): Path<String>,
     Query(request): Query<ListRequest>,
 ) -> impl IntoResponse {
-    let request = request.validate();
-    let query = ListQuery {
-        page: request.page,
-        category: request.category,
-        sort: request.sort,
-        order: request.order,
-        filter: request.filter,
-        query: request.query,
-    }
-    .remove_defaults();
+    let expression = match nyaa_parser::dsl::parse(request.query.as_deref().unwrap_or_default()) {
+        Ok(expression) => expression,
+        Err(err) => {
+            return (
+                axum::http::StatusCode::BAD_REQUEST,
+                Json(QueryErrorResponse {
+                    error: err.message,
+                    start: err.start,
+                    end: err.end,
+                }),
+            )
+                .into_response();
+        }
+    };
+    let query = request.into_query().term(expression.text.clone());
 
     let Some(mirror) = mext.find_by_id(&mirror_id) else {
         tracing::error!("mirror not found");
@@ -134,27 +189,38 @@ pub async fn handler(
             .into_response();
     };
 
-    match mirror.client.lock().await.list(&query).await {
-        Ok(items) => {
-            let items = items
-                .iter()
-                .map(|item| ListItem {
-                    id: item.id,
-                    title: item.title.clone(),
-                    pub_date: item.pub_date,
-                    description: item.description.clone(),
-                    category: item.category.clone(),
-                    size: item.size,
-                    seeders: item.seeders,
-                    leechers: item.leechers,
-                    downloads: item.downloads,
-                    comments: item.comments,
-                    trusted: item.trusted,
-                    remake: item.remake,
-                })
-                .collect::<Vec<_>>();
-
-            let response = ListResponse { items };
+    let client = mirror.client.lock().await;
+    let result = if expression.has_predicates() {
+        client.list_filtered(&query, &expression).await.map(|list| {
+            let post_filter = PostFilter {
+                upstream_pages: list.upstream_pages,
+                truncated: list.truncated,
+            };
+            (list.items, Some(post_filter))
+        })
+    } else {
+        client.list(&query).await.map(|items| (items, None))
+    };
+    drop(client);
+
+    match result {
+        Ok((sources, post_filter)) => {
+            let mut items = sources.iter().map(ListItem::from).collect::<Vec<_>>();
+            if mirror.scrape_overlay() {
+                let scraped = scraper
+                    .scrape_list(&sources, mirror.scrape_trackers())
+                    .await;
+                for (item, stats) in items.iter_mut().zip(scraped) {
+                    if let Some(stats) = stats {
+                        item.seeders = stats.seeders;
+                        item.leechers = stats.leechers;
+                        item.downloads = stats.downloads;
+                        item.scraped_at = Some(stats.scraped_at);
+                    }
+                }
+            }
+
+            let response = ListResponse { items, post_filter };
             Json(response).into_response()
         }
         Err(err) => {
@@ -2,8 +2,17 @@ use axum::{Extension, Json, response::IntoResponse};
 
 use crate::{MirrorExt, cli::MirrorType};
 
+pub mod changes;
+pub mod download;
+pub mod events;
+pub mod external;
 pub mod list;
 pub mod magnet;
+pub mod send;
+pub mod stats;
+pub mod stream;
+pub mod trending;
+pub mod user;
 pub mod view;
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
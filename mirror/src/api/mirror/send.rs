@@ -0,0 +1,84 @@
+use axum::{
+    Extension, Json,
+    extract::Path,
+    http::{self, StatusCode},
+    response::IntoResponse,
+};
+
+use crate::{
+    MirrorExt,
+    download_client::{DownloadClients, Source, Torrent},
+};
+
+#[derive(Debug, Clone, Default, serde::Deserialize)]
+pub struct SendRequest {
+    /// Download client id; defaults to the first configured client.
+    pub client: Option<String>,
+    pub category: Option<String>,
+    pub save_path: Option<String>,
+    pub paused: Option<bool>,
+    #[serde(default)]
+    pub source: Source,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct SendResponse {
+    pub client: String,
+    pub source: Source,
+}
+
+#[axum::debug_handler]
+pub async fn handler(
+    Extension(mext): Extension<MirrorExt>,
+    Extension(clients): Extension<DownloadClients>,
+    Path((mirror_id, item_id)): Path<(String, usize)>,
+    Json(request): Json<SendRequest>,
+) -> impl IntoResponse {
+    let Some(mirror) = mext.find_by_id(&mirror_id) else {
+        tracing::error!("mirror not found");
+        return (StatusCode::BAD_REQUEST, "Mirror not found".to_string()).into_response();
+    };
+    let Some(download_client) = clients.find(request.client.as_deref()) else {
+        return (
+            StatusCode::BAD_REQUEST,
+            "Download client not found".to_string(),
+        )
+            .into_response();
+    };
+
+    let item_id = item_id.to_string();
+    let fetched = Torrent::fetch(&*mirror.client.lock().await, &item_id, request.source).await;
+    let (torrent, source) = match fetched {
+        Ok(fetched) => fetched,
+        Err(err) => {
+            tracing::error!("Error fetching torrent file: {:?}", err);
+            return (
+                http::StatusCode::BAD_GATEWAY,
+                "Failed to fetch torrent".to_string(),
+            )
+                .into_response();
+        }
+    };
+
+    let options = download_client.options(request.category, request.save_path, request.paused);
+    if let Err(err) = download_client.add(&torrent, &options).await {
+        tracing::error!(
+            "failed to send {} to {}: {:#}",
+            item_id,
+            download_client.id(),
+            err
+        );
+        return (
+            StatusCode::BAD_GATEWAY,
+            format!("Failed to add torrent: {:#}", err),
+        )
+            .into_response();
+    }
+    tracing::info!("sent {} to {}", item_id, download_client.id());
+
+    Json(SendResponse {
+        client: download_client.id().to_string(),
+        source,
+    })
+    .into_response()
+}
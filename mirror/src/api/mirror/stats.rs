@@ -0,0 +1,108 @@
+use std::time::Duration;
+
+use axum::{
+    Extension, Json,
+    extract::{Path, Query},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+
+use crate::{
+    MirrorExt,
+    index::{Index, SwarmSample},
+};
+
+const DEFAULT_POINTS: usize = 200;
+const MAX_POINTS: usize = 1000;
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct StatsRequest {
+    /// Start of the range. Defaults to the first observation.
+    #[serde(default)]
+    pub from: Option<DateTime<Utc>>,
+    /// End of the range. Defaults to now.
+    #[serde(default)]
+    pub until: Option<DateTime<Utc>>,
+    /// Bucket size to downsample to, such as `1h`. Derived from `points`
+    /// when missing.
+    #[serde(with = "humantime_serde")]
+    #[serde(default)]
+    pub resolution: Option<Duration>,
+    /// Maximum number of buckets when no resolution is given.
+    #[serde(default)]
+    pub points: Option<usize>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct StatsResponse {
+    pub id: usize,
+    /// Bucket size in seconds.
+    pub resolution: u64,
+    pub items: Vec<SwarmSample>,
+}
+
+fn index_error(err: rusqlite::Error) -> axum::response::Response {
+    tracing::error!("failed to query index: {:?}", err);
+    (
+        axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+        "Failed to query index".to_string(),
+    )
+        .into_response()
+}
+
+/// Seeders, leechers and downloads of a torrent over time, as observed in
+/// lists and views fetched through the mirror.
+#[axum::debug_handler]
+pub async fn handler(
+    Extension(mext): Extension<MirrorExt>,
+    Extension(index): Extension<Index>,
+    Path((mirror_id, item_id)): Path<(String, usize)>,
+    Query(request): Query<StatsRequest>,
+) -> impl IntoResponse {
+    let Some(mirror) = mext.find_by_id(&mirror_id) else {
+        tracing::error!("mirror not found");
+        return (
+            axum::http::StatusCode::BAD_REQUEST,
+            "Mirror not found".to_string(),
+        )
+            .into_response();
+    };
+
+    let until = request.until.unwrap_or_else(Utc::now);
+    let from = match request.from {
+        Some(from) => from,
+        None => match index.first_swarm_sample(mirror.id(), item_id).await {
+            Ok(first) => first.unwrap_or(until),
+            Err(err) => return index_error(err),
+        },
+    };
+    if from > until {
+        return (
+            axum::http::StatusCode::BAD_REQUEST,
+            "Range starts after it ends".to_string(),
+        )
+            .into_response();
+    }
+    let resolution = request.resolution.unwrap_or_else(|| {
+        let points = request
+            .points
+            .unwrap_or(DEFAULT_POINTS)
+            .clamp(1, MAX_POINTS) as u64;
+        let span = (until - from).num_seconds().max(0) as u64;
+        Duration::from_secs(span.div_ceil(points))
+    });
+    let resolution = resolution.max(Duration::from_secs(1));
+
+    match index
+        .swarm_stats(mirror.id(), item_id, from, until, resolution)
+        .await
+    {
+        Ok(items) => Json(StatsResponse {
+            id: item_id,
+            resolution: resolution.as_secs(),
+            items,
+        })
+        .into_response(),
+        Err(err) => index_error(err),
+    }
+}
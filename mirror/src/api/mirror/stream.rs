@@ -0,0 +1,124 @@
+use std::convert::Infallible;
+
+use axum::{
+    Extension, Json,
+    body::Body,
+    extract::{Path, Query},
+    http::header,
+    response::IntoResponse,
+};
+use futures_util::StreamExt;
+
+use crate::{
+    MirrorExt,
+    client::{Client, PageEvent, PageStreamOptions, StopReason},
+};
+
+use super::list::{ListItem, ListRequest, QueryErrorResponse};
+
+const DEFAULT_MAX_PAGES: usize = 10;
+const MAX_PAGES: usize = 100;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct StreamRequest {
+    #[serde(default)]
+    pub max_pages: Option<usize>,
+}
+
+/// One line of the NDJSON response.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StreamLine {
+    Page {
+        page: usize,
+        items: Vec<ListItem>,
+        duplicates: usize,
+    },
+    End {
+        reason: StopReason,
+        pages: usize,
+    },
+    Error {
+        error: String,
+    },
+}
+
+impl From<anyhow::Result<PageEvent>> for StreamLine {
+    fn from(event: anyhow::Result<PageEvent>) -> Self {
+        match event {
+            Ok(PageEvent::Page {
+                page,
+                items,
+                duplicates,
+            }) => StreamLine::Page {
+                page,
+                items: items.iter().map(ListItem::from).collect(),
+                duplicates,
+            },
+            Ok(PageEvent::End { reason, pages }) => StreamLine::End { reason, pages },
+            Err(err) => {
+                tracing::error!("failed to stream mirror list: {:?}", err);
+                StreamLine::Error {
+                    error: format!("Failed to fetch mirror list: {:?}", err),
+                }
+            }
+        }
+    }
+}
+
+/// Streams every page of a list query as newline-delimited JSON: one `page`
+/// line per upstream page, then an `end` line with the reason for stopping,
+/// or an `error` line.
+#[axum::debug_handler]
+pub async fn handler(
+    Extension(mext): Extension<MirrorExt>,
+    Path(mirror_id): Path<String>,
+    Query(request): Query<ListRequest>,
+    Query(stream_request): Query<StreamRequest>,
+) -> impl IntoResponse {
+    let expression = match nyaa_parser::dsl::parse(request.query.as_deref().unwrap_or_default()) {
+        Ok(expression) => expression,
+        Err(err) => {
+            return (
+                axum::http::StatusCode::BAD_REQUEST,
+                Json(QueryErrorResponse {
+                    error: err.message,
+                    start: err.start,
+                    end: err.end,
+                }),
+            )
+                .into_response();
+        }
+    };
+    let query = request.into_query().term(expression.text.clone());
+
+    let Some(mirror) = mext.find_by_id(&mirror_id) else {
+        tracing::error!("mirror not found");
+        return (
+            axum::http::StatusCode::BAD_REQUEST,
+            "Mirror not found".to_string(),
+        )
+            .into_response();
+    };
+
+    let options = PageStreamOptions {
+        max_pages: stream_request
+            .max_pages
+            .unwrap_or(DEFAULT_MAX_PAGES)
+            .clamp(1, MAX_PAGES),
+        expression: Some(expression).filter(|expression| expression.has_predicates()),
+        ..Default::default()
+    };
+    let lines = Client::pages(mirror.client.clone(), query, options).map(|event| {
+        let mut line =
+            serde_json::to_vec(&StreamLine::from(event)).expect("stream lines serialize to JSON");
+        line.push(b'\n');
+        Ok::<_, Infallible>(line)
+    });
+
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(lines),
+    )
+        .into_response()
+}
@@ -0,0 +1,143 @@
+use axum::{
+    Extension, Json,
+    extract::{Path, Query},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+
+use crate::{
+    MirrorExt,
+    api::mirror::list::ListItem,
+    index::{Index, SwarmGrowth},
+    trending::{Metric, Trending, Window},
+};
+
+const DEFAULT_LIMIT: usize = 75;
+const MAX_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct TrendingRequest {
+    #[serde(default)]
+    pub window: Window,
+    #[serde(default)]
+    pub by: Metric,
+    #[serde(default)]
+    #[serde(rename = "c")]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub trusted: Option<bool>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// A torrent with how much its swarm grew over the window.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct TrendingItem {
+    #[serde(flatten)]
+    pub item: ListItem,
+    pub info_hash: Option<String>,
+    pub seeder_growth: i64,
+    pub download_growth: i64,
+    /// Start of the window, which is later than requested for torrents
+    /// first seen during it.
+    pub observed_since: DateTime<Utc>,
+}
+
+impl From<&SwarmGrowth> for TrendingItem {
+    fn from(growth: &SwarmGrowth) -> Self {
+        Self {
+            item: ListItem::from(&growth.torrent),
+            info_hash: growth.torrent.info_hash.clone(),
+            seeder_growth: growth.seeder_growth,
+            download_growth: growth.download_growth,
+            observed_since: growth.observed_since,
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct TrendingResponse {
+    pub window: Window,
+    pub by: Metric,
+    pub computed_at: DateTime<Utc>,
+    pub items: Vec<TrendingItem>,
+}
+
+/// Ranks torrents of a mirror by how much their downloads or seeders grew
+/// over a window. Only torrents seen repeatedly in lists or views fetched
+/// through the mirror are ranked.
+#[axum::debug_handler]
+pub async fn handler(
+    Extension(mext): Extension<MirrorExt>,
+    Extension(index): Extension<Index>,
+    Extension(trending): Extension<Trending>,
+    Path(mirror_id): Path<String>,
+    Query(request): Query<TrendingRequest>,
+) -> impl IntoResponse {
+    let Some(mirror) = mext.find_by_id(&mirror_id) else {
+        tracing::error!("mirror not found");
+        return (
+            axum::http::StatusCode::BAD_REQUEST,
+            "Mirror not found".to_string(),
+        )
+            .into_response();
+    };
+
+    let category = match request
+        .category
+        .as_deref()
+        .map(str::parse::<nyaa_parser::Category>)
+        .transpose()
+    {
+        Ok(category) => category.unwrap_or_default(),
+        Err(_) => {
+            return (
+                axum::http::StatusCode::BAD_REQUEST,
+                "Invalid category".to_string(),
+            )
+                .into_response();
+        }
+    };
+    let trusted_only = request.trusted.unwrap_or(false);
+    let limit = request.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+
+    match trending
+        .ranking(&index, mirror.id(), request.window, request.by)
+        .await
+    {
+        Ok(ranking) => {
+            // Filtering before the limit keeps filtered requests full, as
+            // the cached ranking holds every torrent that grew.
+            let items = ranking
+                .items
+                .iter()
+                .filter(|growth| !trusted_only || growth.torrent.trusted)
+                .filter(|growth| {
+                    category.is_all()
+                        || growth
+                            .torrent
+                            .category
+                            .parse()
+                            .is_ok_and(|c| category.contains(&c))
+                })
+                .take(limit)
+                .map(TrendingItem::from)
+                .collect();
+            Json(TrendingResponse {
+                window: request.window,
+                by: request.by,
+                computed_at: ranking.computed_at,
+                items,
+            })
+            .into_response()
+        }
+        Err(err) => {
+            tracing::error!("failed to query index: {:?}", err);
+            (
+                axum::http::StatusCode::INTERNAL_SERVER_ERROR,
+                "Failed to query index".to_string(),
+            )
+                .into_response()
+        }
+    }
+}
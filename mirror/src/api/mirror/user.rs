@@ -0,0 +1,69 @@
+use axum::{
+    Extension, Json,
+    extract::{Path, Query},
+    response::IntoResponse,
+};
+
+use crate::MirrorExt;
+
+use super::list::{ListItem, ListRequest};
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct UserProfile {
+    pub name: String,
+    pub level: Option<String>,
+    pub trusted: bool,
+    pub badges: Vec<String>,
+    pub avatar: Option<String>,
+    pub torrent_count: Option<usize>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct UserResponse {
+    pub user: UserProfile,
+    pub items: Vec<ListItem>,
+}
+
+#[axum::debug_handler]
+pub async fn handler(
+    Extension(mext): Extension<MirrorExt>,
+    Path((mirror_id, name)): Path<(String, String)>,
+    Query(request): Query<ListRequest>,
+) -> impl IntoResponse {
+    let query = request.into_query();
+
+    let Some(mirror) = mext.find_by_id(&mirror_id) else {
+        tracing::error!("mirror not found");
+        return (
+            axum::http::StatusCode::BAD_REQUEST,
+            "Mirror not found".to_string(),
+        )
+            .into_response();
+    };
+
+    match mirror.client.lock().await.user(&name, &query).await {
+        Ok(page) => {
+            let user = page.user;
+            let response = UserResponse {
+                user: UserProfile {
+                    name: user.name,
+                    level: user.level,
+                    trusted: user.trusted,
+                    badges: user.badges,
+                    avatar: user.avatar,
+                    torrent_count: user.torrent_count,
+                },
+                items: page.items.iter().map(ListItem::from).collect(),
+            };
+            Json(response).into_response()
+        }
+        Err(err) => {
+            tracing::error!("failed to fetch mirror user: {:?}", err);
+            (
+                axum::http::StatusCode::BAD_REQUEST,
+                format!("Failed to fetch mirror user: {:?}", err),
+            )
+                .into_response()
+        }
+    }
+}
@@ -1,6 +1,43 @@
-use axum::{Extension, Json, extract::Path, response::IntoResponse};
+use axum::{
+    Extension, Json,
+    extract::{Path, Query},
+    response::IntoResponse,
+};
 
-use crate::MirrorExt;
+use crate::{
+    MirrorExt,
+    index::{ArchivedView, Index},
+    scrape::{Scraper, Target},
+};
+
+/// Format of the description and comments in a view response.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentFormat {
+    /// Markdown as stored by nyaa.
+    #[default]
+    Md,
+    /// Markdown rendered to sanitized HTML.
+    Html,
+    /// Markdown rendered to plain text.
+    Text,
+}
+
+impl ContentFormat {
+    pub fn render(&self, markdown: &str) -> String {
+        match self {
+            ContentFormat::Md => markdown.to_string(),
+            ContentFormat::Html => nyaa_parser::markdown::to_html(markdown),
+            ContentFormat::Text => nyaa_parser::markdown::to_text(markdown),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct ViewRequest {
+    #[serde(default)]
+    pub format: ContentFormat,
+}
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct ViewComment {
@@ -16,31 +53,90 @@ pub struct ViewComment {
 pub struct ViewFile {
     pub id: usize,
     pub name: String,
+    pub path: String,
     pub size: u64,
 }
 
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum ViewFileNode {
+    Folder {
+        name: String,
+        children: Vec<ViewFileNode>,
+    },
+    File {
+        name: String,
+        size: u64,
+    },
+}
+
+impl From<&nyaa_parser::FileNode> for ViewFileNode {
+    fn from(node: &nyaa_parser::FileNode) -> Self {
+        match node {
+            nyaa_parser::FileNode::Folder { name, children } => ViewFileNode::Folder {
+                name: name.clone(),
+                children: children.iter().map(ViewFileNode::from).collect(),
+            },
+            nyaa_parser::FileNode::File { name, size } => ViewFileNode::File {
+                name: name.clone(),
+                size: *size,
+            },
+        }
+    }
+}
+
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct ViewResponse {
     pub id: usize,
     pub title: String,
     pub pub_date: chrono::DateTime<chrono::Utc>,
+    /// Description as markdown, whatever the requested format.
     pub description_md: String,
+    /// Format of `description` and of the comment contents.
+    pub format: ContentFormat,
+    pub description: String,
     pub category: String,
     pub size: u64,
     pub seeders: usize,
     pub leechers: usize,
     pub downloads: usize,
+    /// Set when the counts were replaced with ones scraped from trackers.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scraped_at: Option<chrono::DateTime<chrono::Utc>>,
     pub trusted: bool,
     pub remake: bool,
     pub magnet_link: Option<String>,
+    pub submitter: String,
     pub comments: Vec<ViewComment>,
+    /// Flat list of the files in `file_tree`.
     pub files: Vec<ViewFile>,
+    pub file_tree: Vec<ViewFileNode>,
+    pub information: Option<String>,
+    pub external_ids: nyaa_parser::ExternalIds,
+    /// The torrent was deleted upstream.
+    pub deleted: bool,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Set when the upstream could not serve the view, so it comes from the
+    /// archive as of this time.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archived_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Why the upstream could not serve the view, set with `archived_at`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upstream_error: Option<String>,
 }
 
 #[axum::debug_handler]
 pub async fn handler(
     Extension(mext): Extension<MirrorExt>,
+    Extension(scraper): Extension<Scraper>,
+    Extension(index): Extension<Index>,
     Path((mirror_id, item_id)): Path<(String, String)>,
+    Query(request): Query<ViewRequest>,
 ) -> impl IntoResponse {
     let Some(mirror) = mext.find_by_id(&mirror_id) else {
         tracing::error!("mirror not found");
@@ -51,21 +147,73 @@ pub async fn handler(
             .into_response();
     };
 
-    match mirror.client.lock().await.view(&item_id).await {
-        Ok(item) => {
+    let result = mirror.client.lock().await.view(&item_id).await;
+    // Views the upstream cannot serve, usually because the torrent was
+    // deleted, come from the archive. The upstream error goes along, so
+    // clients can tell a deletion from an outage.
+    let result = match result {
+        Ok(item) => Ok((item, None)),
+        Err(err) => {
+            let archived = match item_id.parse() {
+                Ok(id) => index.archived_view(mirror.id(), id).await,
+                Err(_) => Ok(None),
+            };
+            match archived {
+                Ok(Some(ArchivedView {
+                    view,
+                    archived_at,
+                    deletion,
+                })) => {
+                    let err = format!("{:#}", err);
+                    tracing::warn!("serving archived view {}: {}", item_id, err);
+                    Ok((view, Some((archived_at, deletion, err))))
+                }
+                Ok(None) => Err(err),
+                Err(e) => {
+                    tracing::error!("failed to query index: {:?}", e);
+                    Err(err)
+                }
+            }
+        }
+    };
+    match result {
+        Ok((mut item, archived)) => {
+            let (archived_at, deletion, upstream_error) = match archived {
+                Some((archived_at, deletion, err)) => (Some(archived_at), deletion, Some(err)),
+                None => (None, None, None),
+            };
+            let mut scraped_at = None;
+            if mirror.scrape_overlay()
+                && let Some(target) = Target::new(
+                    Some(&item.info_hash),
+                    item.magnet_link.as_deref(),
+                    mirror.scrape_trackers(),
+                )
+                && let Some(stats) = scraper.scrape(&[target]).await.into_values().next()
+            {
+                item.seeders = stats.seeders;
+                item.leechers = stats.leechers;
+                item.downloads = stats.downloads;
+                scraped_at = Some(stats.scraped_at);
+            }
+            let files = item.files();
             let response = ViewResponse {
                 id: item.id,
                 title: item.title,
                 pub_date: item.pub_date,
+                format: request.format,
+                description: request.format.render(&item.description_md),
                 description_md: item.description_md,
                 category: item.category,
                 size: item.size,
                 seeders: item.seeders,
                 leechers: item.leechers,
                 downloads: item.downloads,
+                scraped_at,
                 trusted: item.trusted,
                 remake: item.remake,
                 magnet_link: item.magnet_link,
+                submitter: item.submitter,
                 comments: item
                     .comments
                     .iter()
@@ -74,19 +222,26 @@ pub async fn handler(
                         user: comment.user.clone(),
                         date: comment.date,
                         edited_date: comment.edited_date,
-                        content: comment.content.clone(),
+                        content: request.format.render(&comment.content),
                         avatar: comment.avatar.clone(),
                     })
                     .collect(),
-                files: item
-                    .files
-                    .iter()
+                files: files
+                    .into_iter()
                     .map(|file| ViewFile {
                         id: file.id,
-                        name: file.name.clone(),
+                        name: file.name,
+                        path: file.path,
                         size: file.size,
                     })
                     .collect(),
+                file_tree: item.file_tree.iter().map(ViewFileNode::from).collect(),
+                information: item.information,
+                external_ids: item.external_ids,
+                deleted: deletion.is_some(),
+                deleted_at: deletion.map(|deletion| deletion.deleted_at),
+                archived_at,
+                upstream_error,
             };
 
             Json(response).into_response()
@@ -1,2 +1,6 @@
-pub mod mirror;
+pub mod auto_download;
+pub mod feed;
 pub mod health;
+pub mod mirror;
+pub mod saved_search;
+pub mod search;
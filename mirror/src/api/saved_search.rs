@@ -0,0 +1,116 @@
+use axum::{Extension, Json, extract::Path, http::StatusCode, response::IntoResponse};
+
+use crate::{
+    MirrorExt,
+    api::mirror::list::{ListRequest, QueryErrorResponse},
+    saved_search::{self, NewSavedSearch, SavedSearch, SavedSearches},
+};
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct CreateRequest {
+    pub mirror: String,
+    pub name: String,
+    #[serde(default)]
+    pub query: Option<ListRequest>,
+    /// How often to check, such as `15m`. Defaults to 15 minutes.
+    #[serde(with = "humantime_serde")]
+    #[serde(default)]
+    pub interval: Option<std::time::Duration>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct SavedSearchesResponse {
+    pub items: Vec<SavedSearch>,
+}
+
+fn database_error(err: rusqlite::Error) -> axum::response::Response {
+    tracing::error!("saved search database error: {}", err);
+    (
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Saved search database error".to_string(),
+    )
+        .into_response()
+}
+
+#[axum::debug_handler]
+pub async fn list(Extension(searches): Extension<SavedSearches>) -> impl IntoResponse {
+    match searches.list().await {
+        Ok(items) => Json(SavedSearchesResponse { items }).into_response(),
+        Err(err) => database_error(err),
+    }
+}
+
+#[axum::debug_handler]
+pub async fn create(
+    Extension(mext): Extension<MirrorExt>,
+    Extension(searches): Extension<SavedSearches>,
+    Json(request): Json<CreateRequest>,
+) -> impl IntoResponse {
+    if mext.find_by_id(&request.mirror).is_none() {
+        return (StatusCode::BAD_REQUEST, "Mirror not found".to_string()).into_response();
+    }
+    let name = request.name.trim().to_string();
+    if name.is_empty() {
+        return (StatusCode::BAD_REQUEST, "Name is required".to_string()).into_response();
+    }
+    let interval = request.interval.unwrap_or(saved_search::DEFAULT_INTERVAL);
+    if interval < saved_search::MIN_INTERVAL {
+        return (
+            StatusCode::BAD_REQUEST,
+            format!(
+                "Interval must be at least {} seconds",
+                saved_search::MIN_INTERVAL.as_secs()
+            ),
+        )
+            .into_response();
+    }
+    let query = request.query.unwrap_or_default();
+    if let Err(err) = nyaa_parser::dsl::parse(query.query.as_deref().unwrap_or_default()) {
+        return (
+            StatusCode::BAD_REQUEST,
+            Json(QueryErrorResponse {
+                error: err.message,
+                start: err.start,
+                end: err.end,
+            }),
+        )
+            .into_response();
+    }
+
+    match searches
+        .create(NewSavedSearch {
+            mirror_id: request.mirror,
+            name,
+            query,
+            interval,
+        })
+        .await
+    {
+        Ok(search) => (StatusCode::CREATED, Json(search)).into_response(),
+        Err(err) => database_error(err),
+    }
+}
+
+#[axum::debug_handler]
+pub async fn get(
+    Extension(searches): Extension<SavedSearches>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match searches.get(id).await {
+        Ok(Some(search)) => Json(search).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, "Saved search not found".to_string()).into_response(),
+        Err(err) => database_error(err),
+    }
+}
+
+#[axum::debug_handler]
+pub async fn delete(
+    Extension(searches): Extension<SavedSearches>,
+    Path(id): Path<i64>,
+) -> impl IntoResponse {
+    match searches.delete(id).await {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => (StatusCode::NOT_FOUND, "Saved search not found".to_string()).into_response(),
+        Err(err) => database_error(err),
+    }
+}
@@ -0,0 +1,279 @@
+use std::collections::HashMap;
+use std::time::Instant;
+
+use axum::{Extension, Json, extract::Query, response::IntoResponse};
+use futures_util::future::join_all;
+use nyaa_parser::{Order, Sort};
+
+use crate::{
+    Mirror, MirrorExt,
+    api::mirror::list::{ListItem, ListRequest, QueryErrorResponse},
+};
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct SearchRequest {
+    /// Comma-separated mirror ids. Defaults to every mirror that is not
+    /// hidden.
+    #[serde(default)]
+    pub mirrors: Option<String>,
+}
+
+/// Where a hit was found.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct SearchSource {
+    pub mirror: String,
+    pub id: usize,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct SearchHit {
+    #[serde(flatten)]
+    pub item: ListItem,
+    pub info_hash: Option<String>,
+    pub sources: Vec<SearchSource>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct MirrorResult {
+    pub mirror: String,
+    pub ok: bool,
+    pub error: Option<String>,
+    pub item_count: usize,
+    /// Time spent on this mirror, in seconds.
+    pub elapsed: f64,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct SearchResponse {
+    pub items: Vec<SearchHit>,
+    pub mirrors: Vec<MirrorResult>,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum DedupeKey {
+    InfoHash(String),
+    TitleSize(String, u64),
+}
+
+/// Merges per-mirror results, in mirror order. Duplicates keep the first
+/// copy's fields and gain the later copies' sources.
+fn merge(
+    results: Vec<(String, Vec<nyaa_parser::ListItem>)>,
+    sort: Sort,
+    order: Order,
+) -> Vec<SearchHit> {
+    let mut hits: Vec<SearchHit> = Vec::new();
+    let mut seen = HashMap::new();
+    for (mirror, items) in results {
+        for item in items {
+            let info_hash = item.btih();
+            let key = match &info_hash {
+                Some(hash) => DedupeKey::InfoHash(hash.clone()),
+                None => DedupeKey::TitleSize(item.title.trim().to_lowercase(), item.size),
+            };
+            let source = SearchSource {
+                mirror: mirror.clone(),
+                id: item.id,
+            };
+            if let Some(&position) = seen.get(&key) {
+                let hit: &mut SearchHit = &mut hits[position];
+                if !hit.sources.contains(&source) {
+                    hit.sources.push(source);
+                }
+                continue;
+            }
+            seen.insert(key, hits.len());
+            hits.push(SearchHit {
+                item: ListItem::from(&item),
+                info_hash,
+                sources: vec![source],
+            });
+        }
+    }
+
+    // Ids are per mirror, so upload order is compared by date instead.
+    hits.sort_by(|a, b| {
+        let (a, b) = (&a.item, &b.item);
+        let ordering = match sort {
+            Sort::Id => a.pub_date.cmp(&b.pub_date),
+            Sort::Size => a.size.cmp(&b.size),
+            Sort::Seeders => a.seeders.cmp(&b.seeders),
+            Sort::Leechers => a.leechers.cmp(&b.leechers),
+            Sort::Downloads => a.downloads.cmp(&b.downloads),
+            Sort::Comments => a.comments.cmp(&b.comments),
+        }
+        .then_with(|| a.pub_date.cmp(&b.pub_date));
+        match order {
+            Order::Asc => ordering,
+            Order::Desc => ordering.reverse(),
+        }
+    });
+    hits
+}
+
+#[axum::debug_handler]
+pub async fn handler(
+    Extension(mext): Extension<MirrorExt>,
+    Query(search): Query<SearchRequest>,
+    Query(request): Query<ListRequest>,
+) -> impl IntoResponse {
+    let expression = match nyaa_parser::dsl::parse(request.query.as_deref().unwrap_or_default()) {
+        Ok(expression) => expression,
+        Err(err) => {
+            return (
+                axum::http::StatusCode::BAD_REQUEST,
+                Json(QueryErrorResponse {
+                    error: err.message,
+                    start: err.start,
+                    end: err.end,
+                }),
+            )
+                .into_response();
+        }
+    };
+    let query = request.into_query().term(expression.text.clone());
+
+    let mirrors: Vec<&Mirror> = match search.mirrors.as_deref() {
+        Some(ids) => {
+            let mut mirrors = Vec::new();
+            for id in ids.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+                let Some(mirror) = mext.find_by_id(id) else {
+                    tracing::error!("mirror not found");
+                    return (
+                        axum::http::StatusCode::BAD_REQUEST,
+                        format!("Mirror not found: {}", id),
+                    )
+                        .into_response();
+                };
+                if !mirrors.iter().any(|m: &&Mirror| m.id() == id) {
+                    mirrors.push(mirror);
+                }
+            }
+            mirrors
+        }
+        None => mext.iter().filter(|mirror| !mirror.is_hidden()).collect(),
+    };
+
+    let searches = mirrors.iter().map(|mirror| {
+        let query = &query;
+        let expression = &expression;
+        async move {
+            let start = Instant::now();
+            let result = tokio::time::timeout(mirror.search_timeout(), async {
+                let client = mirror.client.lock().await;
+                if expression.has_predicates() {
+                    client
+                        .list_filtered(query, expression)
+                        .await
+                        .map(|list| list.items)
+                } else {
+                    client.list(query).await
+                }
+            })
+            .await;
+            let result = match result {
+                Ok(Ok(items)) => Ok(items),
+                Ok(Err(err)) => {
+                    tracing::warn!("search failed on mirror {}: {:?}", mirror.id(), err);
+                    Err(format!("{:#}", err))
+                }
+                Err(_) => {
+                    tracing::warn!("search timed out on mirror {}", mirror.id());
+                    Err(format!("timed out after {:?}", mirror.search_timeout()))
+                }
+            };
+            (mirror.id().to_string(), result, start.elapsed())
+        }
+    });
+
+    let mut results = Vec::new();
+    let mut statuses = Vec::new();
+    for (mirror, result, elapsed) in join_all(searches).await {
+        let (ok, error, item_count) = match result {
+            Ok(items) => {
+                let count = items.len();
+                results.push((mirror.clone(), items));
+                (true, None, count)
+            }
+            Err(err) => (false, Some(err), 0),
+        };
+        statuses.push(MirrorResult {
+            mirror,
+            ok,
+            error,
+            item_count,
+            elapsed: elapsed.as_secs_f64(),
+        });
+    }
+
+    let items = merge(results, query.sort, query.order);
+    Json(SearchResponse {
+        items,
+        mirrors: statuses,
+    })
+    .into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(
+        id: usize,
+        title: &str,
+        seeders: usize,
+        info_hash: Option<&str>,
+    ) -> nyaa_parser::ListItem {
+        nyaa_parser::ListItem {
+            title: title.into(),
+            pub_date: chrono::DateTime::from_timestamp(1_743_239_642 + id as i64, 0).unwrap(),
+            id,
+            seeders,
+            category: "1_2".into(),
+            size: 1 << 30,
+            magnet_link: info_hash.map(|hash| format!("magnet:?xt=urn:btih:{}&dn=x", hash)),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let hits = merge(
+            vec![
+                (
+                    "nyaa".into(),
+                    vec![item(1, "A", 5, Some("ABCD")), item(2, "B", 10, None)],
+                ),
+                (
+                    "backup".into(),
+                    vec![
+                        item(7, "Other title", 3, Some("abcd")),
+                        item(8, " b ", 1, None),
+                        item(9, "C", 20, None),
+                    ],
+                ),
+            ],
+            Sort::Seeders,
+            Order::Desc,
+        );
+        let summary = hits
+            .iter()
+            .map(|hit| (hit.item.title.as_str(), hit.sources.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(summary, vec![("C", 1), ("B", 2), ("A", 2)]);
+        assert_eq!(hits[2].info_hash.as_deref(), Some("abcd"));
+        assert_eq!(
+            hits[2].sources,
+            vec![
+                SearchSource {
+                    mirror: "nyaa".into(),
+                    id: 1
+                },
+                SearchSource {
+                    mirror: "backup".into(),
+                    id: 7
+                },
+            ]
+        );
+    }
+}
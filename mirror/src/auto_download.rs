@@ -0,0 +1,936 @@
+//! Auto-download rules: list queries whose matches are grabbed without
+//! anyone looking, either into a watch directory or a download client.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, LazyLock, Mutex},
+    time::Duration,
+};
+
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use nyaa_parser::ListItem;
+use regex::{Regex, RegexBuilder};
+use rusqlite::OptionalExtension;
+
+use crate::{
+    MirrorExt,
+    api::mirror::list::ListRequest,
+    download_client::{DownloadClients, Source, Torrent},
+    saved_search,
+};
+
+/// How often due rules are looked for.
+const TICK: Duration = Duration::from_secs(30);
+/// Share of the rate limiter budget rules may use when not configured.
+pub const DEFAULT_BUDGET_SHARE: f64 = 0.25;
+/// Number of history entries returned per rule.
+const HISTORY_LIMIT: usize = 500;
+
+#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
+pub struct RuleFilter {
+    /// Regular expressions the title must all match, ignoring case.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Regular expressions the title must not match, ignoring case.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+    /// Minimum size in bytes.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_size: Option<u64>,
+    /// Maximum size in bytes.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_size: Option<u64>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_seeders: Option<usize>,
+    #[serde(default)]
+    pub trusted_only: bool,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Action {
+    /// Saves the `.torrent` file to the configured watch directory.
+    WatchDir,
+    /// Sends the torrent to a download client, the first one by default.
+    DownloadClient {
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        client: Option<String>,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        category: Option<String>,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        save_path: Option<String>,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        paused: Option<bool>,
+        #[serde(default)]
+        source: Source,
+    },
+}
+
+impl Action {
+    fn describe(&self) -> String {
+        match self {
+            Action::WatchDir => "watch_dir".to_string(),
+            Action::DownloadClient { client, .. } => match client {
+                Some(client) => format!("download_client:{}", client),
+                None => "download_client".to_string(),
+            },
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_interval() -> Duration {
+    saved_search::DEFAULT_INTERVAL
+}
+
+/// What a rule looks for and what it does with matches.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct RuleSpec {
+    pub mirror_id: String,
+    pub name: String,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Record what would be grabbed without grabbing it.
+    #[serde(default)]
+    pub dry_run: bool,
+    #[serde(default)]
+    pub query: ListRequest,
+    #[serde(default)]
+    pub filter: RuleFilter,
+    /// Grab each episode once, replacing it only with a higher version or
+    /// resolution.
+    #[serde(default)]
+    pub track_episodes: bool,
+    pub action: Action,
+    #[serde(with = "humantime_serde")]
+    #[serde(default = "default_interval")]
+    pub interval: Duration,
+}
+
+impl RuleSpec {
+    pub fn matcher(&self) -> Result<Matcher, regex::Error> {
+        let compile = |patterns: &[String]| {
+            patterns
+                .iter()
+                .map(|pattern| RegexBuilder::new(pattern).case_insensitive(true).build())
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Matcher {
+            include: compile(&self.filter.include)?,
+            exclude: compile(&self.filter.exclude)?,
+        })
+    }
+}
+
+/// Compiled title patterns of a rule.
+#[derive(Debug, Clone)]
+pub struct Matcher {
+    include: Vec<Regex>,
+    exclude: Vec<Regex>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Rule {
+    pub id: i64,
+    #[serde(flatten)]
+    pub spec: RuleSpec,
+    pub created_at: DateTime<Utc>,
+    pub last_checked_at: Option<DateTime<Utc>>,
+    /// Error of the last check, if it failed.
+    pub last_error: Option<String>,
+}
+
+/// A torrent a rule grabbed, or would have grabbed in dry-run mode.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Grab {
+    pub id: i64,
+    pub rule_id: i64,
+    pub mirror_id: String,
+    pub torrent_id: usize,
+    pub title: String,
+    /// Episode key, when the rule tracks episodes.
+    pub episode: Option<String>,
+    pub version: Option<u32>,
+    pub resolution: Option<u32>,
+    pub action: String,
+    pub dry_run: bool,
+    pub reason: String,
+    /// Why grabbing failed. Failed grabs are retried on the next check.
+    pub error: Option<String>,
+    pub grabbed_at: DateTime<Utc>,
+}
+
+/// Episode and quality information parsed from a title.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Release {
+    /// Lowercased title without tags, episode or quality.
+    pub show: String,
+    pub season: u32,
+    pub episode: u32,
+    pub version: u32,
+    /// Vertical resolution, such as 1080.
+    pub resolution: Option<u32>,
+}
+
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\[[^\]]*\]|\([^)]*\)").unwrap());
+static RESOLUTION: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(?:(\d{3,4})[pi]|\d{3,4}x(\d{3,4})|(4k))\b").unwrap());
+static SEASON_EPISODE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\bS(\d{1,2})\s*E(\d{1,4})(?:v(\d+))?\b").unwrap());
+static DASH_EPISODE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\s-\s(\d{1,4})(?:v(\d+))?\b").unwrap());
+static EPISODE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?i)\b(?:EP?|Episode\s*)(\d{1,4})(?:v(\d+))?\b").unwrap());
+
+impl Release {
+    /// Parses titles such as `[Group] Show - 05v2 (1080p) [ABCD1234].mkv` or
+    /// `Show S02E05 1080p WEB`. Batches such as `Show - 01-12` have no
+    /// single episode and are not parsed.
+    pub fn parse(title: &str) -> Option<Self> {
+        let resolution = RESOLUTION.captures(title).and_then(|captures| {
+            match (captures.get(1), captures.get(2)) {
+                (Some(height), _) | (None, Some(height)) => height.as_str().parse().ok(),
+                (None, None) => Some(2160),
+            }
+        });
+
+        let stripped = TAG.replace_all(title, " ").replace(['_', '.'], " ");
+        let number = |captures: &regex::Captures, index: usize| {
+            captures
+                .get(index)
+                .and_then(|m| m.as_str().parse::<u32>().ok())
+        };
+        let (start, season, episode, version) =
+            if let Some(captures) = SEASON_EPISODE.captures(&stripped) {
+                (
+                    captures.get(0)?.start(),
+                    number(&captures, 1)?,
+                    number(&captures, 2)?,
+                    number(&captures, 3),
+                )
+            } else if let Some(captures) = DASH_EPISODE
+                .captures(&stripped)
+                .or_else(|| EPISODE.captures(&stripped))
+            {
+                let whole = captures.get(0)?;
+                let rest = stripped[whole.end()..].trim_start();
+                if rest.starts_with(['-', '~'])
+                    && rest[1..]
+                        .trim_start()
+                        .starts_with(|c: char| c.is_ascii_digit())
+                {
+                    return None;
+                }
+                (
+                    whole.start(),
+                    1,
+                    number(&captures, 1)?,
+                    number(&captures, 2),
+                )
+            } else {
+                return None;
+            };
+
+        let show = stripped[..start]
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .trim_end_matches([' ', '-'])
+            .to_lowercase();
+        if show.is_empty() {
+            return None;
+        }
+        Some(Release {
+            show,
+            season,
+            episode,
+            version: version.unwrap_or(1),
+            resolution,
+        })
+    }
+
+    pub fn key(&self) -> String {
+        format!("{} S{:02}E{:02}", self.show, self.season, self.episode)
+    }
+
+    fn quality(&self) -> (u32, u32) {
+        (self.resolution.unwrap_or(0), self.version)
+    }
+
+    fn describe_quality(resolution: Option<u32>, version: u32) -> String {
+        match resolution {
+            Some(resolution) => format!("{}p v{}", resolution, version),
+            None => format!("v{}", version),
+        }
+    }
+}
+
+/// What a rule does with one listed torrent.
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Decision {
+    pub id: usize,
+    pub title: String,
+    pub grab: bool,
+    /// Why the torrent is grabbed or skipped.
+    pub reason: String,
+    pub release: Option<Release>,
+}
+
+/// Why `item` does not pass the rule's filters, if it doesn't.
+fn rejection(spec: &RuleSpec, matcher: &Matcher, item: &ListItem) -> Option<String> {
+    let filter = &spec.filter;
+    if let Some(pattern) = matcher.include.iter().find(|re| !re.is_match(&item.title)) {
+        return Some(format!("title does not match `{}`", pattern));
+    }
+    if let Some(pattern) = matcher.exclude.iter().find(|re| re.is_match(&item.title)) {
+        return Some(format!("title matches excluded `{}`", pattern));
+    }
+    if filter.min_size.is_some_and(|min| item.size < min) {
+        return Some("smaller than the minimum size".to_string());
+    }
+    if filter.max_size.is_some_and(|max| item.size > max) {
+        return Some("larger than the maximum size".to_string());
+    }
+    if let Some(min) = filter.min_seeders.filter(|&min| item.seeders < min) {
+        return Some(format!("fewer than {} seeders", min));
+    }
+    if filter.trusted_only && !item.trusted {
+        return Some("not trusted".to_string());
+    }
+    None
+}
+
+/// Decides which of `items` to grab given the rule's successful grabs so
+/// far. Torrents published before `since` are skipped.
+pub fn evaluate(
+    spec: &RuleSpec,
+    matcher: &Matcher,
+    items: &[ListItem],
+    history: &[Grab],
+    since: Option<DateTime<Utc>>,
+) -> Vec<Decision> {
+    let mut decisions = items
+        .iter()
+        .map(|item| {
+            let release = spec
+                .track_episodes
+                .then(|| Release::parse(&item.title))
+                .flatten();
+            let rejection = if history.iter().any(|grab| grab.torrent_id == item.id) {
+                Some("already grabbed".to_string())
+            } else if since.is_some_and(|since| item.pub_date < since) {
+                Some("published before the rule was created".to_string())
+            } else {
+                rejection(spec, matcher, item)
+            };
+            Decision {
+                id: item.id,
+                title: item.title.clone(),
+                grab: rejection.is_none(),
+                reason: rejection.unwrap_or_else(|| "matches the rule".to_string()),
+                release,
+            }
+        })
+        .collect::<Vec<_>>();
+    if !spec.track_episodes {
+        return decisions;
+    }
+
+    // Keep the best release of each episode, and only if it beats what was
+    // grabbed before.
+    let mut episodes = HashMap::<String, Vec<usize>>::new();
+    for (index, decision) in decisions.iter().enumerate() {
+        if let (true, Some(release)) = (decision.grab, &decision.release) {
+            episodes.entry(release.key()).or_default().push(index);
+        }
+    }
+    for (key, indices) in episodes {
+        let best = *indices
+            .iter()
+            .max_by_key(|&&index| {
+                let release = decisions[index]
+                    .release
+                    .as_ref()
+                    .expect("grouped by release");
+                (release.quality(), items[index].seeders)
+            })
+            .expect("groups are not empty");
+        let previous = history
+            .iter()
+            .filter(|grab| grab.episode.as_deref() == Some(key.as_str()))
+            .map(|grab| {
+                (
+                    grab.resolution.unwrap_or(0),
+                    grab.version.unwrap_or(1),
+                    grab,
+                )
+            })
+            .max_by_key(|(resolution, version, _)| (*resolution, *version));
+        let release = decisions[best].release.clone().expect("grouped by release");
+        for &index in &indices {
+            let decision = &mut decisions[index];
+            if index != best {
+                decision.grab = false;
+                decision.reason = format!("a better release of {} is listed", key);
+                continue;
+            }
+            decision.reason = match previous {
+                Some((resolution, version, grab)) if release.quality() <= (resolution, version) => {
+                    decision.grab = false;
+                    format!(
+                        "{} was already grabbed as {} ({})",
+                        key,
+                        Release::describe_quality(grab.resolution, version),
+                        grab.torrent_id
+                    )
+                }
+                Some((_, version, grab)) => format!(
+                    "upgrades {} from {} to {}",
+                    key,
+                    Release::describe_quality(grab.resolution, version),
+                    Release::describe_quality(release.resolution, release.version)
+                ),
+                None => format!("new episode {}", key),
+            };
+        }
+    }
+    decisions
+}
+
+#[derive(Debug, Clone)]
+struct NewGrab<'a> {
+    rule: &'a Rule,
+    decision: &'a Decision,
+    error: Option<String>,
+}
+
+/// Auto-download rules and what they grabbed.
+#[derive(Debug, Clone)]
+pub struct Rules {
+    conn: Arc<Mutex<rusqlite::Connection>>,
+    watch_dir: Option<PathBuf>,
+}
+
+const RULE_COLUMNS: &str = "id, spec, created_at, last_checked_at, last_error";
+const GRAB_COLUMNS: &str = "id, rule_id, mirror_id, torrent_id, title, episode, version, resolution, action, dry_run, reason, error, grabbed_at";
+
+impl Rules {
+    pub fn open(db_path: &Path, watch_dir: Option<PathBuf>) -> rusqlite::Result<Self> {
+        let conn = crate::db::open(db_path, MIGRATIONS, "auto-download")?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+            watch_dir,
+        })
+    }
+
+    #[cfg(test)]
+    fn open_in_memory() -> Self {
+        Self {
+            conn: Arc::new(Mutex::new(crate::db::open_in_memory(MIGRATIONS))),
+            watch_dir: None,
+        }
+    }
+
+    pub fn watch_dir(&self) -> Option<&Path> {
+        self.watch_dir.as_deref()
+    }
+
+    pub async fn create(&self, spec: RuleSpec) -> rusqlite::Result<Rule> {
+        crate::db::blocking(&self.conn, move |conn| {
+            let now = Utc::now();
+            conn.execute(
+                "INSERT INTO rules (spec, created_at) VALUES (?, ?)",
+                rusqlite::params![
+                    serde_json::to_string(&spec).expect("rule is serializable"),
+                    now
+                ],
+            )?;
+            Ok(Rule {
+                id: conn.last_insert_rowid(),
+                spec,
+                created_at: now,
+                last_checked_at: None,
+                last_error: None,
+            })
+        })
+        .await
+    }
+
+    /// Replaces the spec of a rule, keeping its history.
+    pub async fn update(&self, id: i64, spec: RuleSpec) -> rusqlite::Result<Option<Rule>> {
+        crate::db::blocking(&self.conn, move |conn| {
+            conn.execute(
+                "UPDATE rules SET spec = ? WHERE id = ?",
+                rusqlite::params![
+                    serde_json::to_string(&spec).expect("rule is serializable"),
+                    id
+                ],
+            )?;
+            query_rule(conn, id)
+        })
+        .await
+    }
+
+    pub async fn list(&self) -> rusqlite::Result<Vec<Rule>> {
+        crate::db::blocking(&self.conn, |conn| {
+            let mut stmt =
+                conn.prepare_cached(&format!("SELECT {} FROM rules ORDER BY id", RULE_COLUMNS))?;
+            stmt.query_map([], read_rule)?.collect()
+        })
+        .await
+    }
+
+    pub async fn get(&self, id: i64) -> rusqlite::Result<Option<Rule>> {
+        crate::db::blocking(&self.conn, move |conn| query_rule(conn, id)).await
+    }
+
+    /// Deletes a rule and its history. Returns whether it existed.
+    pub async fn delete(&self, id: i64) -> rusqlite::Result<bool> {
+        crate::db::blocking(&self.conn, move |conn| {
+            Ok(conn.execute("DELETE FROM rules WHERE id = ?", [id])? > 0)
+        })
+        .await
+    }
+
+    /// Enabled rules never checked, or last checked at least one interval
+    /// before `now`.
+    async fn due(&self, now: DateTime<Utc>) -> rusqlite::Result<Vec<Rule>> {
+        Ok(self
+            .list()
+            .await?
+            .into_iter()
+            .filter(|rule| {
+                rule.spec.enabled
+                    && rule.last_checked_at.is_none_or(|checked| {
+                        now - checked
+                            >= chrono::Duration::from_std(rule.spec.interval)
+                                .unwrap_or(chrono::Duration::MAX)
+                    })
+            })
+            .collect())
+    }
+
+    async fn record_check(
+        &self,
+        rule_id: i64,
+        now: DateTime<Utc>,
+        error: Option<String>,
+    ) -> rusqlite::Result<()> {
+        crate::db::blocking(&self.conn, move |conn| {
+            conn.execute(
+                "UPDATE rules SET last_checked_at = ?, last_error = ? WHERE id = ?",
+                rusqlite::params![now, error, rule_id],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+
+    /// History of a rule, newest first.
+    pub async fn history(&self, rule_id: i64) -> rusqlite::Result<Vec<Grab>> {
+        crate::db::blocking(&self.conn, move |conn| {
+            let mut stmt = conn.prepare_cached(&format!(
+                "SELECT {} FROM grabs WHERE rule_id = ? ORDER BY id DESC LIMIT ?",
+                GRAB_COLUMNS
+            ))?;
+            stmt.query_map(rusqlite::params![rule_id, HISTORY_LIMIT], read_grab)?
+                .collect()
+        })
+        .await
+    }
+
+    /// Successful grabs of a rule in the given mode. Dry runs don't count
+    /// as grabs once the rule is live, and failed grabs are retried.
+    async fn grabbed(&self, rule_id: i64, dry_run: bool) -> rusqlite::Result<Vec<Grab>> {
+        crate::db::blocking(&self.conn, move |conn| {
+            let mut stmt = conn.prepare_cached(&format!(
+                "SELECT {} FROM grabs WHERE rule_id = ? AND dry_run = ? AND error IS NULL",
+                GRAB_COLUMNS
+            ))?;
+            stmt.query_map(rusqlite::params![rule_id, dry_run], read_grab)?
+                .collect()
+        })
+        .await
+    }
+
+    async fn record_grab(&self, grab: NewGrab<'_>, now: DateTime<Utc>) -> rusqlite::Result<()> {
+        let release = grab.decision.release.as_ref();
+        let params = (
+            grab.rule.id,
+            grab.rule.spec.mirror_id.clone(),
+            grab.decision.id,
+            grab.decision.title.clone(),
+            release.map(Release::key),
+            release.map(|release| release.version),
+            release.and_then(|release| release.resolution),
+            grab.rule.spec.action.describe(),
+            grab.rule.spec.dry_run,
+            grab.decision.reason.clone(),
+            grab.error,
+            now,
+        );
+        crate::db::blocking(&self.conn, move |conn| {
+            conn.execute(
+                "INSERT INTO grabs (rule_id, mirror_id, torrent_id, title, episode, version, resolution, action, dry_run, reason, error, grabbed_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                params,
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+fn query_rule(conn: &rusqlite::Connection, id: i64) -> rusqlite::Result<Option<Rule>> {
+    conn.query_row(
+        &format!("SELECT {} FROM rules WHERE id = ?", RULE_COLUMNS),
+        [id],
+        read_rule,
+    )
+    .optional()
+}
+
+fn read_rule(row: &rusqlite::Row) -> rusqlite::Result<Rule> {
+    let spec: String = row.get(1)?;
+    let spec = serde_json::from_str(&spec).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+    Ok(Rule {
+        id: row.get(0)?,
+        spec,
+        created_at: row.get(2)?,
+        last_checked_at: row.get(3)?,
+        last_error: row.get(4)?,
+    })
+}
+
+fn read_grab(row: &rusqlite::Row) -> rusqlite::Result<Grab> {
+    Ok(Grab {
+        id: row.get(0)?,
+        rule_id: row.get(1)?,
+        mirror_id: row.get(2)?,
+        torrent_id: row.get(3)?,
+        title: row.get(4)?,
+        episode: row.get(5)?,
+        version: row.get(6)?,
+        resolution: row.get(7)?,
+        action: row.get(8)?,
+        dry_run: row.get(9)?,
+        reason: row.get(10)?,
+        error: row.get(11)?,
+        grabbed_at: row.get(12)?,
+    })
+}
+
+/// Schema migrations of the auto-download database.
+const MIGRATIONS: &[&str] = &["CREATE TABLE IF NOT EXISTS rules (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        spec TEXT NOT NULL,
+        created_at TEXT NOT NULL,
+        last_checked_at TEXT,
+        last_error TEXT
+    );
+    CREATE TABLE IF NOT EXISTS grabs (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        rule_id INTEGER NOT NULL REFERENCES rules (id) ON DELETE CASCADE,
+        mirror_id TEXT NOT NULL,
+        torrent_id INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        episode TEXT,
+        version INTEGER,
+        resolution INTEGER,
+        action TEXT NOT NULL,
+        dry_run INTEGER NOT NULL,
+        reason TEXT NOT NULL,
+        error TEXT,
+        grabbed_at TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS grabs_rule_id ON grabs (rule_id, dry_run);"];
+
+/// Spawns a background task that checks due rules and grabs what they
+/// match. Only torrents published after a rule was created are grabbed.
+pub fn spawn(mext: MirrorExt, rules: Rules, clients: DownloadClients) {
+    let store = rules.clone();
+    crate::scheduler::spawn_due(
+        "auto-download",
+        TICK,
+        move |now| {
+            let rules = store.clone();
+            async move { rules.due(now).await }
+        },
+        move |rule| {
+            let mext = mext.clone();
+            let rules = rules.clone();
+            let clients = clients.clone();
+            async move { check(&mext, &rules, &clients, &rule).await }
+        },
+    );
+}
+
+async fn check(mext: &MirrorExt, rules: &Rules, clients: &DownloadClients, rule: &Rule) {
+    let now = Utc::now();
+    let Some(mirror) = mext.find_by_id(&rule.spec.mirror_id) else {
+        let error = format!("mirror {} not found", rule.spec.mirror_id);
+        tracing::warn!("auto-download rule {} failed: {}", rule.id, error);
+        let _ = rules.record_check(rule.id, now, Some(error)).await;
+        return;
+    };
+    let budget_share = mirror
+        .config
+        .auto_download_budget_share
+        .unwrap_or(DEFAULT_BUDGET_SHARE);
+    // The client is only locked while talking to upstream, not while
+    // download clients or the disk take their time with a grab.
+    let items = {
+        let client = mirror.client.lock().await;
+        if !client.has_budget(budget_share).await {
+            tracing::debug!(
+                "postponed auto-download rule {}: rate limiter budget in use",
+                rule.id
+            );
+            return;
+        }
+        saved_search::run(&client, &rule.spec.query).await
+    };
+
+    let result = async {
+        let matcher = rule.spec.matcher()?;
+        let items = items?;
+        let history = rules.grabbed(rule.id, rule.spec.dry_run).await?;
+        Ok::<_, anyhow::Error>(evaluate(
+            &rule.spec,
+            &matcher,
+            &items,
+            &history,
+            Some(rule.created_at),
+        ))
+    }
+    .await;
+
+    let error = match result {
+        Ok(decisions) => {
+            for decision in decisions.iter().filter(|decision| decision.grab) {
+                let error = if rule.spec.dry_run {
+                    tracing::info!(
+                        "auto-download rule {} would grab {}: {}",
+                        rule.id,
+                        decision.id,
+                        decision.reason
+                    );
+                    None
+                } else {
+                    match grab(
+                        &mirror.client,
+                        clients,
+                        rules.watch_dir(),
+                        rule,
+                        decision.id,
+                    )
+                    .await
+                    {
+                        Ok(()) => {
+                            tracing::info!(
+                                "auto-download rule {} grabbed {}: {}",
+                                rule.id,
+                                decision.id,
+                                decision.reason
+                            );
+                            None
+                        }
+                        Err(e) => {
+                            tracing::warn!(
+                                "auto-download rule {} failed to grab {}: {:#}",
+                                rule.id,
+                                decision.id,
+                                e
+                            );
+                            Some(format!("{:#}", e))
+                        }
+                    }
+                };
+                let grab = NewGrab {
+                    rule,
+                    decision,
+                    error,
+                };
+                if let Err(e) = rules.record_grab(grab, Utc::now()).await {
+                    tracing::warn!("failed to record grab of {}: {}", decision.id, e);
+                }
+            }
+            None
+        }
+        Err(e) => Some(format!("{:#}", e)),
+    };
+    if let Some(error) = &error {
+        tracing::warn!("auto-download rule {} failed: {}", rule.id, error);
+    }
+    if let Err(e) = rules.record_check(rule.id, now, error).await {
+        tracing::warn!(
+            "failed to record check of auto-download rule {}: {}",
+            rule.id,
+            e
+        );
+    }
+}
+
+/// Performs the rule's action on torrent `id`, locking `client` only to
+/// fetch the torrent.
+async fn grab(
+    client: &tokio::sync::Mutex<crate::client::Client>,
+    clients: &DownloadClients,
+    watch_dir: Option<&Path>,
+    rule: &Rule,
+    id: usize,
+) -> anyhow::Result<()> {
+    let id = id.to_string();
+    match &rule.spec.action {
+        Action::WatchDir => {
+            let dir = watch_dir.context("no watch_dir is configured")?;
+            let data = client.lock().await.torrent_file(&id).await?;
+            let name = format!("{}-{}.torrent", rule.spec.mirror_id, id);
+            // Write under a temporary name so watchers never see a partial
+            // file.
+            let partial = dir.join(format!(".{}.part", name));
+            let path = dir.join(name);
+            tokio::task::spawn_blocking(move || {
+                std::fs::write(&partial, data)?;
+                std::fs::rename(&partial, &path)
+            })
+            .await?
+            .with_context(|| format!("failed to save torrent to {}", dir.display()))
+        }
+        Action::DownloadClient {
+            client: client_id,
+            category,
+            save_path,
+            paused,
+            source,
+        } => {
+            let download_client = clients
+                .find(client_id.as_deref())
+                .context("download client not found")?;
+            let (torrent, _) = Torrent::fetch(&*client.lock().await, &id, *source).await?;
+            let options = download_client.options(category.clone(), save_path.clone(), *paused);
+            download_client.add(&torrent, &options).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_release() {
+        let release =
+            Release::parse("[SubsPlease] Super Cube - 05v2 (1080p) [ABCD1234].mkv").unwrap();
+        assert_eq!(
+            release,
+            Release {
+                show: "super cube".into(),
+                season: 1,
+                episode: 5,
+                version: 2,
+                resolution: Some(1080),
+            }
+        );
+        assert_eq!(release.key(), "super cube S01E05");
+
+        let release = Release::parse("Super.Cube.S02E11.720p.WEB.x264").unwrap();
+        assert_eq!((release.season, release.episode), (2, 11));
+        assert_eq!(release.resolution, Some(720));
+        assert_eq!(release.version, 1);
+
+        assert!(Release::parse("[Group] Super Cube - 01-12 (1080p) [Batch]").is_none());
+        assert!(Release::parse("Super Cube Original Soundtrack").is_none());
+    }
+
+    fn item(id: usize, title: &str, seeders: usize) -> ListItem {
+        ListItem {
+            title: title.into(),
+            pub_date: Utc::now(),
+            id,
+            seeders,
+            category: "1_2".into(),
+            size: 1 << 30,
+            trusted: true,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_evaluate() {
+        let rules = Rules::open_in_memory();
+        let spec: RuleSpec = serde_json::from_value(serde_json::json!({
+            "mirror_id": "nyaa",
+            "name": "cube",
+            "filter": { "include": ["super cube"], "exclude": ["480p"], "min_seeders": 5 },
+            "track_episodes": true,
+            "action": { "type": "watch_dir" },
+        }))
+        .unwrap();
+        let matcher = spec.matcher().unwrap();
+        let rule = rules.create(spec.clone()).await.unwrap();
+
+        let items = [
+            item(1, "[A] Super Cube - 05 (720p)", 50),
+            item(2, "[B] Super Cube - 05 (1080p)", 10),
+            item(3, "[C] Super Cube - 05 (480p)", 100),
+            item(4, "[A] Super Cube - 06 (720p)", 1),
+            item(5, "[A] Other Show - 05 (1080p)", 50),
+        ];
+        let decisions = evaluate(&spec, &matcher, &items, &[], None);
+        let grabbed = |decisions: &[Decision]| {
+            decisions
+                .iter()
+                .filter(|decision| decision.grab)
+                .map(|decision| decision.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(grabbed(&decisions), [2]);
+        assert_eq!(decisions[1].reason, "new episode super cube S01E05");
+        assert_eq!(decisions[3].reason, "fewer than 5 seeders");
+        for decision in decisions.iter().filter(|decision| decision.grab) {
+            let grab = NewGrab {
+                rule: &rule,
+                decision,
+                error: None,
+            };
+            rules.record_grab(grab, Utc::now()).await.unwrap();
+        }
+
+        // A repeat of the episode is skipped, but a v2 is an upgrade.
+        let history = rules.grabbed(rule.id, false).await.unwrap();
+        let items = [
+            item(6, "[D] Super Cube - 05 (1080p)", 50),
+            item(7, "[B] Super Cube - 05v2 (1080p)", 50),
+        ];
+        let decisions = evaluate(&spec, &matcher, &items[..1], &history, None);
+        assert!(grabbed(&decisions).is_empty());
+        let decisions = evaluate(&spec, &matcher, &items, &history, None);
+        assert_eq!(grabbed(&decisions), [7]);
+        assert_eq!(
+            decisions[1].reason,
+            "upgrades super cube S01E05 from 1080p v1 to 1080p v2"
+        );
+
+        assert!(rules.grabbed(rule.id, true).await.unwrap().is_empty());
+        assert_eq!(rules.history(rule.id).await.unwrap().len(), 1);
+        assert!(rules.delete(rule.id).await.unwrap());
+        assert!(rules.history(rule.id).await.unwrap().is_empty());
+    }
+}
@@ -1,8 +1,11 @@
 use std::{fs, net::SocketAddr, path::{Path, PathBuf}};
 
+use anyhow::Context;
 use clap::Parser;
+use nyaa_parser::LayoutProfile;
 use serde::{Deserialize, Serialize};
 
+use crate::notification::EventKind;
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
@@ -10,7 +13,184 @@ pub struct Config {
     pub static_dir: PathBuf,
     pub cors_allow_everyone: Option<bool>,
     pub request_tracker_db: PathBuf,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_tracker_queue_size: Option<usize>,
+    #[serde(with = "humantime_serde")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_tracker_raw_retention: Option<std::time::Duration>,
+    #[serde(with = "humantime_serde")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_tracker_retention: Option<std::time::Duration>,
+    /// Database of torrents seen through the mirrors. Defaults to
+    /// `index.db` next to the request tracker database.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub index_db: Option<PathBuf>,
+    /// How long swarm stats are kept in the index. Defaults to 30 days, and
+    /// is never shorter than the longest trending window.
+    #[serde(with = "humantime_serde")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub swarm_stats_retention: Option<std::time::Duration>,
+    /// Database of saved searches. Defaults to `saved_searches.db` next to
+    /// the request tracker database.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saved_search_db: Option<PathBuf>,
+    /// Database of auto-download rules and their history. Defaults to
+    /// `auto_download.db` next to the request tracker database.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_download_db: Option<PathBuf>,
+    /// Directory rules with the `watch_dir` action save `.torrent` files to,
+    /// typically one a torrent client watches.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watch_dir: Option<PathBuf>,
+    /// Time allowed for a tracker to answer a scrape.
+    #[serde(with = "humantime_serde")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scrape_timeout: Option<std::time::Duration>,
+    /// How long scraped tracker stats are reused.
+    #[serde(with = "humantime_serde")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scrape_cache_duration: Option<std::time::Duration>,
     pub mirror: Vec<MirrorConfig>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub notification: Vec<NotificationConfig>,
+    /// Torrent clients torrents can be sent to. The first one is used when
+    /// a request does not name one.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub download_client: Vec<DownloadClientConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DownloadClientConfig {
+    pub id: String,
+    #[serde(flatten)]
+    pub kind: DownloadClientKind,
+    /// Category (label in Transmission and Deluge) used when a request
+    /// does not give one.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub save_path: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paused: Option<bool>,
+    #[serde(with = "humantime_serde")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<std::time::Duration>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum DownloadClientKind {
+    /// qBittorrent Web API, for example `http://localhost:8080`.
+    #[serde(rename = "qbittorrent")]
+    QBittorrent {
+        url: String,
+        username: String,
+        password: Secret,
+    },
+    /// Transmission RPC, for example `http://localhost:9091/transmission/rpc`.
+    #[serde(rename = "transmission")]
+    Transmission {
+        url: String,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        username: Option<String>,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        password: Option<Secret>,
+    },
+    /// Deluge Web UI JSON-RPC, for example `http://localhost:8112/json`.
+    #[serde(rename = "deluge")]
+    Deluge { url: String, password: Secret },
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NotificationConfig {
+    #[serde(flatten)]
+    pub sink: SinkConfig,
+    /// Event kinds delivered to this sink; all events when unset.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events: Option<Vec<EventKind>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "type")]
+pub enum SinkConfig {
+    /// Posts the event as JSON. With a secret, the body is signed with
+    /// HMAC-SHA256 in the `X-Signature-256` header as `sha256=<hex>`.
+    #[serde(rename = "webhook")]
+    Webhook {
+        url: String,
+        #[serde(default)]
+        #[serde(skip_serializing_if = "Option::is_none")]
+        secret: Option<Secret>,
+    },
+    /// Discord webhook URL, which embeds its token.
+    #[serde(rename = "discord")]
+    Discord { url: Secret },
+    /// Slack incoming webhook URL, which embeds its token.
+    #[serde(rename = "slack")]
+    Slack { url: Secret },
+    #[serde(rename = "smtp")]
+    Smtp(SmtpConfig),
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    /// Defaults to the port of the TLS mode.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(default)]
+    pub tls: SmtpTls,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password: Option<Secret>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+/// A string kept out of logs.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct Secret(pub String);
+
+impl std::fmt::Debug for Secret {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("\"***\"")
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    /// Plain connection upgraded with STARTTLS, port 587 by default.
+    #[default]
+    Starttls,
+    /// Implicit TLS, port 465 by default.
+    Tls,
+    /// Unencrypted, port 25 by default.
+    None,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -38,6 +218,55 @@ pub struct MirrorConfig {
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub update_interval: Option<std::time::Duration>,
+    /// How often to probe the upstream in the background. Probing is
+    /// disabled when unset.
+    #[serde(with = "humantime_serde")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probe_interval: Option<std::time::Duration>,
+    /// Share of the rate limiter budget (0.0 to 1.0) reserved for probes.
+    /// Users get the rest, so a busy mirror is still probed.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub probe_budget_share: Option<f64>,
+    /// How often to poll the front page for live events while anyone is
+    /// subscribed. Defaults to a minute.
+    #[serde(with = "humantime_serde")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events_interval: Option<std::time::Duration>,
+    /// Share of the rate limiter budget (0.0 to 1.0) event polling may use.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub events_budget_share: Option<f64>,
+    /// Replace the seeders, leechers and downloads of list and view
+    /// responses with counts scraped from the torrents' trackers.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scrape_overlay: Option<bool>,
+    /// Trackers scraped for torrents whose magnet link lists none, such as
+    /// those from RSS feeds.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scrape_trackers: Option<Vec<String>>,
+    /// Archive the HTML of view pages along with the parsed views, so they
+    /// can be parsed again later. Parsed views are always archived. Only the
+    /// latest copy of a page is kept, but archives are never pruned, so the
+    /// index database grows by a page, tens of kilobytes, for every torrent
+    /// ever viewed.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub archive_html: Option<bool>,
+    /// Share of the rate limiter budget (0.0 to 1.0) saved searches may
+    /// use. Checks are postponed while more is in use.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub saved_search_budget_share: Option<f64>,
+    /// Share of the rate limiter budget (0.0 to 1.0) auto-download rules
+    /// may use. Checks are postponed while more is in use.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub auto_download_budget_share: Option<f64>,
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub window_requests: Option<usize>,
@@ -52,6 +281,68 @@ pub struct MirrorConfig {
     #[serde(default)]
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cache_duration: Option<std::time::Duration>,
+
+    /// Maximum number of upstream pages scanned to fill one page of search
+    /// results when the query has predicates such as `seeders:>20`.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search_max_pages: Option<usize>,
+    /// How long a cross-mirror search waits for this mirror before
+    /// reporting it as failed. Defaults to 10 seconds.
+    #[serde(with = "humantime_serde")]
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search_timeout: Option<std::time::Duration>,
+
+    /// Markup profile used to parse HTML pages. Defaults to the built-in
+    /// profile matching the mirror type.
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub layout: Option<LayoutConfig>,
+}
+
+impl Config {
+    pub fn index_db(&self) -> PathBuf {
+        self.index_db
+            .clone()
+            .unwrap_or_else(|| self.request_tracker_db.with_file_name("index.db"))
+    }
+
+    pub fn saved_search_db(&self) -> PathBuf {
+        self.saved_search_db
+            .clone()
+            .unwrap_or_else(|| self.request_tracker_db.with_file_name("saved_searches.db"))
+    }
+
+    pub fn auto_download_db(&self) -> PathBuf {
+        self.auto_download_db
+            .clone()
+            .unwrap_or_else(|| self.request_tracker_db.with_file_name("auto_download.db"))
+    }
+}
+
+impl MirrorConfig {
+    pub fn layout_profile(&self) -> anyhow::Result<LayoutProfile> {
+        let profile = match &self.layout {
+            Some(LayoutConfig::Builtin(name)) => LayoutProfile::builtin(name)
+                .with_context(|| format!("unknown layout profile: {}", name))?,
+            Some(LayoutConfig::Custom(profile)) => profile.as_ref().clone(),
+            None => match self.ty {
+                MirrorType::Normal => LayoutProfile::nyaa(),
+                MirrorType::Adult => LayoutProfile::sukebei(),
+            },
+        };
+        Ok(profile)
+    }
+}
+
+/// Either the name of a built-in layout profile (`nyaa` or `sukebei`) or a
+/// custom profile table overriding parts of the nyaa.si layout.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum LayoutConfig {
+    Builtin(String),
+    Custom(Box<LayoutProfile>),
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -1,62 +1,25 @@
 use std::{
+    collections::HashSet,
     path::{Path, PathBuf},
     sync::Arc,
     time::Duration,
 };
 
+use nyaa_parser::{LayoutProfile, ParserContext, SearchQuery, dsl::Expression};
 use reqwest::Url;
 
 use anyhow::Context;
-use serde::{Deserialize, Serialize};
+use futures_util::Stream;
+use serde::Serialize;
 use tokio::sync::Mutex;
 
-use crate::{cache::Cache, rate_limiter::RateLimiter, request_tracker::RequestTracker};
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ListQuery {
-    #[serde(default)]
-    #[serde(rename = "p")]
-    pub page: Option<usize>,
-    #[serde(default)]
-    #[serde(rename = "c")]
-    pub category: Option<String>,
-    #[serde(default)]
-    #[serde(rename = "f")]
-    pub filter: Option<String>,
-    #[serde(default)]
-    #[serde(rename = "s")]
-    pub sort: Option<String>,
-    #[serde(default)]
-    #[serde(rename = "o")]
-    pub order: Option<String>,
-    #[serde(default)]
-    #[serde(rename = "q")]
-    pub query: Option<String>,
-}
-
-impl ListQuery {
-    pub fn remove_defaults(mut self) -> Self {
-        if self.page == Some(1) {
-            self.page = None;
-        }
-        if self.category == Some("0_0".to_string()) {
-            self.category = None;
-        }
-        if self.filter == Some("0".to_string()) {
-            self.filter = None;
-        }
-        if self.sort == Some("id".to_string()) {
-            self.sort = None;
-        }
-        if self.order == Some("desc".to_string()) {
-            self.order = None;
-        }
-        if self.query == Some("".to_string()) {
-            self.query = None;
-        }
-        self
-    }
-}
+use crate::{
+    cache::Cache,
+    index::{DeletionReason, Index},
+    notification::{Event, Notifier},
+    rate_limiter::RateLimiter,
+    request_tracker::{ErrorKind, RequestOutcome, RequestTracker},
+};
 
 #[derive(Debug)]
 pub struct Client {
@@ -69,30 +32,170 @@ pub struct Client {
     cache: Arc<Mutex<Cache>>,
     rate_limiter: RateLimiter,
     request_tracker: Option<RequestTracker>,
+    notifier: Option<Notifier>,
+    index: Option<Index>,
+    /// Archive the HTML of view pages along with the parsed views.
+    archive_html: bool,
+    parser: Arc<ParserContext>,
     cache_duration: Duration,
+    search_max_pages: usize,
 }
 
-impl Client {
-    pub fn builder(mirror_id: impl AsRef<str>, url: Url) -> ClientBuilder {
-        ClientBuilder::new(mirror_id, url)
+/// One page of search results after applying query predicates.
+#[derive(Debug, Clone)]
+pub struct FilteredList {
+    pub items: Vec<nyaa_parser::ListItem>,
+    /// Number of upstream pages scanned.
+    pub upstream_pages: usize,
+    /// The scan stopped at the page limit before filling the page or
+    /// reaching the last upstream page.
+    pub truncated: bool,
+}
+
+/// Limits for [`Client::pages`].
+#[derive(Debug, Clone)]
+pub struct PageStreamOptions {
+    pub max_pages: usize,
+    /// Pages after the first wait until less than this share of the rate
+    /// limiter budget is in use, leaving room for other requests.
+    pub budget_share: f64,
+    /// Predicates applied to each page; items are counted as fetched
+    /// whether or not they match.
+    pub expression: Option<Expression>,
+}
+
+impl Default for PageStreamOptions {
+    fn default() -> Self {
+        Self {
+            max_pages: 100,
+            budget_share: 0.5,
+            expression: None,
+        }
     }
+}
 
-    pub async fn list(&self, query: &ListQuery) -> anyhow::Result<Vec<nyaa_parser::ListItem>> {
-        tracing::debug!("fetching list from {:?}", self.url.to_string());
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StopReason {
+    /// A page came back shorter than the first one, or empty.
+    LastPage,
+    MaxPages,
+    /// A page held only items already seen, as happens when the upstream
+    /// repeats its last page for out-of-range page numbers.
+    Duplicates,
+}
 
-        let begin = std::time::Instant::now();
+#[derive(Debug, Clone)]
+pub enum PageEvent {
+    Page {
+        page: usize,
+        items: Vec<nyaa_parser::ListItem>,
+        /// Items dropped because an earlier page already had them, usually
+        /// after new uploads shifted results between pages.
+        duplicates: usize,
+    },
+    End {
+        reason: StopReason,
+        pages: usize,
+    },
+}
 
-        let url = self.url.clone();
-        if let Some(value) = self.cache.lock().await.get(&url, &query) {
-            self.request_tracker
-                .as_ref()
-                .map(|tracker| tracker.track_request_cached(&self.mirror_id, &url, &query));
-            return Ok(value);
+struct PageStream {
+    client: Arc<Mutex<Client>>,
+    query: SearchQuery,
+    options: PageStreamOptions,
+    fetched: usize,
+    page_size: Option<usize>,
+    seen: HashSet<usize>,
+    pending_end: Option<StopReason>,
+    done: bool,
+}
+
+impl PageStream {
+    async fn next(&mut self) -> anyhow::Result<PageEvent> {
+        let end = |stream: &mut Self, reason| {
+            stream.done = true;
+            Ok(PageEvent::End {
+                reason,
+                pages: stream.fetched,
+            })
+        };
+        if let Some(reason) = self.pending_end.take() {
+            return end(self, reason);
+        }
+        if self.fetched >= self.options.max_pages {
+            return end(self, StopReason::MaxPages);
         }
 
-        self.rate_limiter.acquire().await;
+        if self.fetched > 0 {
+            loop {
+                let client = self.client.lock().await;
+                if client
+                    .rate_limiter
+                    .has_share(self.options.budget_share)
+                    .await
+                {
+                    break;
+                }
+                drop(client);
+                tokio::time::sleep(Duration::from_millis(500)).await;
+            }
+        }
 
-        let url = self.url.clone();
+        let page = self.query.page + self.fetched;
+        let items = self
+            .client
+            .lock()
+            .await
+            .list(&self.query.clone().page(page))
+            .await
+            .inspect_err(|_| self.done = true)?;
+        self.fetched += 1;
+
+        let page_size = *self.page_size.get_or_insert(items.len());
+        if items.is_empty() {
+            return end(self, StopReason::LastPage);
+        }
+        if items.len() < page_size {
+            self.pending_end = Some(StopReason::LastPage);
+        }
+
+        let total = items.len();
+        let items = items
+            .into_iter()
+            .filter(|item| self.seen.insert(item.id))
+            .collect::<Vec<_>>();
+        if items.is_empty() {
+            return end(self, StopReason::Duplicates);
+        }
+        let duplicates = total - items.len();
+        let items = match &self.options.expression {
+            Some(expression) => items
+                .into_iter()
+                .filter(|item| expression.matches(item))
+                .collect(),
+            None => items,
+        };
+        Ok(PageEvent::Page {
+            page,
+            items,
+            duplicates,
+        })
+    }
+}
+
+/// A successfully fetched upstream page.
+struct Page {
+    content_type: String,
+    body: String,
+}
+
+impl Client {
+    pub fn builder(mirror_id: impl AsRef<str>, url: Url) -> ClientBuilder {
+        ClientBuilder::new(mirror_id, url)
+    }
+
+    fn http_client(&self) -> anyhow::Result<reqwest::Client> {
         let mut client = reqwest::Client::builder()
             .connection_verbose(true)
             .user_agent(self.user_agent.clone())
@@ -109,156 +212,465 @@ impl Client {
             client = client.interface(interface);
         }
 
-        let client = client.build().context("failed to build HTTP client")?;
+        client.build().context("failed to build HTTP client")
+    }
+
+    fn base_url(url: &Url) -> String {
+        let scheme = url.scheme();
+        let host = url.host_str().unwrap_or("");
+        if let Some(port) = url.port() {
+            format!("{}://{}:{}", scheme, host, port)
+        } else {
+            format!("{}://{}", scheme, host)
+        }
+    }
+
+    /// Logs and reports any layout drift found by the parser canary.
+    fn check_layout(
+        &self,
+        url: &Url,
+        drift: Option<nyaa_parser::canary::LayoutDrift>,
+        outcome: &mut RequestOutcome,
+    ) {
+        let Some(drift) = drift else {
+            return;
+        };
+        tracing::warn!("mirror {}: {} ({})", self.mirror_id, drift, url);
+        if let Some(notifier) = self.notifier.as_ref() {
+            notifier.notify(Event::LayoutDrift {
+                mirror_id: self.mirror_id.clone(),
+                url: url.to_string(),
+                drift: drift.clone(),
+            });
+        }
+        outcome.layout_drift = Some(drift);
+    }
+
+    /// Sends a GET for `url` to the upstream, recording status, content type
+    /// and the kind of any failure in `outcome`. Unsuccessful responses are
+    /// returned as errors. Callers are responsible for acquiring the rate
+    /// limiter first.
+    async fn send<Q>(
+        &self,
+        url: &Url,
+        query: &Q,
+        outcome: &mut RequestOutcome,
+    ) -> anyhow::Result<(reqwest::Response, String)>
+    where
+        Q: Serialize,
+    {
+        let client = self.http_client().inspect_err(|_| {
+            outcome.error_kind = Some(ErrorKind::Request);
+        })?;
 
         let response = client
             .get(url.clone())
-            .query(&query)
+            .query(query)
             .send()
             .await
+            .inspect_err(|e| outcome.error_kind = Some(ErrorKind::from_reqwest(e)))
             .context("failed to send request")?;
-        let status = response.status();
-        if status.is_success() {
-            let content_type = response
-                .headers()
-                .get(reqwest::header::CONTENT_TYPE)
-                .and_then(|v| v.to_str().ok())
-                .unwrap_or("")
-                .to_lowercase()
-                .to_string();
-
-            let body = response
-                .text()
-                .await
-                .context("failed to read response body")?;
-            let result = if content_type.contains("xml") {
-                nyaa_parser::list::rss::parse(&body)?
-            } else if content_type.contains("html") {
-                let scheme = url.scheme();
-                let host = url.host_str().unwrap_or("");
-                let url_str = if let Some(port) = url.port() {
-                    format!("{}://{}:{}", scheme, host, port)
-                } else {
-                    format!("{}://{}", scheme, host)
-                };
-                nyaa_parser::list::html::parse(&url_str, &body)?
-            } else {
-                return Err(anyhow::anyhow!(
-                    "unsupported content type: {}",
-                    content_type
-                ));
-            };
 
-            let elapsed_time = begin.elapsed().as_secs_f64();
-            self.request_tracker.as_ref().map(|tracker| {
-                tracker.track_request(&self.mirror_id, &url, &query, true, elapsed_time)
-            });
+        let status = response.status();
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+            .to_lowercase()
+            .to_string();
+        outcome.upstream_status = Some(status.as_u16());
+        outcome.content_type = Some(content_type.clone()).filter(|v| !v.is_empty());
 
-            self.cache
-                .lock()
-                .await
-                .put(&url, query, self.cache_duration, &result);
-            return Ok(result);
-        } else {
+        if !status.is_success() {
+            outcome.error_kind = Some(ErrorKind::from_status(status));
             let error_body = response
                 .text()
                 .await
                 .unwrap_or("failed to read error response body".to_string());
-
-            let elapsed_time = begin.elapsed().as_secs_f64();
-            self.request_tracker.as_ref().map(|tracker| {
-                tracker.track_request(&self.mirror_id, &url, &query, false, elapsed_time)
-            });
-
+            outcome.response_bytes = Some(error_body.len() as u64);
             return Err(anyhow::anyhow!(
                 "request failed with status code {}:\n{}",
                 status,
                 error_body
             ));
         }
+
+        Ok((response, content_type))
+    }
+
+    /// Fetches `url` from the upstream as text, recording status, size,
+    /// content type and the kind of any failure in `outcome`. Callers are
+    /// responsible for acquiring the rate limiter first.
+    async fn fetch<Q>(
+        &self,
+        url: &Url,
+        query: &Q,
+        outcome: &mut RequestOutcome,
+    ) -> anyhow::Result<Page>
+    where
+        Q: Serialize,
+    {
+        let (response, content_type) = self.send(url, query, outcome).await?;
+        let body = response
+            .text()
+            .await
+            .inspect_err(|e| outcome.error_kind = Some(ErrorKind::from_reqwest(e)))
+            .context("failed to read response body")?;
+        outcome.response_bytes = Some(body.len() as u64);
+
+        Ok(Page { content_type, body })
+    }
+
+    fn track<Q>(&self, url: &Url, query: &Q, begin: std::time::Instant, mut outcome: RequestOutcome)
+    where
+        Q: Serialize,
+    {
+        outcome.elapsed_time = begin.elapsed().as_secs_f64();
+        if let Some(tracker) = self.request_tracker.as_ref() {
+            tracker.track_request(&self.mirror_id, url, query, outcome)
+        }
+    }
+
+    /// Fetches the search results for `query`. User-scoped queries list the
+    /// torrent table of the user page; RSS queries are parsed as feeds.
+    pub async fn list(&self, query: &SearchQuery) -> anyhow::Result<Vec<nyaa_parser::ListItem>> {
+        let items = self.fetch_list(query, true).await?;
+        Ok(items.expect("waiting for the rate limiter always fetches"))
+    }
+
+    /// Like [`Client::list`], but without `wait`, returns `None` instead of
+    /// waiting when the rate limiter has no room for an upstream request.
+    async fn fetch_list(
+        &self,
+        query: &SearchQuery,
+        wait: bool,
+    ) -> anyhow::Result<Option<Vec<nyaa_parser::ListItem>>> {
+        tracing::debug!("fetching list from {:?}", self.url.to_string());
+
+        let begin = std::time::Instant::now();
+
+        let url = query.to_url(&self.url);
+        if let Some(value) = self.cache.lock().await.get(&url, &"list") {
+            if let Some(tracker) = self.request_tracker.as_ref() {
+                tracker.track_request_cached(&self.mirror_id, &url, &())
+            }
+            return Ok(Some(value));
+        }
+
+        if wait {
+            self.rate_limiter.acquire().await;
+        } else if !self.rate_limiter.try_acquire().await {
+            return Ok(None);
+        }
+
+        let mut outcome = RequestOutcome::default();
+        let result = match self.fetch(&url, &(), &mut outcome).await {
+            Ok(page) => self.parse_list(&url, &page, query.is_complete_listing(), &mut outcome),
+            Err(e) => Err(e),
+        };
+
+        outcome.success = result.is_ok();
+        outcome.item_count = result.as_ref().ok().map(|items| items.len());
+        self.track(&url, &(), begin, outcome);
+
+        let result = result?;
+        self.cache
+            .lock()
+            .await
+            .put(&url, &"list", self.cache_duration, &result);
+        Ok(Some(result))
+    }
+
+    /// Fetches page `query.page` of the results matching `expression`.
+    /// Predicates are applied to upstream results starting from the first
+    /// page, so filling a page may take several upstream requests. The
+    /// result page size is the upstream page size.
+    ///
+    /// Only the first upstream page waits for the rate limiter. Later ones
+    /// are fetched while it has room, and the page is truncated once it has
+    /// none, so the client isn't held for a whole window.
+    pub async fn list_filtered(
+        &self,
+        query: &SearchQuery,
+        expression: &Expression,
+    ) -> anyhow::Result<FilteredList> {
+        let wanted = query.page.max(1);
+        let mut matches = Vec::new();
+        let mut page_size = None;
+        let mut upstream_pages = 0;
+        let mut truncated = true;
+        while upstream_pages < self.search_max_pages {
+            let page = query.clone().page(upstream_pages + 1);
+            let Some(items) = self.fetch_list(&page, upstream_pages == 0).await? else {
+                break;
+            };
+            upstream_pages += 1;
+            let page_size = *page_size.get_or_insert(items.len());
+            let last = items.len() < page_size || items.is_empty();
+            matches.extend(items.into_iter().filter(|item| expression.matches(item)));
+            if last || matches.len() >= wanted * page_size {
+                truncated = false;
+                break;
+            }
+        }
+
+        let page_size = page_size.unwrap_or_default();
+        let items = matches
+            .into_iter()
+            .skip((wanted - 1) * page_size)
+            .take(page_size)
+            .collect();
+        Ok(FilteredList {
+            items,
+            upstream_pages,
+            truncated,
+        })
+    }
+
+    /// Fetches the user page of `name` with the filters, sort and page of
+    /// `query`.
+    pub async fn user(
+        &self,
+        name: &str,
+        query: &SearchQuery,
+    ) -> anyhow::Result<nyaa_parser::UserPage> {
+        tracing::debug!("fetching user {} from {:?}", name, self.url.to_string());
+
+        let begin = std::time::Instant::now();
+
+        // List pages of the same URL are cached under their own key, as
+        // they hold a different type.
+        let url = query.clone().user(name).rss(false).to_url(&self.url);
+        if let Some(value) = self.cache.lock().await.get(&url, &"user") {
+            if let Some(tracker) = self.request_tracker.as_ref() {
+                tracker.track_request_cached(&self.mirror_id, &url, &())
+            }
+            return Ok(value);
+        }
+
+        self.rate_limiter.acquire().await;
+
+        let mut outcome = RequestOutcome::default();
+        let result = match self.fetch(&url, &(), &mut outcome).await {
+            Ok(page) => {
+                let (drift, parsed) = self
+                    .parser
+                    .parse_user_checked(&Self::base_url(&url), &page.body);
+                self.check_layout(&url, drift, &mut outcome);
+                parsed
+                    .inspect_err(|_| outcome.error_kind = Some(ErrorKind::Parse))
+                    .context("failed to parse response body")
+            }
+            Err(e) => Err(e),
+        };
+
+        outcome.success = result.is_ok();
+        outcome.item_count = result.as_ref().ok().map(|page| page.items.len());
+        self.track(&url, &(), begin, outcome);
+
+        let result = result?;
+        self.cache
+            .lock()
+            .await
+            .put(&url, &"user", self.cache_duration, &result);
+        Ok(result)
+    }
+
+    /// Parses a list page and records its items in the index. `complete`
+    /// lists hold every torrent between their lowest and highest id.
+    fn parse_list(
+        &self,
+        url: &Url,
+        page: &Page,
+        complete: bool,
+        outcome: &mut RequestOutcome,
+    ) -> anyhow::Result<Vec<nyaa_parser::ListItem>> {
+        let parsed = if page.content_type.contains("xml") {
+            nyaa_parser::list::rss::parse(&page.body).map_err(anyhow::Error::from)
+        } else if page.content_type.contains("html") {
+            let (drift, parsed) = self
+                .parser
+                .parse_list_checked(&Self::base_url(url), &page.body);
+            self.check_layout(url, drift, outcome);
+            parsed.map_err(anyhow::Error::from)
+        } else {
+            outcome.error_kind = Some(ErrorKind::UnsupportedContentType);
+            Err(anyhow::anyhow!(
+                "unsupported content type: {}",
+                page.content_type
+            ))
+        };
+        if parsed.is_err() && outcome.error_kind.is_none() {
+            outcome.error_kind = Some(ErrorKind::Parse);
+        }
+        if let (Ok(items), Some(index)) = (&parsed, self.index.as_ref()) {
+            index.record_list(&self.mirror_id, items, complete);
+        }
+        parsed
+    }
+
+    /// Fetches the front page, bypassing the cache, to check that the
+    /// upstream is reachable and the parser still extracts rows. Probes use
+    /// the slots reserved in the rate limiter, so users can't starve them.
+    /// Returns `None` when those are used up.
+    pub async fn probe(&self) -> Option<RequestOutcome> {
+        if !self.rate_limiter.try_acquire_reserved().await {
+            return None;
+        }
+
+        let begin = std::time::Instant::now();
+        let url = self.url.clone();
+        let mut outcome = RequestOutcome::default();
+        let result = match self.fetch(&url, &(), &mut outcome).await {
+            Ok(page) => self.parse_list(&url, &page, true, &mut outcome),
+            Err(e) => Err(e),
+        };
+
+        match &result {
+            Ok(items) if items.is_empty() => {
+                tracing::warn!("probe of {} parsed no items", url);
+                outcome.error_kind = Some(ErrorKind::Empty);
+            }
+            Ok(items) => {
+                outcome.success = true;
+                outcome.item_count = Some(items.len());
+            }
+            Err(e) => tracing::warn!("probe of {} failed: {:?}", url, e),
+        }
+        outcome.elapsed_time = begin.elapsed().as_secs_f64();
+
+        if let Some(tracker) = self.request_tracker.as_ref() {
+            tracker.track_probe(&self.mirror_id, &url, outcome.clone());
+        }
+        Some(outcome)
     }
 
     pub async fn view(&self, id: &str) -> anyhow::Result<nyaa_parser::View> {
         tracing::debug!("fetching view from {:?}", self.url.to_string());
 
         let begin = std::time::Instant::now();
-        let url = self.url.clone();
-        if let Some(value) = self.cache.lock().await.get(&url, &id) {
-            self.request_tracker
-                .as_ref()
-                .map(|tracker| tracker.track_request_cached(&self.mirror_id, &url, &id));
+        let url = self.url.join(&format!("/view/{}", id))?;
+        if let Some(value) = self.cache.lock().await.get(&url, &"view") {
+            if let Some(tracker) = self.request_tracker.as_ref() {
+                tracker.track_request_cached(&self.mirror_id, &url, &())
+            }
             return Ok(value);
         }
 
         self.rate_limiter.acquire().await;
 
-        let url = self.url.join(&format!("/view/{}", id))?;
-        let mut client = reqwest::Client::builder()
-            .connection_verbose(true)
-            .user_agent(self.user_agent.clone())
-            .timeout(self.timeout);
+        let mut outcome = RequestOutcome::default();
+        let result = match self.fetch(&url, &(), &mut outcome).await {
+            Ok(page) => {
+                let (drift, parsed) = self
+                    .parser
+                    .parse_view_checked(&Self::base_url(&url), &page.body);
+                self.check_layout(&url, drift, &mut outcome);
+                parsed
+                    .inspect_err(|_| outcome.error_kind = Some(ErrorKind::Parse))
+                    .context("failed to parse response body")
+                    .map(|view| (view, page.body))
+            }
+            Err(e) => Err(e),
+        };
 
-        if let Some(local_addr) = &self.local_addr {
-            client = client.local_address(Some(
-                local_addr
-                    .parse()
-                    .context("failed to parse local address")?,
-            ));
+        outcome.success = result.is_ok();
+        outcome.item_count = result.as_ref().ok().map(|_| 1);
+        let not_found = outcome.error_kind == Some(ErrorKind::NotFound);
+        self.track(&url, &(), begin, outcome);
+
+        if not_found && let (Some(index), Ok(id)) = (self.index.as_ref(), id.parse()) {
+            index
+                .mark_deleted(&self.mirror_id, id, DeletionReason::NotFound)
+                .await;
         }
-        if let Some(interface) = &self.interface {
-            client = client.interface(interface);
+        let (result, html) = result?;
+        if let Some(index) = self.index.as_ref() {
+            index.record_view(
+                &self.mirror_id,
+                &result,
+                self.archive_html.then_some(html.as_str()),
+            );
         }
+        self.cache
+            .lock()
+            .await
+            .put(&url, &"view", self.cache_duration, &result);
+        Ok(result)
+    }
 
-        let client = client.build().context("failed to build HTTP client")?;
+    /// Streams the pages of `query`, starting at `query.page`, until the last
+    /// page, a page of duplicates or `options.max_pages`. The client is
+    /// locked for one page at a time so other requests are served in
+    /// between. An error ends the stream.
+    pub fn pages(
+        client: Arc<Mutex<Client>>,
+        query: SearchQuery,
+        options: PageStreamOptions,
+    ) -> impl Stream<Item = anyhow::Result<PageEvent>> + Send + 'static {
+        let stream = PageStream {
+            client,
+            query,
+            options,
+            fetched: 0,
+            page_size: None,
+            seen: HashSet::new(),
+            pending_end: None,
+            done: false,
+        };
+        futures_util::stream::unfold(stream, |mut stream| async move {
+            if stream.done {
+                return None;
+            }
+            let event = stream.next().await;
+            Some((event, stream))
+        })
+    }
 
-        let response = client
-            .get(url.clone())
-            .send()
-            .await
-            .context("failed to send request")?;
-        let status = response.status();
-        if status.is_success() {
-            let body = response
-                .text()
-                .await
-                .context("failed to read response body")?;
-            let scheme = url.scheme();
-            let host = url.host_str().unwrap_or("");
-            let url_str = if let Some(port) = url.port() {
-                format!("{}://{}:{}", scheme, host, port)
-            } else {
-                format!("{}://{}", scheme, host)
-            };
-            let result = nyaa_parser::view::html::parse(&url_str, &body)
-                .context("failed to parse response body")?;
+    /// Downloads the `.torrent` file of `id`.
+    pub async fn torrent_file(&self, id: &str) -> anyhow::Result<Vec<u8>> {
+        tracing::debug!("fetching torrent file from {:?}", self.url.to_string());
 
-            let elapsed_time = begin.elapsed().as_secs_f64();
-            self.request_tracker.as_ref().map(|tracker| {
-                tracker.track_request(&self.mirror_id, &url, &id, true, elapsed_time)
-            });
+        let begin = std::time::Instant::now();
+        let url = self.url.join(&format!("/download/{}.torrent", id))?;
+        if let Some(value) = self.cache.lock().await.get(&url, &"torrent") {
+            if let Some(tracker) = self.request_tracker.as_ref() {
+                tracker.track_request_cached(&self.mirror_id, &url, &())
+            }
+            return Ok(value);
+        }
 
-            self.cache
-                .lock()
-                .await
-                .put(&url, &("view", &id), self.cache_duration, &result);
-            return Ok(result);
-        } else {
-            let error_body = response
-                .text()
+        self.rate_limiter.acquire().await;
+
+        let mut outcome = RequestOutcome::default();
+        let result = match self.send(&url, &(), &mut outcome).await {
+            Ok((response, _)) => response
+                .bytes()
                 .await
-                .unwrap_or("failed to read error response body".to_string());
+                .inspect_err(|e| outcome.error_kind = Some(ErrorKind::from_reqwest(e)))
+                .context("failed to read response body")
+                .map(|body| body.to_vec()),
+            Err(e) => Err(e),
+        };
 
-            let elapsed_time = begin.elapsed().as_secs_f64();
-            self.request_tracker.as_ref().map(|tracker| {
-                tracker.track_request(&self.mirror_id, &url, &id, false, elapsed_time)
-            });
+        outcome.success = result.is_ok();
+        outcome.response_bytes = result.as_ref().ok().map(|body| body.len() as u64);
+        outcome.item_count = result.as_ref().ok().map(|_| 1);
+        self.track(&url, &(), begin, outcome);
 
-            return Err(anyhow::anyhow!(
-                "request failed with status code {}:\n{}",
-                status,
-                error_body
-            ));
-        }
+        let result = result?;
+        self.cache
+            .lock()
+            .await
+            .put(&url, &"torrent", self.cache_duration, &result);
+        Ok(result)
+    }
+
+    /// Whether less than `share` of the rate limiter budget is in use, for
+    /// background work that should wait for a quieter moment.
+    pub async fn has_budget(&self, share: f64) -> bool {
+        self.rate_limiter.has_share(share).await
     }
 
     pub async fn magnet_link(&self, id: &str) -> anyhow::Result<String> {
@@ -267,7 +679,7 @@ impl Client {
             return Ok(magnet_link);
         }
 
-        return Err(anyhow::anyhow!("failed to get magnet link for id {}", id));
+        Err(anyhow::anyhow!("failed to get magnet link for id {}", id))
     }
 }
 
@@ -281,8 +693,13 @@ pub struct ClientBuilder {
     cache_dir: PathBuf,
     cache_size: u64,
     cache_duration: Duration,
+    search_max_pages: usize,
     rate_limiter: RateLimiter,
     request_tracker: Option<RequestTracker>,
+    notifier: Option<Notifier>,
+    index: Option<Index>,
+    archive_html: bool,
+    parser: Option<Arc<ParserContext>>,
 }
 
 impl ClientBuilder {
@@ -295,8 +712,13 @@ impl ClientBuilder {
             cache_dir: PathBuf::from("cache"),
             cache_size: 64 * 1024 * 1024,
             cache_duration: Duration::from_secs(60 * 60),
+            search_max_pages: 10,
             rate_limiter: RateLimiter::new(10, Duration::from_secs(1)),
             request_tracker: None,
+            notifier: None,
+            index: None,
+            archive_html: false,
+            parser: None,
             interface: None,
             local_addr: None,
         }
@@ -312,6 +734,13 @@ impl ClientBuilder {
         self
     }
 
+    /// Maximum number of upstream pages scanned to fill one page of
+    /// filtered search results.
+    pub fn search_max_pages(mut self, pages: usize) -> Self {
+        self.search_max_pages = pages.max(1);
+        self
+    }
+
     pub fn cache_duration(mut self, duration: Duration) -> Self {
         self.cache_duration = duration;
         self
@@ -347,6 +776,26 @@ impl ClientBuilder {
         self
     }
 
+    pub fn notifier(mut self, notifier: Notifier) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    pub fn index(mut self, index: Index) -> Self {
+        self.index = Some(index);
+        self
+    }
+
+    pub fn archive_html(mut self, archive_html: bool) -> Self {
+        self.archive_html = archive_html;
+        self
+    }
+
+    pub fn parser(mut self, parser: Arc<ParserContext>) -> Self {
+        self.parser = Some(parser);
+        self
+    }
+
     pub fn build(self) -> Client {
         let cache = Cache::new(self.cache_dir, self.cache_size).expect("failed to create cache");
         let cache = Arc::new(Mutex::new(cache));
@@ -360,7 +809,270 @@ impl ClientBuilder {
             cache,
             rate_limiter: self.rate_limiter,
             request_tracker: self.request_tracker,
+            notifier: self.notifier,
+            index: self.index,
+            archive_html: self.archive_html,
+            parser: self.parser.unwrap_or_else(|| {
+                Arc::new(
+                    ParserContext::new(LayoutProfile::nyaa()).expect("built-in profile is valid"),
+                )
+            }),
             cache_duration: self.cache_duration,
+            search_max_pages: self.search_max_pages,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        collections::HashMap,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use axum::{Router, extract::Query, response::Html, routing::get};
+
+    use super::*;
+
+    /// A list page in the nyaa.si layout with a row per id. Odd ids have 10
+    /// seeders, even ids none.
+    fn list_page(ids: &[usize]) -> String {
+        let rows = ids
+            .iter()
+            .map(|id| {
+                format!(
+                    r#"<tr class="default">
+    <td><a href="/?c=1_2" title="Anime - English-translated"></a></td>
+    <td colspan="2"><a href="/view/{id}" title="Torrent {id}">Torrent {id}</a></td>
+    <td class="text-center">
+        <a href="/download/{id}.torrent"><i class="fa fa-fw fa-download"></i></a>
+        <a href="magnet:?xt=urn:btih:{hash:040x}&amp;dn=Torrent"><i class="fa fa-fw fa-magnet"></i></a>
+    </td>
+    <td class="text-center">1.0 GiB</td>
+    <td class="text-center" data-timestamp="{timestamp}">2025-03-29 09:14</td>
+    <td class="text-center">{seeders}</td>
+    <td class="text-center">0</td>
+    <td class="text-center">0</td>
+</tr>"#,
+                    id = id,
+                    hash = id,
+                    timestamp = 1_743_239_642 + id,
+                    seeders = id % 2 * 10,
+                )
+            })
+            .collect::<String>();
+        format!(
+            r#"<table class="table torrent-list"><thead><tr><th class="hdr-category"></th></tr></thead><tbody>{}</tbody></table>"#,
+            rows
+        )
+    }
+
+    /// Serves `pages` of ids as list pages, and empty pages past them.
+    /// Returns a client for it and the number of requests served.
+    async fn upstream(
+        pages: Vec<Vec<usize>>,
+        configure: impl FnOnce(ClientBuilder) -> ClientBuilder,
+    ) -> (Client, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let router = Router::new().route(
+            "/",
+            get({
+                let requests = requests.clone();
+                move |Query(params): Query<HashMap<String, String>>| async move {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    let page = params.get("p").map_or(1, |p| p.parse().unwrap());
+                    Html(list_page(
+                        pages.get(page - 1).map(Vec::as_slice).unwrap_or_default(),
+                    ))
+                }
+            }),
+        );
+        let url = Url::parse(&crate::download_client::stub_server(router).await).unwrap();
+        let cache_dir =
+            std::env::temp_dir().join(format!("nyaa-mirror-test-{}", uuid::Uuid::new_v4()));
+        let client = configure(Client::builder("nyaa", url).cache_dir(cache_dir)).build();
+        (client, requests)
+    }
+
+    fn ids(items: &[nyaa_parser::ListItem]) -> Vec<usize> {
+        items.iter().map(|item| item.id).collect()
+    }
+
+    #[tokio::test]
+    async fn test_list_filtered() {
+        let pages = vec![
+            vec![20, 19, 18, 17],
+            vec![16, 15, 14, 13],
+            vec![12, 11, 10, 9],
+            vec![8, 7],
+        ];
+        let (client, requests) = upstream(pages.clone(), |builder| builder).await;
+        let expression = nyaa_parser::dsl::parse("seeders:>5").unwrap();
+
+        // The first page fills up after two upstream pages.
+        let list = client
+            .list_filtered(&SearchQuery::new(), &expression)
+            .await
+            .unwrap();
+        assert_eq!(ids(&list.items), [19, 17, 15, 13]);
+        assert_eq!(list.upstream_pages, 2);
+        assert!(!list.truncated);
+
+        // Page 0 reads as the first page.
+        let page_zero = SearchQuery {
+            page: 0,
+            ..SearchQuery::new()
+        };
+        let list = client.list_filtered(&page_zero, &expression).await.unwrap();
+        assert_eq!(ids(&list.items), [19, 17, 15, 13]);
+
+        // The second page skips the first page's matches and ends at the
+        // short last upstream page. Pages fetched before come from the cache.
+        let list = client
+            .list_filtered(&SearchQuery::new().page(2), &expression)
+            .await
+            .unwrap();
+        assert_eq!(ids(&list.items), [11, 9, 7]);
+        assert_eq!(list.upstream_pages, 4);
+        assert!(!list.truncated);
+        assert_eq!(requests.load(Ordering::SeqCst), 4);
+
+        // Hitting the page limit first truncates the page.
+        let (client, _) = upstream(pages.clone(), |builder| builder.search_max_pages(3)).await;
+        let list = client
+            .list_filtered(&SearchQuery::new().page(2), &expression)
+            .await
+            .unwrap();
+        assert_eq!(ids(&list.items), [11, 9]);
+        assert_eq!(list.upstream_pages, 3);
+        assert!(list.truncated);
+
+        // So does running out of rate limiter budget, instead of waiting
+        // for the window to move on.
+        let (client, requests) = upstream(pages, |builder| {
+            builder.rate_limiter(RateLimiter::new(3, Duration::from_secs(60)))
+        })
+        .await;
+        let list = client
+            .list_filtered(&SearchQuery::new().page(2), &expression)
+            .await
+            .unwrap();
+        assert_eq!(ids(&list.items), [11, 9]);
+        assert_eq!(list.upstream_pages, 3);
+        assert!(list.truncated);
+        assert_eq!(requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_probe_with_limiter_in_use() {
+        let (client, requests) = upstream(vec![vec![3, 2, 1]], |builder| {
+            builder.rate_limiter(RateLimiter::new(2, Duration::from_secs(60)).reserve(0.1))
+        })
+        .await;
+
+        // A user request takes the only slot users get, but the probe still
+        // runs on the reserved one.
+        client.list(&SearchQuery::new()).await.unwrap();
+        assert!(!client.rate_limiter.try_acquire().await);
+        let outcome = client.probe().await.unwrap();
+        assert!(outcome.success);
+        assert_eq!(outcome.item_count, Some(3));
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+
+        // The reserve is used up until the window moves on.
+        assert!(client.probe().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_user_and_list_cached_apart() {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let router = Router::new().route(
+            "/user/{name}",
+            get({
+                let requests = requests.clone();
+                move || async move {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    Html(format!(
+                        r#"<h3><span data-toggle="tooltip" title="User">uploader</span>'s torrents (2)</h3>{}"#,
+                        list_page(&[2, 1])
+                    ))
+                }
+            }),
+        );
+        let url = Url::parse(&crate::download_client::stub_server(router).await).unwrap();
+        let cache_dir =
+            std::env::temp_dir().join(format!("nyaa-mirror-test-{}", uuid::Uuid::new_v4()));
+        let client = Client::builder("nyaa", url).cache_dir(cache_dir).build();
+
+        // Both fetch the same URL, but each keeps its own cache entry.
+        let query = SearchQuery::new().user("uploader");
+        for _ in 0..2 {
+            let items = client.list(&query).await.unwrap();
+            assert_eq!(ids(&items), [2, 1]);
+            let page = client.user("uploader", &SearchQuery::new()).await.unwrap();
+            assert_eq!(page.user.name, "uploader");
+            assert_eq!(ids(&page.items), [2, 1]);
+        }
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    /// Streams `pages` and returns the id, items and duplicates of each page,
+    /// and how the stream ended.
+    async fn stream(
+        pages: Vec<Vec<usize>>,
+        max_pages: usize,
+    ) -> (Vec<(usize, Vec<usize>, usize)>, StopReason, usize) {
+        use futures_util::StreamExt;
+
+        let (client, _) = upstream(pages, |builder| {
+            builder.rate_limiter(RateLimiter::new(100, Duration::from_secs(1)))
+        })
+        .await;
+        let options = PageStreamOptions {
+            max_pages,
+            ..Default::default()
+        };
+        let events = Client::pages(Arc::new(Mutex::new(client)), SearchQuery::new(), options)
+            .collect::<Vec<_>>()
+            .await;
+        let mut fetched = Vec::new();
+        for event in events {
+            match event.unwrap() {
+                PageEvent::Page {
+                    page,
+                    items,
+                    duplicates,
+                } => fetched.push((page, ids(&items), duplicates)),
+                PageEvent::End { reason, pages } => return (fetched, reason, pages),
+            }
+        }
+        panic!("stream ended without an end event");
+    }
+
+    #[tokio::test]
+    async fn test_pages_stop() {
+        // A short page is the last one.
+        let (pages, reason, count) = stream(vec![vec![6, 5, 4], vec![3, 2]], 10).await;
+        assert_eq!(pages, [(1, vec![6, 5, 4], 0), (2, vec![3, 2], 0)]);
+        assert_eq!((reason, count), (StopReason::LastPage, 2));
+
+        // So is an empty page after full ones.
+        let (pages, reason, count) = stream(vec![vec![4, 3], vec![2, 1]], 10).await;
+        assert_eq!(pages.len(), 2);
+        assert_eq!((reason, count), (StopReason::LastPage, 3));
+
+        let (pages, reason, count) = stream(vec![vec![6, 5], vec![4, 3], vec![2, 1]], 2).await;
+        assert_eq!(pages.len(), 2);
+        assert_eq!((reason, count), (StopReason::MaxPages, 2));
+
+        // Items shifted from an earlier page are dropped, and a page of only
+        // those ends the stream.
+        let (pages, reason, count) = stream(
+            vec![vec![6, 5, 4], vec![4, 3, 2], vec![4, 3, 2], vec![1]],
+            10,
+        )
+        .await;
+        assert_eq!(pages, [(1, vec![6, 5, 4], 0), (2, vec![3, 2], 1)]);
+        assert_eq!((reason, count), (StopReason::Duplicates, 3));
+    }
+}
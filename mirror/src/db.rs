@@ -0,0 +1,60 @@
+//! SQLite plumbing shared by the stores.
+
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use rusqlite::Connection;
+
+/// Opens the database at `path` for concurrent use and brings it up to date
+/// with `migrations`. `label` names the database in logs.
+///
+/// Migrations are applied in order, each in its own transaction. The number
+/// applied so far is stored in `PRAGMA user_version`, so migrations must only
+/// ever be appended.
+pub fn open(path: &Path, migrations: &[&str], label: &str) -> rusqlite::Result<Connection> {
+    let mut conn = Connection::open(path)?;
+    conn.busy_timeout(Duration::from_secs(5))?;
+    conn.pragma_update(None, "journal_mode", "WAL")?;
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    conn.pragma_update(None, "foreign_keys", "ON")?;
+    migrate(&mut conn, migrations, label)?;
+    Ok(conn)
+}
+
+#[cfg(test)]
+pub fn open_in_memory(migrations: &[&str]) -> Connection {
+    let mut conn = Connection::open_in_memory().unwrap();
+    conn.pragma_update(None, "foreign_keys", "ON").unwrap();
+    migrate(&mut conn, migrations, "in-memory").unwrap();
+    conn
+}
+
+fn migrate(conn: &mut Connection, migrations: &[&str], label: &str) -> rusqlite::Result<()> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (index, migration) in migrations.iter().enumerate().skip(version) {
+        tracing::info!("migrating {} database to version {}", label, index + 1);
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration)?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Runs `f` with the locked connection on the blocking thread pool, so
+/// queries don't stall the async workers.
+pub async fn blocking<T>(
+    conn: &Arc<Mutex<Connection>>,
+    f: impl FnOnce(&mut Connection) -> T + Send + 'static,
+) -> T
+where
+    T: Send + 'static,
+{
+    let conn = conn.clone();
+    tokio::task::spawn_blocking(move || f(&mut conn.lock().expect("database poisoned")))
+        .await
+        .expect("database task panicked")
+}
@@ -0,0 +1,277 @@
+//! Deluge Web UI JSON-RPC.
+
+use anyhow::Context;
+use base64::Engine;
+use reqwest::header;
+use tokio::sync::Mutex;
+
+use super::{AddOptions, Torrent};
+
+/// Error code Deluge returns for calls made without a valid session.
+const NOT_AUTHENTICATED: i64 = 1;
+
+#[derive(Debug, serde::Deserialize)]
+struct RpcError {
+    message: String,
+    code: i64,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct RpcResponse {
+    #[serde(default)]
+    result: serde_json::Value,
+    error: Option<RpcError>,
+}
+
+/// Calls `method`, returning the raw response so callers can tell an expired
+/// session apart from other errors.
+async fn call(
+    http: &reqwest::Client,
+    url: &str,
+    cookie: Option<&str>,
+    method: &str,
+    params: serde_json::Value,
+) -> anyhow::Result<(RpcResponse, Option<String>)> {
+    let mut request = http.post(url).json(&serde_json::json!({
+        "method": method,
+        "params": params,
+        "id": 1,
+    }));
+    if let Some(cookie) = cookie {
+        request = request.header(header::COOKIE, cookie);
+    }
+    let response = request
+        .send()
+        .await
+        .with_context(|| format!("failed to call {}", method))?
+        .error_for_status()
+        .with_context(|| format!("{} failed", method))?;
+    let cookie = response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|value| value.split(';').next())
+        .find(|cookie| cookie.starts_with("_session_id="))
+        .map(str::to_string);
+    let response = response
+        .json()
+        .await
+        .with_context(|| format!("failed to parse {} response", method))?;
+    Ok((response, cookie))
+}
+
+fn check(method: &str, response: RpcResponse) -> anyhow::Result<serde_json::Value> {
+    match response.error {
+        Some(error) => Err(anyhow::anyhow!("{} failed: {}", method, error.message)),
+        None => Ok(response.result),
+    }
+}
+
+/// Logs in and connects the Web UI to a daemon if it isn't already.
+async fn login(http: &reqwest::Client, url: &str, password: &str) -> anyhow::Result<String> {
+    let (response, cookie) =
+        call(http, url, None, "auth.login", serde_json::json!([password])).await?;
+    let cookie = match (check("auth.login", response)?, cookie) {
+        (serde_json::Value::Bool(true), Some(cookie)) => cookie,
+        _ => return Err(anyhow::anyhow!("login failed: invalid password")),
+    };
+
+    let (response, _) = call(
+        http,
+        url,
+        Some(&cookie),
+        "web.connected",
+        serde_json::json!([]),
+    )
+    .await?;
+    if check("web.connected", response)? != serde_json::Value::Bool(true) {
+        let (response, _) = call(
+            http,
+            url,
+            Some(&cookie),
+            "web.get_hosts",
+            serde_json::json!([]),
+        )
+        .await?;
+        let host = check("web.get_hosts", response)?
+            .get(0)
+            .and_then(|host| host.get(0))
+            .and_then(|id| id.as_str())
+            .map(str::to_string)
+            .context("the Web UI has no daemon configured")?;
+        let (response, _) = call(
+            http,
+            url,
+            Some(&cookie),
+            "web.connect",
+            serde_json::json!([host]),
+        )
+        .await?;
+        check("web.connect", response)?;
+    }
+    Ok(cookie)
+}
+
+fn request(torrent: &Torrent, options: &AddOptions) -> (&'static str, serde_json::Value) {
+    let mut opts = serde_json::Map::new();
+    opts.insert("add_paused".into(), options.paused.into());
+    if let Some(save_path) = &options.save_path {
+        opts.insert("download_location".into(), save_path.clone().into());
+    }
+    match torrent {
+        Torrent::Magnet(magnet) => ("core.add_torrent_magnet", serde_json::json!([magnet, opts])),
+        Torrent::File { name, data } => {
+            let data = base64::engine::general_purpose::STANDARD.encode(data);
+            (
+                "core.add_torrent_file",
+                serde_json::json!([name, data, opts]),
+            )
+        }
+    }
+}
+
+pub async fn add(
+    http: &reqwest::Client,
+    url: &str,
+    password: &str,
+    session: &Mutex<Option<String>>,
+    torrent: &Torrent,
+    options: &AddOptions,
+) -> anyhow::Result<()> {
+    let (method, params) = request(torrent, options);
+    let mut session = session.lock().await;
+    for attempt in 0..2 {
+        let cookie = match session.as_ref() {
+            Some(cookie) => cookie.clone(),
+            None => session.insert(login(http, url, password).await?).clone(),
+        };
+        let (response, _) = call(http, url, Some(&cookie), method, params.clone()).await?;
+        if matches!(&response.error, Some(error) if error.code == NOT_AUTHENTICATED) && attempt == 0
+        {
+            // The session expired; log in again.
+            *session = None;
+            continue;
+        }
+        let hash = check(method, response)?;
+
+        // Labels come from an optional plugin, so failing to set one
+        // shouldn't fail the whole request.
+        if let (Some(category), Some(hash)) = (&options.category, hash.as_str()) {
+            let result = call(
+                http,
+                url,
+                Some(&cookie),
+                "label.set_torrent",
+                serde_json::json!([hash, category]),
+            )
+            .await
+            .and_then(|(response, _)| check("label.set_torrent", response));
+            if let Err(err) = result {
+                tracing::warn!("failed to label torrent {}: {:#}", hash, err);
+            }
+        }
+        return Ok(());
+    }
+    Err(anyhow::anyhow!("Deluge rejected the session"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{Json, Router, http::HeaderMap, response::IntoResponse, routing::post};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add() {
+        let calls = Arc::new(std::sync::Mutex::new(Vec::<serde_json::Value>::new()));
+        let router = Router::new().route(
+            "/json",
+            post({
+                let calls = calls.clone();
+                move |headers: HeaderMap, Json(body): Json<serde_json::Value>| async move {
+                    calls.lock().unwrap().push(body.clone());
+                    let authenticated = headers.get("cookie").and_then(|v| v.to_str().ok())
+                        == Some("_session_id=abc");
+                    let reply = |result: serde_json::Value| {
+                        Json(serde_json::json!({ "result": result, "error": null, "id": 1 }))
+                    };
+                    match body["method"].as_str().unwrap() {
+                        "auth.login" if body["params"][0] == "secret" => (
+                            [("set-cookie", "_session_id=abc; Path=/json")],
+                            reply(true.into()),
+                        )
+                            .into_response(),
+                        "auth.login" => reply(false.into()).into_response(),
+                        _ if !authenticated => Json(serde_json::json!({
+                            "result": null,
+                            "error": { "message": "Not authenticated", "code": 1 },
+                            "id": 1,
+                        }))
+                        .into_response(),
+                        "web.connected" => reply(false.into()).into_response(),
+                        "web.get_hosts" => {
+                            reply(serde_json::json!([["host1", "127.0.0.1", 58846, "admin"]]))
+                                .into_response()
+                        }
+                        "web.connect" => reply(serde_json::json!([])).into_response(),
+                        "core.add_torrent_magnet" => reply("abcd".into()).into_response(),
+                        "label.set_torrent" => Json(serde_json::json!({
+                            "result": null,
+                            "error": { "message": "Unknown method", "code": 2 },
+                            "id": 1,
+                        }))
+                        .into_response(),
+                        _ => unreachable!(),
+                    }
+                }
+            }),
+        );
+        let url = format!("{}/json", crate::download_client::stub_server(router).await);
+
+        // A stale session is replaced after the first "not authenticated".
+        let session = Mutex::new(Some("_session_id=expired".to_string()));
+        add(
+            &reqwest::Client::new(),
+            &url,
+            "secret",
+            &session,
+            &Torrent::Magnet("magnet:?xt=urn:btih:abcd".into()),
+            &AddOptions {
+                category: Some("anime".into()),
+                save_path: Some("/downloads".into()),
+                paused: true,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(session.lock().await.as_deref(), Some("_session_id=abc"));
+
+        let calls = calls.lock().unwrap();
+        let methods: Vec<_> = calls
+            .iter()
+            .map(|call| call["method"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            methods,
+            [
+                "core.add_torrent_magnet",
+                "auth.login",
+                "web.connected",
+                "web.get_hosts",
+                "web.connect",
+                "core.add_torrent_magnet",
+                "label.set_torrent",
+            ]
+        );
+        assert_eq!(
+            calls[5]["params"],
+            serde_json::json!([
+                "magnet:?xt=urn:btih:abcd",
+                { "add_paused": true, "download_location": "/downloads" },
+            ])
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Adapters that add torrents to torrent clients.
+
+use std::{sync::Arc, time::Duration};
+
+use tokio::sync::Mutex;
+
+use crate::cli::{DownloadClientConfig, DownloadClientKind};
+
+mod deluge;
+mod qbittorrent;
+mod transmission;
+
+/// A torrent to add, either as a magnet link or as a `.torrent` file.
+#[derive(Debug, Clone)]
+pub enum Torrent {
+    Magnet(String),
+    File { name: String, data: Vec<u8> },
+}
+
+impl Torrent {
+    /// Fetches torrent `id` through `client`, returning the source that was
+    /// actually used.
+    pub async fn fetch(
+        client: &crate::client::Client,
+        id: &str,
+        source: Source,
+    ) -> anyhow::Result<(Self, Source)> {
+        let magnet = match source {
+            Source::Magnet => client.magnet_link(id).await.ok(),
+            Source::Torrent => None,
+        };
+        if let Some(magnet) = magnet {
+            return Ok((Torrent::Magnet(magnet), Source::Magnet));
+        }
+        let data = client.torrent_file(id).await?;
+        Ok((
+            Torrent::File {
+                name: format!("{}.torrent", id),
+                data,
+            },
+            Source::Torrent,
+        ))
+    }
+}
+
+/// Where a torrent is taken from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// The magnet link, falling back to the `.torrent` file when the view
+    /// page has none.
+    #[default]
+    Magnet,
+    Torrent,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AddOptions {
+    pub category: Option<String>,
+    pub save_path: Option<String>,
+    pub paused: bool,
+}
+
+#[derive(Debug)]
+struct Inner {
+    config: DownloadClientConfig,
+    http: reqwest::Client,
+    /// Session cookie or token, reused until the client rejects it.
+    session: Mutex<Option<String>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct DownloadClient {
+    inner: Arc<Inner>,
+}
+
+impl DownloadClient {
+    pub fn new(config: DownloadClientConfig) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(config.timeout.unwrap_or(Duration::from_secs(30)))
+            .build()
+            .expect("failed to build HTTP client");
+        Self {
+            inner: Arc::new(Inner {
+                config,
+                http,
+                session: Mutex::new(None),
+            }),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.inner.config.id
+    }
+
+    /// Options for a request, falling back to the configured defaults.
+    pub fn options(
+        &self,
+        category: Option<String>,
+        save_path: Option<String>,
+        paused: Option<bool>,
+    ) -> AddOptions {
+        let config = &self.inner.config;
+        AddOptions {
+            category: category.or_else(|| config.category.clone()),
+            save_path: save_path.or_else(|| config.save_path.clone()),
+            paused: paused.or(config.paused).unwrap_or(false),
+        }
+    }
+
+    pub async fn add(&self, torrent: &Torrent, options: &AddOptions) -> anyhow::Result<()> {
+        let inner = &self.inner;
+        match &inner.config.kind {
+            DownloadClientKind::QBittorrent {
+                url,
+                username,
+                password,
+            } => {
+                qbittorrent::add(
+                    &inner.http,
+                    url,
+                    (username, &password.0),
+                    &inner.session,
+                    torrent,
+                    options,
+                )
+                .await
+            }
+            DownloadClientKind::Transmission {
+                url,
+                username,
+                password,
+            } => {
+                let credentials = username
+                    .as_deref()
+                    .map(|username| (username, password.as_ref().map(|p| p.0.as_str())));
+                transmission::add(
+                    &inner.http,
+                    url,
+                    credentials,
+                    &inner.session,
+                    torrent,
+                    options,
+                )
+                .await
+            }
+            DownloadClientKind::Deluge { url, password } => {
+                deluge::add(
+                    &inner.http,
+                    url,
+                    &password.0,
+                    &inner.session,
+                    torrent,
+                    options,
+                )
+                .await
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DownloadClients {
+    clients: Vec<DownloadClient>,
+}
+
+impl DownloadClients {
+    pub fn new(configs: Vec<DownloadClientConfig>) -> Self {
+        Self {
+            clients: configs.into_iter().map(DownloadClient::new).collect(),
+        }
+    }
+
+    /// The client with `id`, or the first one when no id is given.
+    pub fn find(&self, id: Option<&str>) -> Option<&DownloadClient> {
+        match id {
+            Some(id) => self.clients.iter().find(|client| client.id() == id),
+            None => self.clients.first(),
+        }
+    }
+}
+
+/// Serves `router` on a random local port for tests.
+#[cfg(test)]
+pub(crate) async fn stub_server(router: axum::Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    format!("http://{}", addr)
+}
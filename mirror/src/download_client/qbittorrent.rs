@@ -0,0 +1,192 @@
+//! qBittorrent Web API v2.
+
+use anyhow::Context;
+use reqwest::{StatusCode, header, multipart};
+use tokio::sync::Mutex;
+
+use super::{AddOptions, Torrent};
+
+/// Logs in and returns the session cookie.
+async fn login(
+    http: &reqwest::Client,
+    url: &str,
+    credentials: (&str, &str),
+) -> anyhow::Result<String> {
+    let response = http
+        .post(format!("{}/api/v2/auth/login", url))
+        .header(header::REFERER, url)
+        .form(&[("username", credentials.0), ("password", credentials.1)])
+        .send()
+        .await
+        .context("failed to send login request")?
+        .error_for_status()
+        .context("login failed")?;
+    let cookie = response
+        .headers()
+        .get_all(header::SET_COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .filter_map(|value| value.split(';').next())
+        .find(|cookie| cookie.starts_with("SID="))
+        .map(str::to_string);
+    let body = response.text().await.unwrap_or_default();
+    match cookie {
+        Some(cookie) if body.trim() != "Fails." => Ok(cookie),
+        _ => Err(anyhow::anyhow!(
+            "login failed: invalid username or password"
+        )),
+    }
+}
+
+fn form(torrent: &Torrent, options: &AddOptions) -> anyhow::Result<multipart::Form> {
+    let paused = options.paused.to_string();
+    let mut form = match torrent {
+        Torrent::Magnet(magnet) => multipart::Form::new().text("urls", magnet.clone()),
+        Torrent::File { name, data } => multipart::Form::new().part(
+            "torrents",
+            multipart::Part::bytes(data.clone())
+                .file_name(name.clone())
+                .mime_str("application/x-bittorrent")?,
+        ),
+    }
+    // qBittorrent 5 renamed `paused` to `stopped`.
+    .text("paused", paused.clone())
+    .text("stopped", paused);
+    if let Some(category) = &options.category {
+        form = form.text("category", category.clone());
+    }
+    if let Some(save_path) = &options.save_path {
+        form = form.text("savepath", save_path.clone());
+    }
+    Ok(form)
+}
+
+pub async fn add(
+    http: &reqwest::Client,
+    url: &str,
+    credentials: (&str, &str),
+    session: &Mutex<Option<String>>,
+    torrent: &Torrent,
+    options: &AddOptions,
+) -> anyhow::Result<()> {
+    let url = url.trim_end_matches('/');
+    let mut session = session.lock().await;
+    for attempt in 0..2 {
+        let cookie = match session.as_ref() {
+            Some(cookie) => cookie.clone(),
+            None => session.insert(login(http, url, credentials).await?).clone(),
+        };
+        let response = http
+            .post(format!("{}/api/v2/torrents/add", url))
+            .header(header::REFERER, url)
+            .header(header::COOKIE, cookie)
+            .multipart(form(torrent, options)?)
+            .send()
+            .await
+            .context("failed to send add request")?;
+        if response.status() == StatusCode::FORBIDDEN && attempt == 0 {
+            // The session expired; log in again.
+            *session = None;
+            continue;
+        }
+        let body = response
+            .error_for_status()
+            .context("add request failed")?
+            .text()
+            .await
+            .unwrap_or_default();
+        if body.trim() == "Fails." {
+            return Err(anyhow::anyhow!("qBittorrent rejected the torrent"));
+        }
+        return Ok(());
+    }
+    Err(anyhow::anyhow!("qBittorrent rejected the session"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        Router,
+        http::{HeaderMap, StatusCode},
+        response::IntoResponse,
+        routing::post,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add() {
+        let added = Arc::new(std::sync::Mutex::new(Vec::<String>::new()));
+        let router = Router::new()
+            .route(
+                "/api/v2/auth/login",
+                post(|body: String| async move {
+                    if body == "username=admin&password=secret" {
+                        ([("set-cookie", "SID=abc; HttpOnly; path=/")], "Ok.").into_response()
+                    } else {
+                        "Fails.".into_response()
+                    }
+                }),
+            )
+            .route(
+                "/api/v2/torrents/add",
+                post({
+                    let added = added.clone();
+                    move |headers: HeaderMap, body: String| async move {
+                        if headers.get("cookie").and_then(|v| v.to_str().ok()) != Some("SID=abc") {
+                            return StatusCode::FORBIDDEN.into_response();
+                        }
+                        added.lock().unwrap().push(body);
+                        "Ok.".into_response()
+                    }
+                }),
+            );
+        let url = crate::download_client::stub_server(router).await;
+
+        let http = reqwest::Client::new();
+        // A stale session is replaced after the first 403.
+        let session = Mutex::new(Some("SID=expired".to_string()));
+        let options = AddOptions {
+            category: Some("anime".into()),
+            save_path: Some("/downloads/anime".into()),
+            paused: true,
+        };
+        add(
+            &http,
+            &url,
+            ("admin", "secret"),
+            &session,
+            &Torrent::Magnet("magnet:?xt=urn:btih:abcd".into()),
+            &options,
+        )
+        .await
+        .unwrap();
+        assert_eq!(session.lock().await.as_deref(), Some("SID=abc"));
+
+        let added = added.lock().unwrap().clone();
+        assert_eq!(added.len(), 1);
+        for expected in [
+            "name=\"urls\"\r\n\r\nmagnet:?xt=urn:btih:abcd",
+            "name=\"category\"\r\n\r\nanime",
+            "name=\"savepath\"\r\n\r\n/downloads/anime",
+            "name=\"paused\"\r\n\r\ntrue",
+        ] {
+            assert!(added[0].contains(expected), "missing {:?}", expected);
+        }
+
+        let session = Mutex::new(None);
+        let error = add(
+            &http,
+            &url,
+            ("admin", "wrong"),
+            &session,
+            &Torrent::Magnet("magnet:?xt=urn:btih:abcd".into()),
+            &options,
+        )
+        .await
+        .unwrap_err();
+        assert!(error.to_string().contains("login failed"));
+    }
+}
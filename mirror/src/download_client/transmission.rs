@@ -0,0 +1,165 @@
+//! Transmission RPC.
+
+use anyhow::Context;
+use base64::Engine;
+use reqwest::StatusCode;
+use tokio::sync::Mutex;
+
+use super::{AddOptions, Torrent};
+
+const SESSION_HEADER: &str = "X-Transmission-Session-Id";
+
+#[derive(Debug, serde::Deserialize)]
+struct RpcResponse {
+    result: String,
+}
+
+fn request(torrent: &Torrent, options: &AddOptions) -> serde_json::Value {
+    let mut arguments = serde_json::Map::new();
+    match torrent {
+        Torrent::Magnet(magnet) => {
+            arguments.insert("filename".into(), magnet.clone().into());
+        }
+        Torrent::File { data, .. } => {
+            let metainfo = base64::engine::general_purpose::STANDARD.encode(data);
+            arguments.insert("metainfo".into(), metainfo.into());
+        }
+    }
+    arguments.insert("paused".into(), options.paused.into());
+    if let Some(save_path) = &options.save_path {
+        arguments.insert("download-dir".into(), save_path.clone().into());
+    }
+    if let Some(category) = &options.category {
+        arguments.insert("labels".into(), serde_json::json!([category]));
+    }
+    serde_json::json!({
+        "method": "torrent-add",
+        "arguments": arguments,
+    })
+}
+
+pub async fn add(
+    http: &reqwest::Client,
+    url: &str,
+    credentials: Option<(&str, Option<&str>)>,
+    session: &Mutex<Option<String>>,
+    torrent: &Torrent,
+    options: &AddOptions,
+) -> anyhow::Result<()> {
+    let body = request(torrent, options);
+    let mut session = session.lock().await;
+    for _ in 0..2 {
+        let mut request = http.post(url).json(&body);
+        if let Some(id) = session.as_ref() {
+            request = request.header(SESSION_HEADER, id);
+        }
+        if let Some((username, password)) = credentials {
+            request = request.basic_auth(username, password);
+        }
+        let response = request.send().await.context("failed to send RPC request")?;
+
+        // Transmission answers 409 with a fresh session id, to be sent with
+        // the retried request.
+        if response.status() == StatusCode::CONFLICT {
+            *session = response
+                .headers()
+                .get(SESSION_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+            continue;
+        }
+        let response: RpcResponse = response
+            .error_for_status()
+            .context("RPC request failed")?
+            .json()
+            .await
+            .context("failed to parse RPC response")?;
+        if response.result != "success" {
+            return Err(anyhow::anyhow!(
+                "Transmission refused the torrent: {}",
+                response.result
+            ));
+        }
+        return Ok(());
+    }
+    Err(anyhow::anyhow!("Transmission rejected the session id"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        Json, Router,
+        http::{HeaderMap, StatusCode},
+        response::IntoResponse,
+        routing::post,
+    };
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_add() {
+        let added = Arc::new(std::sync::Mutex::new(Vec::<serde_json::Value>::new()));
+        let router = Router::new().route(
+            "/transmission/rpc",
+            post({
+                let added = added.clone();
+                move |headers: HeaderMap, Json(body): Json<serde_json::Value>| async move {
+                    if headers.get(SESSION_HEADER).and_then(|v| v.to_str().ok()) != Some("s1") {
+                        return (StatusCode::CONFLICT, [(SESSION_HEADER, "s1")]).into_response();
+                    }
+                    // "user:pass" in base64.
+                    if headers.get("authorization").and_then(|v| v.to_str().ok())
+                        != Some("Basic dXNlcjpwYXNz")
+                    {
+                        return StatusCode::UNAUTHORIZED.into_response();
+                    }
+                    added.lock().unwrap().push(body);
+                    Json(serde_json::json!({
+                        "result": "success",
+                        "arguments": { "torrent-added": { "id": 1 } },
+                    }))
+                    .into_response()
+                }
+            }),
+        );
+        let url = format!(
+            "{}/transmission/rpc",
+            crate::download_client::stub_server(router).await
+        );
+
+        let session = Mutex::new(None);
+        add(
+            &reqwest::Client::new(),
+            &url,
+            Some(("user", Some("pass"))),
+            &session,
+            &Torrent::File {
+                name: "1.torrent".into(),
+                data: b"d4:infode".to_vec(),
+            },
+            &AddOptions {
+                category: Some("anime".into()),
+                save_path: None,
+                paused: false,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(session.lock().await.as_deref(), Some("s1"));
+
+        let added = added.lock().unwrap();
+        assert_eq!(
+            added[0],
+            serde_json::json!({
+                "method": "torrent-add",
+                "arguments": {
+                    "metainfo": "ZDQ6aW5mb2Rl",
+                    "paused": false,
+                    "labels": ["anime"],
+                },
+            })
+        );
+    }
+}
@@ -0,0 +1,355 @@
+//! Live events about a mirror's newest torrents, detected by polling the
+//! front page while anyone is listening.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use nyaa_parser::{Category, ListItem, SearchQuery, dsl::Expression};
+use tokio::sync::broadcast;
+
+use crate::Mirror;
+
+/// Polling interval when none is configured.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(60);
+/// Share of the rate limiter budget polling may use when not configured.
+pub const DEFAULT_BUDGET_SHARE: f64 = 0.25;
+/// Number of events kept for clients resuming from a last event id.
+const BUFFER_SIZE: usize = 1000;
+const CHANNEL_CAPACITY: usize = 256;
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveEvent {
+    /// A torrent appeared on the front page.
+    NewTorrent {
+        #[serde(serialize_with = "serialize_item")]
+        item: ListItem,
+    },
+    /// The seeders or leechers of a listed torrent changed.
+    SwarmChange {
+        #[serde(serialize_with = "serialize_item")]
+        item: ListItem,
+        previous_seeders: usize,
+        previous_leechers: usize,
+    },
+}
+
+/// Serializes items the way the list endpoint does.
+fn serialize_item<S: serde::Serializer>(item: &ListItem, serializer: S) -> Result<S::Ok, S::Error> {
+    serde::Serialize::serialize(&crate::api::mirror::list::ListItem::from(item), serializer)
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct Envelope {
+    pub id: u64,
+    pub mirror_id: String,
+    pub at: DateTime<Utc>,
+    #[serde(flatten)]
+    pub event: LiveEvent,
+}
+
+impl Envelope {
+    /// Event name, as used for the SSE `event` field.
+    pub fn name(&self) -> &'static str {
+        match self.event {
+            LiveEvent::NewTorrent { .. } => "new_torrent",
+            LiveEvent::SwarmChange { .. } => "swarm_change",
+        }
+    }
+
+    pub fn item(&self) -> &ListItem {
+        match &self.event {
+            LiveEvent::NewTorrent { item } | LiveEvent::SwarmChange { item, .. } => item,
+        }
+    }
+}
+
+/// Which events a subscriber wants.
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    pub category: Category,
+    pub expression: Expression,
+    pub trusted_only: bool,
+}
+
+impl EventFilter {
+    pub fn matches(&self, item: &ListItem) -> bool {
+        if self.trusted_only && !item.trusted {
+            return false;
+        }
+        if !self.category.is_all()
+            && !item
+                .category
+                .parse()
+                .is_ok_and(|category| self.category.contains(&category))
+        {
+            return false;
+        }
+        text_matches(&self.expression.text, &item.title) && self.expression.matches(item)
+    }
+}
+
+/// Approximates nyaa's text search: every term must appear in the title,
+/// ignoring case, and terms prefixed with `-` must not. Quoted phrases are
+/// single terms.
+fn text_matches(text: &str, title: &str) -> bool {
+    let title = title.to_lowercase();
+    let mut terms = Vec::new();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let (negated, term) = match rest.strip_prefix('-') {
+            Some(term) => (true, term),
+            None => (false, rest),
+        };
+        let (term, next) = match term.strip_prefix('"') {
+            Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+            None => term.split_once(char::is_whitespace).unwrap_or((term, "")),
+        };
+        if !term.is_empty() {
+            terms.push((negated, term.to_lowercase()));
+        }
+        rest = next.trim_start();
+    }
+    terms
+        .iter()
+        .all(|(negated, term)| title.contains(term.as_str()) != *negated)
+}
+
+/// Events a new subscriber should see before live ones.
+#[derive(Debug)]
+pub struct Subscription {
+    pub backlog: Vec<Arc<Envelope>>,
+    /// Whether events after the requested id were dropped from the buffer,
+    /// so the subscriber should reload the list.
+    pub gap: bool,
+    pub receiver: broadcast::Receiver<Arc<Envelope>>,
+}
+
+#[derive(Debug)]
+struct State {
+    buffer: VecDeque<Arc<Envelope>>,
+    next_id: u64,
+    /// Torrents on the front page at the last poll.
+    snapshot: Option<HashMap<usize, ListItem>>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    mirror_id: String,
+    state: Mutex<State>,
+    sender: broadcast::Sender<Arc<Envelope>>,
+}
+
+/// Event buffer and channel of one mirror.
+#[derive(Debug, Clone)]
+pub struct EventHub {
+    inner: Arc<Inner>,
+}
+
+impl EventHub {
+    pub fn new(mirror_id: &str) -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            inner: Arc::new(Inner {
+                mirror_id: mirror_id.to_string(),
+                state: Mutex::new(State {
+                    buffer: VecDeque::new(),
+                    // Ids start at the startup time in microseconds, so ids
+                    // handed out before a restart are older than any
+                    // buffered event.
+                    next_id: Utc::now().timestamp_micros() as u64,
+                    snapshot: None,
+                }),
+                sender,
+            }),
+        }
+    }
+
+    pub fn has_subscribers(&self) -> bool {
+        self.inner.sender.receiver_count() > 0
+    }
+
+    /// Subscribes to events, replaying buffered ones after `last_event_id`.
+    pub fn subscribe(&self, last_event_id: Option<u64>) -> Subscription {
+        let state = self.inner.state.lock().unwrap();
+        // Subscribing under the lock keeps the backlog and the channel from
+        // overlapping or missing an event.
+        let receiver = self.inner.sender.subscribe();
+        let Some(last_event_id) = last_event_id else {
+            return Subscription {
+                backlog: Vec::new(),
+                gap: false,
+                receiver,
+            };
+        };
+        let oldest = state
+            .buffer
+            .front()
+            .map(|envelope| envelope.id)
+            .unwrap_or(state.next_id);
+        Subscription {
+            backlog: state
+                .buffer
+                .iter()
+                .filter(|envelope| envelope.id > last_event_id)
+                .cloned()
+                .collect(),
+            gap: last_event_id.saturating_add(1) < oldest || last_event_id >= state.next_id,
+            receiver,
+        }
+    }
+
+    /// Compares the front page with the previous poll and publishes what
+    /// changed. The first poll only records a baseline.
+    pub fn observe(&self, items: &[ListItem]) {
+        let mut state = self.inner.state.lock().unwrap();
+        let snapshot = items
+            .iter()
+            .map(|item| (item.id, item.clone()))
+            .collect::<HashMap<_, _>>();
+        let Some(previous) = state.snapshot.replace(snapshot) else {
+            return;
+        };
+        let newest = previous.keys().max().copied().unwrap_or(0);
+
+        // Oldest first, so ids follow upload order.
+        for item in items.iter().rev() {
+            let event = match previous.get(&item.id) {
+                Some(before)
+                    if before.seeders != item.seeders || before.leechers != item.leechers =>
+                {
+                    LiveEvent::SwarmChange {
+                        item: item.clone(),
+                        previous_seeders: before.seeders,
+                        previous_leechers: before.leechers,
+                    }
+                }
+                // Torrents that merely moved back onto the page after a
+                // deletion are not new.
+                None if item.id > newest => LiveEvent::NewTorrent { item: item.clone() },
+                _ => continue,
+            };
+            let envelope = Arc::new(Envelope {
+                id: state.next_id,
+                mirror_id: self.inner.mirror_id.clone(),
+                at: Utc::now(),
+                event,
+            });
+            state.next_id += 1;
+            if state.buffer.len() == BUFFER_SIZE {
+                state.buffer.pop_front();
+            }
+            state.buffer.push_back(envelope.clone());
+            // Sending only fails without subscribers.
+            let _ = self.inner.sender.send(envelope);
+        }
+    }
+}
+
+/// Spawns a background task that polls `mirror`'s front page while its
+/// events have subscribers.
+pub fn spawn(mirror: Mirror, interval: Duration, budget_share: f64) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            if !mirror.events.has_subscribers() {
+                continue;
+            }
+
+            let id = format!("events-{}", uuid::Uuid::new_v4());
+            crate::request_id::scope(id, async {
+                let client = mirror.client.lock().await;
+                if !client.has_budget(budget_share).await {
+                    tracing::debug!(
+                        "skipped event poll of mirror {}: rate limiter budget in use",
+                        mirror.id()
+                    );
+                    return;
+                }
+                match client.list(&SearchQuery::new()).await {
+                    Ok(items) => mirror.events.observe(&items),
+                    Err(e) => {
+                        tracing::warn!("event poll of mirror {} failed: {:#}", mirror.id(), e)
+                    }
+                }
+            })
+            .await;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: usize, seeders: usize) -> ListItem {
+        ListItem {
+            title: format!("[Group] Super Cube - {:02} (1080p)", id),
+            pub_date: Utc::now(),
+            id,
+            seeders,
+            category: "1_2".into(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_observe() {
+        let hub = EventHub::new("nyaa");
+        let mut live = hub.subscribe(None);
+        hub.observe(&[item(2, 5), item(1, 5)]);
+        assert!(live.receiver.try_recv().is_err());
+
+        hub.observe(&[item(4, 0), item(3, 0), item(2, 7), item(1, 5)]);
+        let names = std::iter::from_fn(|| live.receiver.try_recv().ok())
+            .map(|envelope| envelope.name())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["swarm_change", "new_torrent", "new_torrent"]);
+
+        let first = hub.subscribe(Some(0)).backlog[0].id;
+        let resumed = hub.subscribe(Some(first));
+        assert!(!resumed.gap);
+        assert_eq!(
+            resumed
+                .backlog
+                .iter()
+                .map(|envelope| envelope.item().id)
+                .collect::<Vec<_>>(),
+            [3, 4]
+        );
+        assert!(hub.subscribe(Some(0)).gap);
+        assert!(hub.subscribe(Some(first + 2)).backlog.is_empty());
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = EventFilter {
+            category: "1_0".parse().unwrap(),
+            expression: nyaa_parser::dsl::parse("\"super cube\" -720p seeders:>1").unwrap(),
+            trusted_only: false,
+        };
+        assert!(filter.matches(&item(1, 5)));
+        assert!(!filter.matches(&item(1, 0)));
+        assert!(!filter.matches(&ListItem {
+            category: "2_1".into(),
+            ..item(1, 5)
+        }));
+        assert!(!filter.matches(&ListItem {
+            title: "Super Cube - 01 (720p)".into(),
+            ..item(1, 5)
+        }));
+        assert!(
+            !EventFilter {
+                trusted_only: true,
+                ..Default::default()
+            }
+            .matches(&item(1, 5))
+        );
+    }
+}
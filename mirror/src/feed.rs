@@ -0,0 +1,400 @@
+//! RSS, Atom and JSON Feed rendering of list results. RSS follows nyaa's
+//! own feed, including the `nyaa:` namespace, so it can be read by the same
+//! clients.
+
+use chrono::{DateTime, Utc};
+use nyaa_parser::ListItem;
+
+use crate::cli::MirrorType;
+
+pub const NYAA_NAMESPACE: &str = "https://nyaa.si/xmlns/nyaa";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FeedFormat {
+    Rss,
+    Atom,
+    Json,
+}
+
+impl FeedFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension {
+            "rss" | "xml" => Some(FeedFormat::Rss),
+            "atom" => Some(FeedFormat::Atom),
+            "json" => Some(FeedFormat::Json),
+            _ => None,
+        }
+    }
+
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            FeedFormat::Rss => "application/rss+xml; charset=utf-8",
+            FeedFormat::Atom => "application/atom+xml; charset=utf-8",
+            FeedFormat::Json => "application/feed+json; charset=utf-8",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FeedItem {
+    pub item: ListItem,
+    /// Page of the torrent on this mirror.
+    pub view_url: String,
+    /// Magnet or `.torrent` link.
+    pub link: String,
+    pub category_name: Option<&'static str>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Feed {
+    pub title: String,
+    pub description: String,
+    pub home_url: String,
+    pub feed_url: String,
+    pub items: Vec<FeedItem>,
+}
+
+impl Feed {
+    /// Publication date of the newest item.
+    pub fn updated(&self) -> Option<DateTime<Utc>> {
+        self.items.iter().map(|item| item.item.pub_date).max()
+    }
+
+    pub fn render(&self, format: FeedFormat) -> String {
+        match format {
+            FeedFormat::Rss => self.rss(),
+            FeedFormat::Atom => self.atom(),
+            FeedFormat::Json => self.json(),
+        }
+    }
+
+    fn rss(&self) -> String {
+        let mut out = String::new();
+        out.push_str(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+        out.push('\n');
+        out.push_str(&format!(
+            r#"<rss xmlns:atom="http://www.w3.org/2005/Atom" xmlns:nyaa="{}" version="2.0">"#,
+            NYAA_NAMESPACE
+        ));
+        out.push_str("<channel>");
+        out.push_str(&format!("<title>{}</title>", escape(&self.title)));
+        out.push_str(&format!(
+            "<description>{}</description>",
+            escape(&self.description)
+        ));
+        out.push_str(&format!("<link>{}</link>", escape(&self.home_url)));
+        out.push_str(&format!(
+            r#"<atom:link href="{}" rel="self" type="application/rss+xml"/>"#,
+            escape(&self.feed_url)
+        ));
+        for entry in &self.items {
+            let item = &entry.item;
+            out.push_str("<item>");
+            out.push_str(&format!("<title>{}</title>", escape(&item.title)));
+            out.push_str(&format!("<link>{}</link>", escape(&entry.link)));
+            out.push_str(&format!(
+                r#"<guid isPermaLink="true">{}</guid>"#,
+                escape(&entry.view_url)
+            ));
+            out.push_str(&format!(
+                "<pubDate>{}</pubDate>",
+                item.pub_date.format("%a, %d %b %Y %H:%M:%S -0000")
+            ));
+            for (name, value) in entry.nyaa_fields() {
+                out.push_str(&format!("<nyaa:{0}>{1}</nyaa:{0}>", name, escape(&value)));
+            }
+            out.push_str(&format!(
+                "<description>{}</description>",
+                escape(&entry.description())
+            ));
+            out.push_str("</item>");
+        }
+        out.push_str("</channel></rss>\n");
+        out
+    }
+
+    fn atom(&self) -> String {
+        let updated = self.updated().unwrap_or_else(Utc::now);
+        let mut out = String::new();
+        out.push_str(r#"<?xml version="1.0" encoding="utf-8"?>"#);
+        out.push('\n');
+        out.push_str(&format!(
+            r#"<feed xmlns="http://www.w3.org/2005/Atom" xmlns:nyaa="{}">"#,
+            NYAA_NAMESPACE
+        ));
+        out.push_str(&format!("<id>{}</id>", escape(&self.feed_url)));
+        out.push_str(&format!("<title>{}</title>", escape(&self.title)));
+        out.push_str(&format!(
+            "<subtitle>{}</subtitle>",
+            escape(&self.description)
+        ));
+        out.push_str(&format!("<updated>{}</updated>", updated.to_rfc3339()));
+        out.push_str(&format!(
+            r#"<link href="{}" rel="self" type="application/atom+xml"/>"#,
+            escape(&self.feed_url)
+        ));
+        out.push_str(&format!(
+            r#"<link href="{}" rel="alternate"/>"#,
+            escape(&self.home_url)
+        ));
+        for entry in &self.items {
+            let item = &entry.item;
+            out.push_str("<entry>");
+            out.push_str(&format!("<id>{}</id>", escape(&entry.view_url)));
+            out.push_str(&format!("<title>{}</title>", escape(&item.title)));
+            out.push_str(&format!(
+                "<updated>{}</updated>",
+                item.pub_date.to_rfc3339()
+            ));
+            out.push_str(&format!(
+                "<published>{}</published>",
+                item.pub_date.to_rfc3339()
+            ));
+            out.push_str(&format!(
+                r#"<link href="{}" rel="alternate"/>"#,
+                escape(&entry.view_url)
+            ));
+            out.push_str(&format!(
+                r#"<link href="{}" rel="enclosure" type="{}"/>"#,
+                escape(&entry.link),
+                entry.link_type()
+            ));
+            out.push_str(&format!(r#"<category term="{}"/>"#, escape(&item.category)));
+            out.push_str(&format!(
+                r#"<summary type="html">{}</summary>"#,
+                escape(&entry.description())
+            ));
+            for (name, value) in entry.nyaa_fields() {
+                out.push_str(&format!("<nyaa:{0}>{1}</nyaa:{0}>", name, escape(&value)));
+            }
+            out.push_str("</entry>");
+        }
+        out.push_str("</feed>\n");
+        out
+    }
+
+    fn json(&self) -> String {
+        let items = self
+            .items
+            .iter()
+            .map(|entry| {
+                let item = &entry.item;
+                serde_json::json!({
+                    "id": entry.view_url,
+                    "url": entry.view_url,
+                    "title": item.title,
+                    "content_html": entry.description(),
+                    "date_published": item.pub_date.to_rfc3339(),
+                    "tags": [item.category],
+                    "attachments": [{
+                        "url": entry.link,
+                        "mime_type": entry.link_type(),
+                    }],
+                    "_nyaa": {
+                        "seeders": item.seeders,
+                        "leechers": item.leechers,
+                        "downloads": item.downloads,
+                        "info_hash": item.btih(),
+                        "category_id": item.category,
+                        "category": entry.category_name,
+                        "size": item.size,
+                        "comments": item.comments,
+                        "trusted": item.trusted,
+                        "remake": item.remake,
+                    },
+                })
+            })
+            .collect::<Vec<_>>();
+        let feed = serde_json::json!({
+            "version": "https://jsonfeed.org/version/1.1",
+            "title": self.title,
+            "description": self.description,
+            "home_page_url": self.home_url,
+            "feed_url": self.feed_url,
+            "items": items,
+        });
+        serde_json::to_string(&feed).expect("feed is serializable")
+    }
+}
+
+impl FeedItem {
+    fn link_type(&self) -> &'static str {
+        if self.link.starts_with("magnet:") {
+            "x-scheme-handler/magnet"
+        } else {
+            "application/x-bittorrent"
+        }
+    }
+
+    /// The upstream description, or one in the format nyaa uses.
+    fn description(&self) -> String {
+        let item = &self.item;
+        if let Some(description) = item.description.as_ref() {
+            return description.clone();
+        }
+        format!(
+            r#"<a href="{}">#{} | {}</a> | {} | {} | {}"#,
+            escape(&self.view_url),
+            item.id,
+            escape(&item.title),
+            format_size(item.size),
+            self.category_name.unwrap_or(&item.category),
+            item.btih().unwrap_or_default().to_uppercase()
+        )
+    }
+
+    /// `nyaa:` elements, in the order nyaa writes them.
+    fn nyaa_fields(&self) -> Vec<(&'static str, String)> {
+        let item = &self.item;
+        let flag = |value: bool| if value { "Yes" } else { "No" }.to_string();
+        vec![
+            ("seeders", item.seeders.to_string()),
+            ("leechers", item.leechers.to_string()),
+            ("downloads", item.downloads.to_string()),
+            ("infoHash", item.btih().unwrap_or_default()),
+            ("categoryId", item.category.clone()),
+            (
+                "category",
+                self.category_name.unwrap_or(&item.category).to_string(),
+            ),
+            ("size", format_size(item.size)),
+            ("comments", item.comments.to_string()),
+            ("trusted", flag(item.trusted)),
+            ("remake", flag(item.remake)),
+        ]
+    }
+}
+
+fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            // Control characters other than tab and newlines are not
+            // allowed in XML 1.0.
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Formats a size the way nyaa does, as in `205.9 MiB`.
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["KiB", "MiB", "GiB", "TiB", "PiB"];
+    if size < 1024 {
+        return format!("{} Bytes", size);
+    }
+    let mut value = size as f64 / 1024.0;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", value, UNITS[unit])
+}
+
+/// Full name of a category code, as nyaa writes it in its feeds.
+pub fn category_name(ty: &MirrorType, category: &str) -> Option<&'static str> {
+    let name = match (ty, category) {
+        (MirrorType::Normal, "1_0") => "Anime",
+        (MirrorType::Normal, "1_1") => "Anime - Anime Music Video",
+        (MirrorType::Normal, "1_2") => "Anime - English-translated",
+        (MirrorType::Normal, "1_3") => "Anime - Non-English-translated",
+        (MirrorType::Normal, "1_4") => "Anime - Raw",
+        (MirrorType::Normal, "2_0") => "Audio",
+        (MirrorType::Normal, "2_1") => "Audio - Lossless",
+        (MirrorType::Normal, "2_2") => "Audio - Lossy",
+        (MirrorType::Normal, "3_0") => "Literature",
+        (MirrorType::Normal, "3_1") => "Literature - English-translated",
+        (MirrorType::Normal, "3_2") => "Literature - Non-English-translated",
+        (MirrorType::Normal, "3_3") => "Literature - Raw",
+        (MirrorType::Normal, "4_0") => "Live Action",
+        (MirrorType::Normal, "4_1") => "Live Action - English-translated",
+        (MirrorType::Normal, "4_2") => "Live Action - Idol/Promotional Video",
+        (MirrorType::Normal, "4_3") => "Live Action - Non-English-translated",
+        (MirrorType::Normal, "4_4") => "Live Action - Raw",
+        (MirrorType::Normal, "5_0") => "Pictures",
+        (MirrorType::Normal, "5_1") => "Pictures - Graphics",
+        (MirrorType::Normal, "5_2") => "Pictures - Photos",
+        (MirrorType::Normal, "6_0") => "Software",
+        (MirrorType::Normal, "6_1") => "Software - Applications",
+        (MirrorType::Normal, "6_2") => "Software - Games",
+        (MirrorType::Adult, "1_0") => "Art",
+        (MirrorType::Adult, "1_1") => "Art - Anime",
+        (MirrorType::Adult, "1_2") => "Art - Doujinshi",
+        (MirrorType::Adult, "1_3") => "Art - Games",
+        (MirrorType::Adult, "1_4") => "Art - Manga",
+        (MirrorType::Adult, "1_5") => "Art - Pictures",
+        (MirrorType::Adult, "2_0") => "Real Life",
+        (MirrorType::Adult, "2_1") => "Real Life - Photobooks and Pictures",
+        (MirrorType::Adult, "2_2") => "Real Life - Videos",
+        _ => return None,
+    };
+    Some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn feed() -> Feed {
+        let item = ListItem {
+            title: "[Sokudo] The Super Cube S01E03 [1080p AV1] <weekly> & more".into(),
+            pub_date: DateTime::from_timestamp(1_743_231_079, 0).unwrap(),
+            id: 1953465,
+            seeders: 59,
+            leechers: 12,
+            downloads: 93,
+            category: "1_2".into(),
+            size: 215_901_798,
+            remake: true,
+            magnet_link: Some(
+                "magnet:?xt=urn:btih:6A1093801C4567CF75AB148D4DB88651CE3B25E3&dn=x".into(),
+            ),
+            ..Default::default()
+        };
+        Feed {
+            title: "Nyaa - Home".into(),
+            description: "RSS Feed for Home".into(),
+            home_url: "http://localhost/nyaa".into(),
+            feed_url: "http://localhost/feed/nyaa.rss".into(),
+            items: vec![FeedItem {
+                view_url: "http://localhost/nyaa/view/1953465".into(),
+                link: "http://localhost/api/mirror/nyaa/download/1953465".into(),
+                category_name: category_name(&MirrorType::Normal, &item.category),
+                item,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_rss_round_trip() {
+        let feed = feed();
+        let items = nyaa_parser::list::rss::parse(feed.render(FeedFormat::Rss)).unwrap();
+        assert_eq!(items.len(), 1);
+        let item = &items[0];
+        let original = &feed.items[0].item;
+        assert_eq!(item.id, original.id);
+        assert_eq!(item.title, original.title);
+        assert_eq!(item.pub_date, original.pub_date);
+        assert_eq!(item.seeders, 59);
+        assert_eq!(
+            item.info_hash.as_deref(),
+            Some("6a1093801c4567cf75ab148d4db88651ce3b25e3")
+        );
+        assert_eq!(item.category, "1_2");
+        assert_eq!(format_size(item.size), "205.9 MiB");
+        assert!(item.remake);
+        assert!(!item.trusted);
+        assert_eq!(item.link, feed.items[0].link);
+
+        let atom = feed.render(FeedFormat::Atom);
+        assert!(atom.contains("<nyaa:seeders>59</nyaa:seeders>"));
+        assert!(atom.contains("&lt;weekly&gt; &amp; more"));
+    }
+}
@@ -0,0 +1,1203 @@
+use std::{
+    collections::HashSet,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use nyaa_parser::{ExternalIds, ExternalSource};
+
+/// Local record of torrents viewed through the mirrors, so they can be
+/// looked up by the external database entries they link to and served once
+/// deleted upstream, of torrents seen in lists, so consumers can follow what
+/// changed, and of their swarm stats over time.
+#[derive(Debug, Clone)]
+pub struct Index {
+    conn: Arc<Mutex<rusqlite::Connection>>,
+}
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct IndexedTorrent {
+    pub mirror_id: String,
+    pub id: usize,
+    pub title: String,
+    pub pub_date: DateTime<Utc>,
+    pub category: String,
+    pub size: u64,
+    pub info_hash: String,
+    pub submitter: String,
+    pub information: Option<String>,
+    pub external_ids: ExternalIds,
+    pub indexed_at: DateTime<Utc>,
+}
+
+/// How a torrent was found to be deleted upstream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeletionReason {
+    /// Its view page answered 404.
+    NotFound,
+    /// It was missing from a list page of every torrent around its id.
+    MissingFromList,
+}
+
+impl DeletionReason {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DeletionReason::NotFound => "not_found",
+            DeletionReason::MissingFromList => "missing_from_list",
+        }
+    }
+}
+
+impl FromStr for DeletionReason {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "not_found" => Ok(DeletionReason::NotFound),
+            "missing_from_list" => Ok(DeletionReason::MissingFromList),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct Deletion {
+    pub deleted_at: DateTime<Utc>,
+    pub reason: DeletionReason,
+}
+
+/// The latest parsed copy of a torrent's view page.
+#[derive(Debug, Clone)]
+pub struct ArchivedView {
+    pub view: nyaa_parser::View,
+    pub archived_at: DateTime<Utc>,
+    pub deletion: Option<Deletion>,
+}
+
+/// A torrent as last seen in a list, with when it was first seen and last
+/// changed.
+#[derive(Debug, Clone)]
+pub struct ListedTorrent {
+    pub id: usize,
+    pub title: String,
+    pub pub_date: DateTime<Utc>,
+    pub description: Option<String>,
+    pub category: String,
+    pub size: u64,
+    pub seeders: usize,
+    pub leechers: usize,
+    pub downloads: usize,
+    pub comments: usize,
+    pub trusted: bool,
+    pub remake: bool,
+    pub info_hash: Option<String>,
+    pub first_seen_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Set once the torrent is found deleted upstream, which counts as a
+    /// change.
+    pub deletion: Option<Deletion>,
+    /// Cursor to continue after this torrent.
+    pub cursor: Cursor,
+}
+
+/// Columns read by [`read_listed`], from `listed` joined with
+/// `deleted_torrents`.
+const LISTED_COLUMNS: &str = "l.id, l.title, l.pub_date, l.description, l.category, l.size, l.seeders, l.leechers, l.downloads, l.comments, l.trusted, l.remake, l.info_hash, l.first_seen_at, l.updated_at, d.deleted_at, d.reason";
+
+fn read_listed(row: &rusqlite::Row) -> rusqlite::Result<ListedTorrent> {
+    let id = row.get(0)?;
+    let updated_at: i64 = row.get(14)?;
+    Ok(ListedTorrent {
+        id,
+        title: row.get(1)?,
+        pub_date: row.get(2)?,
+        description: row.get(3)?,
+        category: row.get(4)?,
+        size: row.get(5)?,
+        seeders: row.get(6)?,
+        leechers: row.get(7)?,
+        downloads: row.get(8)?,
+        comments: row.get(9)?,
+        trusted: row.get(10)?,
+        remake: row.get(11)?,
+        info_hash: row.get(12)?,
+        first_seen_at: row.get(13)?,
+        updated_at: DateTime::from_timestamp_micros(updated_at).unwrap_or_default(),
+        deletion: deletion_from_row(row.get(15)?, row.get(16)?),
+        cursor: Cursor { updated_at, id },
+    })
+}
+
+/// Position in the change feed: the update stamp and id of the last torrent
+/// returned. Encoded as an opaque string for clients.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Cursor {
+    /// Microseconds since the epoch. Strictly increases with every write,
+    /// even if the clock goes back.
+    pub updated_at: i64,
+    pub id: usize,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .encode(format!("{}:{}", self.updated_at, self.id))
+    }
+
+    pub fn decode(value: &str) -> Option<Self> {
+        let decoded = base64::engine::general_purpose::URL_SAFE_NO_PAD
+            .decode(value)
+            .ok()?;
+        let (updated_at, id) = std::str::from_utf8(&decoded).ok()?.split_once(':')?;
+        Some(Self {
+            updated_at: updated_at.parse().ok()?,
+            id: id.parse().ok()?,
+        })
+    }
+}
+
+/// Observations identical to the previous one are only stored again after
+/// this long, keeping the series compact while showing the torrent is alive.
+const STATS_HEARTBEAT: Duration = Duration::from_secs(60 * 60);
+/// How long swarm stats are kept when not configured.
+pub const DEFAULT_STATS_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+/// How often swarm stats past their retention are pruned.
+const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Swarm stats of a torrent at a point in time, or averaged over a bucket
+/// starting at `at`.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct SwarmSample {
+    pub at: DateTime<Utc>,
+    pub seeders: usize,
+    pub leechers: usize,
+    pub downloads: usize,
+}
+
+/// How a torrent's swarm grew over a period.
+#[derive(Debug, Clone)]
+pub struct SwarmGrowth {
+    pub torrent: ListedTorrent,
+    pub seeder_growth: i64,
+    pub download_growth: i64,
+    /// Start of the period, which is later than requested for torrents
+    /// first seen during it.
+    pub observed_since: DateTime<Utc>,
+}
+
+/// Stores an observation of a torrent's swarm unless it repeats the latest
+/// one within [`STATS_HEARTBEAT`].
+fn insert_stats(
+    tx: &rusqlite::Transaction,
+    mirror_id: &str,
+    id: usize,
+    seeders: usize,
+    leechers: usize,
+    downloads: usize,
+    now: DateTime<Utc>,
+) -> rusqlite::Result<()> {
+    let mut stmt = tx.prepare_cached(
+        "INSERT OR REPLACE INTO swarm_stats (mirror_id, id, at, seeders, leechers, downloads)
+        SELECT ?1, ?2, ?3, ?4, ?5, ?6
+        WHERE NOT EXISTS (
+            SELECT 1 FROM (
+                SELECT at, seeders, leechers, downloads FROM swarm_stats
+                WHERE mirror_id = ?1 AND id = ?2
+                ORDER BY at DESC LIMIT 1
+            ) AS latest
+            WHERE latest.seeders = ?4 AND latest.leechers = ?5 AND latest.downloads = ?6
+                AND latest.at > ?3 - ?7
+        )",
+    )?;
+    stmt.execute(rusqlite::params![
+        mirror_id,
+        id,
+        now.timestamp(),
+        seeders,
+        leechers,
+        downloads,
+        STATS_HEARTBEAT.as_secs(),
+    ])?;
+    Ok(())
+}
+
+/// Update stamp for a write to `listed`, later than any earlier one.
+fn next_stamp(tx: &rusqlite::Transaction, now: DateTime<Utc>) -> rusqlite::Result<i64> {
+    let latest: i64 = tx.query_row(
+        "SELECT COALESCE(MAX(updated_at), 0) FROM listed",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(now.timestamp_micros().max(latest + 1))
+}
+
+/// Marks a torrent deleted, keeping the first detection, or clears the mark
+/// when `reason` is `None`. Only torrents that were listed or archived are
+/// marked, so IDs that never existed aren't recorded. Listed torrents whose
+/// mark changed get `stamp` so the change feed reports them.
+fn set_deleted(
+    tx: &rusqlite::Transaction,
+    mirror_id: &str,
+    id: usize,
+    reason: Option<DeletionReason>,
+    now: DateTime<Utc>,
+    stamp: i64,
+) -> rusqlite::Result<()> {
+    let changed = match reason {
+        Some(reason) => tx
+            .prepare_cached(
+                "INSERT OR IGNORE INTO deleted_torrents (mirror_id, id, deleted_at, reason)
+                SELECT ?1, ?2, ?3, ?4
+                WHERE EXISTS (SELECT 1 FROM listed WHERE mirror_id = ?1 AND id = ?2)
+                OR EXISTS (SELECT 1 FROM archived_views WHERE mirror_id = ?1 AND id = ?2)",
+            )?
+            .execute(rusqlite::params![mirror_id, id, now, reason.as_str()])?,
+        None => tx
+            .prepare_cached("DELETE FROM deleted_torrents WHERE mirror_id = ? AND id = ?")?
+            .execute(rusqlite::params![mirror_id, id])?,
+    };
+    if changed > 0 {
+        tx.prepare_cached("UPDATE listed SET updated_at = ? WHERE mirror_id = ? AND id = ?")?
+            .execute(rusqlite::params![stamp, mirror_id, id])?;
+    }
+    Ok(())
+}
+
+fn deletion_from_row(
+    deleted_at: Option<DateTime<Utc>>,
+    reason: Option<String>,
+) -> Option<Deletion> {
+    Some(Deletion {
+        deleted_at: deleted_at?,
+        reason: reason?.parse().ok()?,
+    })
+}
+
+impl Index {
+    pub fn open(db_path: &Path) -> rusqlite::Result<Self> {
+        let conn = crate::db::open(db_path, MIGRATIONS, "index")?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    #[cfg(test)]
+    fn open_in_memory() -> Self {
+        Self {
+            conn: Arc::new(Mutex::new(crate::db::open_in_memory(MIGRATIONS))),
+        }
+    }
+
+    /// Stores `view` in the background, replacing any earlier copy, and
+    /// archives it with the page's `html` if given. A torrent that could be
+    /// viewed is no longer deleted.
+    pub fn record_view(&self, mirror_id: &str, view: &nyaa_parser::View, html: Option<&str>) {
+        let index = self.clone();
+        let mirror_id = mirror_id.to_string();
+        let view = view.clone();
+        let html = html.map(str::to_string);
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = index.insert_view(&mirror_id, &view, html.as_deref(), Utc::now()) {
+                tracing::warn!("failed to index view {} of {}: {}", view.id, mirror_id, e);
+            }
+        });
+    }
+
+    fn insert_view(
+        &self,
+        mirror_id: &str,
+        view: &nyaa_parser::View,
+        html: Option<&str>,
+        now: DateTime<Utc>,
+    ) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let stamp = next_stamp(&tx, now)?;
+        tx.execute(
+            "INSERT OR REPLACE INTO archived_views (mirror_id, id, view, html, archived_at) VALUES (?, ?, ?, ?, ?)",
+            rusqlite::params![
+                mirror_id,
+                view.id,
+                serde_json::to_string(view).expect("view is serializable"),
+                html,
+                now,
+            ],
+        )?;
+        set_deleted(&tx, mirror_id, view.id, None, now, stamp)?;
+        tx.execute(
+            "INSERT OR REPLACE INTO torrents (mirror_id, id, title, pub_date, category, size, info_hash, submitter, information, indexed_at) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            rusqlite::params![
+                mirror_id,
+                view.id,
+                view.title,
+                view.pub_date,
+                view.category,
+                view.size,
+                view.info_hash,
+                view.submitter,
+                view.information,
+                now,
+            ],
+        )?;
+        insert_stats(
+            &tx,
+            mirror_id,
+            view.id,
+            view.seeders,
+            view.leechers,
+            view.downloads,
+            now,
+        )?;
+        tx.execute(
+            "DELETE FROM external_ids WHERE mirror_id = ? AND torrent_id = ?",
+            rusqlite::params![mirror_id, view.id],
+        )?;
+        {
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO external_ids (mirror_id, torrent_id, source, external_id) VALUES (?, ?, ?, ?)",
+            )?;
+            for (source, external_id) in view.external_ids.entries() {
+                stmt.execute(rusqlite::params![
+                    mirror_id,
+                    view.id,
+                    source.as_str(),
+                    external_id
+                ])?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Stores list `items` in the background, bumping the update stamp of
+    /// those that are new or changed. When the list is `complete`, holding
+    /// every torrent between its lowest and highest id, listed torrents
+    /// missing in between are marked deleted.
+    pub fn record_list(&self, mirror_id: &str, items: &[nyaa_parser::ListItem], complete: bool) {
+        if items.is_empty() {
+            return;
+        }
+        let index = self.clone();
+        let mirror_id = mirror_id.to_string();
+        let items = items.to_vec();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = index.insert_list(&mirror_id, &items, complete, Utc::now()) {
+                tracing::warn!("failed to index list of {}: {}", mirror_id, e);
+            }
+        });
+    }
+
+    fn insert_list(
+        &self,
+        mirror_id: &str,
+        items: &[nyaa_parser::ListItem],
+        complete: bool,
+        now: DateTime<Utc>,
+    ) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        // One stamp for the whole batch.
+        let stamp = next_stamp(&tx, now)?;
+        {
+            // Descriptions and info hashes are missing from some list
+            // formats, so they are kept rather than cleared, and don't
+            // count as changes.
+            let mut stmt = tx.prepare_cached(
+                "INSERT INTO listed (mirror_id, id, title, pub_date, description, category, size, seeders, leechers, downloads, comments, trusted, remake, info_hash, first_seen_at, updated_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT (mirror_id, id) DO UPDATE SET
+                    title = excluded.title,
+                    pub_date = excluded.pub_date,
+                    description = COALESCE(excluded.description, listed.description),
+                    category = excluded.category,
+                    size = excluded.size,
+                    seeders = excluded.seeders,
+                    leechers = excluded.leechers,
+                    downloads = excluded.downloads,
+                    comments = excluded.comments,
+                    trusted = excluded.trusted,
+                    remake = excluded.remake,
+                    info_hash = COALESCE(excluded.info_hash, listed.info_hash),
+                    updated_at = excluded.updated_at
+                WHERE listed.title IS NOT excluded.title
+                    OR listed.category IS NOT excluded.category
+                    OR listed.size IS NOT excluded.size
+                    OR listed.seeders IS NOT excluded.seeders
+                    OR listed.leechers IS NOT excluded.leechers
+                    OR listed.downloads IS NOT excluded.downloads
+                    OR listed.comments IS NOT excluded.comments
+                    OR listed.trusted IS NOT excluded.trusted
+                    OR listed.remake IS NOT excluded.remake",
+            )?;
+            for item in items {
+                stmt.execute(rusqlite::params![
+                    mirror_id,
+                    item.id,
+                    item.title,
+                    item.pub_date,
+                    item.description,
+                    item.category,
+                    item.size,
+                    item.seeders,
+                    item.leechers,
+                    item.downloads,
+                    item.comments,
+                    item.trusted,
+                    item.remake,
+                    item.btih(),
+                    now,
+                    stamp,
+                ])?;
+                insert_stats(
+                    &tx,
+                    mirror_id,
+                    item.id,
+                    item.seeders,
+                    item.leechers,
+                    item.downloads,
+                    now,
+                )?;
+                set_deleted(&tx, mirror_id, item.id, None, now, stamp)?;
+            }
+        }
+        if complete {
+            let ids = items.iter().map(|item| item.id).collect::<HashSet<_>>();
+            let (min, max) = (ids.iter().min(), ids.iter().max());
+            let missing = tx
+                .prepare_cached("SELECT id FROM listed WHERE mirror_id = ? AND id > ? AND id < ?")?
+                .query_map(rusqlite::params![mirror_id, min, max], |row| {
+                    row.get::<_, usize>(0)
+                })?
+                .filter(|id| id.as_ref().map_or(true, |id| !ids.contains(id)))
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            for id in missing {
+                set_deleted(
+                    &tx,
+                    mirror_id,
+                    id,
+                    Some(DeletionReason::MissingFromList),
+                    now,
+                    stamp,
+                )?;
+            }
+        }
+        tx.commit()
+    }
+
+    /// Torrents of `mirror_id` added or changed after `since`, oldest change
+    /// first.
+    pub async fn changes(
+        &self,
+        mirror_id: &str,
+        since: Cursor,
+        limit: usize,
+    ) -> rusqlite::Result<Vec<ListedTorrent>> {
+        let mirror_id = mirror_id.to_string();
+        crate::db::blocking(&self.conn, move |conn| {
+            let mut stmt = conn.prepare_cached(&format!(
+                "SELECT {}
+                FROM listed l
+                LEFT JOIN deleted_torrents d USING (mirror_id, id)
+                WHERE mirror_id = ? AND (l.updated_at > ? OR (l.updated_at = ? AND id > ?))
+                ORDER BY l.updated_at, id
+                LIMIT ?",
+                LISTED_COLUMNS
+            ))?;
+            stmt.query_map(
+                rusqlite::params![
+                    mirror_id,
+                    since.updated_at,
+                    since.updated_at,
+                    since.id,
+                    limit
+                ],
+                read_listed,
+            )?
+            .collect()
+        })
+        .await
+    }
+
+    /// Marks a torrent deleted upstream, unless it already is or was never
+    /// listed or archived. Unlike other writes this is awaited, so the
+    /// archived view is served as deleted right away.
+    pub async fn mark_deleted(&self, mirror_id: &str, id: usize, reason: DeletionReason) {
+        let index = self.clone();
+        let mirror_id = mirror_id.to_string();
+        let result = tokio::task::spawn_blocking(move || {
+            if let Err(e) = index.insert_deletion(&mirror_id, id, reason, Utc::now()) {
+                tracing::warn!("failed to mark {} of {} deleted: {}", id, mirror_id, e);
+            }
+        })
+        .await;
+        if let Err(e) = result {
+            tracing::warn!("failed to mark {} deleted: {}", id, e);
+        }
+    }
+
+    fn insert_deletion(
+        &self,
+        mirror_id: &str,
+        id: usize,
+        reason: DeletionReason,
+        now: DateTime<Utc>,
+    ) -> rusqlite::Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        let stamp = next_stamp(&tx, now)?;
+        set_deleted(&tx, mirror_id, id, Some(reason), now, stamp)?;
+        tx.commit()
+    }
+
+    /// Spawns a background task that periodically prunes swarm stats older
+    /// than `retention`. Stats are kept for at least the longest trending
+    /// window plus a heartbeat, so every torrent still listed keeps a sample
+    /// to measure its growth from.
+    pub fn spawn_maintenance(&self, retention: Duration) {
+        let retention = retention.max(crate::trending::Window::Week.duration() + STATS_HEARTBEAT);
+        let index = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(MAINTENANCE_INTERVAL);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let cutoff = Utc::now() - chrono::Duration::from_std(retention).unwrap_or_default();
+                let index = index.clone();
+                let result = tokio::task::spawn_blocking(move || index.prune_stats(cutoff)).await;
+                match result {
+                    Ok(Ok(0)) => {}
+                    Ok(Ok(count)) => tracing::debug!("pruned {} swarm stats", count),
+                    Ok(Err(e)) => tracing::warn!("failed to prune swarm stats: {}", e),
+                    Err(e) => tracing::warn!("failed to prune swarm stats: {}", e),
+                }
+            }
+        });
+    }
+
+    /// Deletes swarm stats taken before `cutoff`, returning how many.
+    fn prune_stats(&self, cutoff: DateTime<Utc>) -> rusqlite::Result<usize> {
+        let conn = self.conn.lock().unwrap();
+        conn.execute("DELETE FROM swarm_stats WHERE at < ?", [cutoff.timestamp()])
+    }
+
+    /// The archived view of a torrent, with its deletion if it was deleted.
+    pub async fn archived_view(
+        &self,
+        mirror_id: &str,
+        id: usize,
+    ) -> rusqlite::Result<Option<ArchivedView>> {
+        let mirror_id = mirror_id.to_string();
+        crate::db::blocking(&self.conn, move |conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT a.view, a.archived_at, d.deleted_at, d.reason
+                FROM archived_views a
+                LEFT JOIN deleted_torrents d USING (mirror_id, id)
+                WHERE mirror_id = ? AND id = ?",
+            )?;
+            let mut rows = stmt.query(rusqlite::params![mirror_id, id])?;
+            let Some(row) = rows.next()? else {
+                return Ok(None);
+            };
+            let view: String = row.get(0)?;
+            let view = serde_json::from_str(&view).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    Box::new(e),
+                )
+            })?;
+            Ok(Some(ArchivedView {
+                view,
+                archived_at: row.get(1)?,
+                deletion: deletion_from_row(row.get(2)?, row.get(3)?),
+            }))
+        })
+        .await
+    }
+
+    /// Swarm stats of a torrent between `from` and `until`, oldest first.
+    /// With a `bucket` of more than a second, samples are averaged per
+    /// bucket, except downloads, which only grow and keep their maximum.
+    pub async fn swarm_stats(
+        &self,
+        mirror_id: &str,
+        id: usize,
+        from: DateTime<Utc>,
+        until: DateTime<Utc>,
+        bucket: Duration,
+    ) -> rusqlite::Result<Vec<SwarmSample>> {
+        let mirror_id = mirror_id.to_string();
+        crate::db::blocking(&self.conn, move |conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT (at / ?1) * ?1 AS bucket,
+                    CAST(ROUND(AVG(seeders)) AS INTEGER),
+                    CAST(ROUND(AVG(leechers)) AS INTEGER),
+                    MAX(downloads)
+                FROM swarm_stats
+                WHERE mirror_id = ?2 AND id = ?3 AND at >= ?4 AND at <= ?5
+                GROUP BY bucket
+                ORDER BY bucket",
+            )?;
+            stmt.query_map(
+                rusqlite::params![
+                    bucket.as_secs().max(1),
+                    mirror_id,
+                    id,
+                    from.timestamp(),
+                    until.timestamp()
+                ],
+                |row| {
+                    Ok(SwarmSample {
+                        at: DateTime::from_timestamp(row.get(0)?, 0).unwrap_or_default(),
+                        seeders: row.get(1)?,
+                        leechers: row.get(2)?,
+                        downloads: row.get(3)?,
+                    })
+                },
+            )?
+            .collect()
+        })
+        .await
+    }
+
+    /// Listed torrents of `mirror_id` observed since `since`, with how much
+    /// their seeders and downloads grew since then. Torrents first seen
+    /// later count from their first observation; those seen only once are
+    /// left out.
+    pub async fn growth(
+        &self,
+        mirror_id: &str,
+        since: DateTime<Utc>,
+    ) -> rusqlite::Result<Vec<SwarmGrowth>> {
+        let mirror_id = mirror_id.to_string();
+        crate::db::blocking(&self.conn, move |conn| {
+            let mut stmt = conn.prepare_cached(&format!(
+                "WITH latest AS (
+                    SELECT id, MAX(at) AS at FROM swarm_stats
+                    WHERE mirror_id = ?1
+                    GROUP BY id
+                    HAVING MAX(at) >= ?2
+                ), baseline AS (
+                    SELECT latest.id, COALESCE(
+                        (SELECT MAX(at) FROM swarm_stats s WHERE s.mirror_id = ?1 AND s.id = latest.id AND s.at <= ?2),
+                        (SELECT MIN(at) FROM swarm_stats s WHERE s.mirror_id = ?1 AND s.id = latest.id)
+                    ) AS at
+                    FROM latest
+                )
+                SELECT {},
+                    last.seeders - first.seeders, last.downloads - first.downloads, baseline.at
+                FROM latest
+                JOIN baseline ON baseline.id = latest.id
+                JOIN swarm_stats first ON first.mirror_id = ?1 AND first.id = baseline.id AND first.at = baseline.at
+                JOIN swarm_stats last ON last.mirror_id = ?1 AND last.id = latest.id AND last.at = latest.at
+                JOIN listed l ON l.mirror_id = ?1 AND l.id = latest.id
+                LEFT JOIN deleted_torrents d ON d.mirror_id = ?1 AND d.id = latest.id
+                WHERE latest.at > baseline.at",
+                LISTED_COLUMNS
+            ))?;
+            stmt.query_map(rusqlite::params![mirror_id, since.timestamp()], |row| {
+                Ok(SwarmGrowth {
+                    torrent: read_listed(row)?,
+                    seeder_growth: row.get(17)?,
+                    download_growth: row.get(18)?,
+                    observed_since: DateTime::from_timestamp(row.get(19)?, 0).unwrap_or_default(),
+                })
+            })?
+            .collect()
+        })
+        .await
+    }
+
+    /// Time of the first stored swarm stats of a torrent.
+    pub async fn first_swarm_sample(
+        &self,
+        mirror_id: &str,
+        id: usize,
+    ) -> rusqlite::Result<Option<DateTime<Utc>>> {
+        let mirror_id = mirror_id.to_string();
+        crate::db::blocking(&self.conn, move |conn| {
+            let at: Option<i64> = conn.query_row(
+                "SELECT MIN(at) FROM swarm_stats WHERE mirror_id = ? AND id = ?",
+                rusqlite::params![mirror_id, id],
+                |row| row.get(0),
+            )?;
+            Ok(at.and_then(|at| DateTime::from_timestamp(at, 0)))
+        })
+        .await
+    }
+
+    /// Torrents of `mirror_id` linking to `external_id` on `source`, newest
+    /// first.
+    pub async fn find_by_external_id(
+        &self,
+        mirror_id: &str,
+        source: ExternalSource,
+        external_id: &str,
+    ) -> rusqlite::Result<Vec<IndexedTorrent>> {
+        let mirror_id = mirror_id.to_string();
+        let external_id = external_id.to_string();
+        crate::db::blocking(&self.conn, move |conn| {
+            query_external_id(conn, &mirror_id, source, &external_id)
+        })
+        .await
+    }
+}
+
+fn query_external_id(
+    conn: &rusqlite::Connection,
+    mirror_id: &str,
+    source: ExternalSource,
+    external_id: &str,
+) -> rusqlite::Result<Vec<IndexedTorrent>> {
+    let mut stmt = conn.prepare_cached(
+            "SELECT t.mirror_id, t.id, t.title, t.pub_date, t.category, t.size, t.info_hash, t.submitter, t.information, t.indexed_at
+            FROM external_ids e
+            JOIN torrents t ON t.mirror_id = e.mirror_id AND t.id = e.torrent_id
+            WHERE e.mirror_id = ? AND e.source = ? AND e.external_id = ?
+            ORDER BY t.pub_date DESC",
+        )?;
+    let mut torrents = stmt
+        .query_map(
+            rusqlite::params![mirror_id, source.as_str(), external_id],
+            read_torrent,
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT source, external_id FROM external_ids WHERE mirror_id = ? AND torrent_id = ?",
+    )?;
+    for torrent in torrents.iter_mut() {
+        let rows = stmt.query_map(rusqlite::params![mirror_id, torrent.id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (source, external_id) = row?;
+            if let Ok(source) = ExternalSource::from_str(&source) {
+                torrent.external_ids.insert(source, external_id);
+            }
+        }
+    }
+    Ok(torrents)
+}
+
+fn read_torrent(row: &rusqlite::Row) -> rusqlite::Result<IndexedTorrent> {
+    Ok(IndexedTorrent {
+        mirror_id: row.get(0)?,
+        id: row.get(1)?,
+        title: row.get(2)?,
+        pub_date: row.get(3)?,
+        category: row.get(4)?,
+        size: row.get(5)?,
+        info_hash: row.get(6)?,
+        submitter: row.get(7)?,
+        information: row.get(8)?,
+        external_ids: ExternalIds::default(),
+        indexed_at: row.get(9)?,
+    })
+}
+
+/// Schema migrations of the index database.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS torrents (
+        mirror_id TEXT NOT NULL,
+        id INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        pub_date TEXT NOT NULL,
+        category TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        info_hash TEXT NOT NULL,
+        submitter TEXT NOT NULL,
+        information TEXT,
+        indexed_at TEXT NOT NULL,
+        PRIMARY KEY (mirror_id, id)
+    );
+    CREATE TABLE IF NOT EXISTS external_ids (
+        mirror_id TEXT NOT NULL,
+        torrent_id INTEGER NOT NULL,
+        source TEXT NOT NULL,
+        external_id TEXT NOT NULL,
+        PRIMARY KEY (mirror_id, torrent_id, source)
+    );
+    CREATE INDEX IF NOT EXISTS external_ids_lookup
+        ON external_ids (source, external_id, mirror_id);",
+    // `updated_at` is in microseconds so cursors can compare it exactly.
+    "CREATE TABLE IF NOT EXISTS listed (
+        mirror_id TEXT NOT NULL,
+        id INTEGER NOT NULL,
+        title TEXT NOT NULL,
+        pub_date TEXT NOT NULL,
+        description TEXT,
+        category TEXT NOT NULL,
+        size INTEGER NOT NULL,
+        seeders INTEGER NOT NULL,
+        leechers INTEGER NOT NULL,
+        downloads INTEGER NOT NULL,
+        comments INTEGER NOT NULL,
+        trusted INTEGER NOT NULL,
+        remake INTEGER NOT NULL,
+        info_hash TEXT,
+        first_seen_at TEXT NOT NULL,
+        updated_at INTEGER NOT NULL,
+        PRIMARY KEY (mirror_id, id)
+    );
+    CREATE INDEX IF NOT EXISTS listed_changes ON listed (mirror_id, updated_at, id);",
+    // `at` is in seconds since the epoch.
+    "CREATE TABLE IF NOT EXISTS swarm_stats (
+        mirror_id TEXT NOT NULL,
+        id INTEGER NOT NULL,
+        at INTEGER NOT NULL,
+        seeders INTEGER NOT NULL,
+        leechers INTEGER NOT NULL,
+        downloads INTEGER NOT NULL,
+        PRIMARY KEY (mirror_id, id, at)
+    ) WITHOUT ROWID;",
+    // Views are kept as JSON of the parsed page, with the raw HTML when
+    // archiving it is enabled, and never pruned: each torrent ever viewed
+    // keeps its latest copy.
+    "CREATE TABLE IF NOT EXISTS archived_views (
+        mirror_id TEXT NOT NULL,
+        id INTEGER NOT NULL,
+        view TEXT NOT NULL,
+        html TEXT,
+        archived_at TEXT NOT NULL,
+        PRIMARY KEY (mirror_id, id)
+    );
+    CREATE TABLE IF NOT EXISTS deleted_torrents (
+        mirror_id TEXT NOT NULL,
+        id INTEGER NOT NULL,
+        deleted_at TEXT NOT NULL,
+        reason TEXT NOT NULL,
+        PRIMARY KEY (mirror_id, id)
+    );",
+    // Lets swarm stats past their retention be pruned without a full scan.
+    "CREATE INDEX IF NOT EXISTS swarm_stats_at ON swarm_stats (at);",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn view(id: usize, description: &str) -> nyaa_parser::View {
+        nyaa_parser::View {
+            title: format!("Torrent {}", id),
+            link: String::new(),
+            pub_date: DateTime::from_timestamp(1_700_000_000 + id as i64, 0).unwrap(),
+            guid: String::new(),
+            id,
+            seeders: 0,
+            leechers: 0,
+            downloads: 0,
+            info_hash: "0".repeat(40),
+            category: "1_2".into(),
+            size: 1024,
+            trusted: false,
+            remake: false,
+            description_md: description.into(),
+            download_link: None,
+            magnet_link: None,
+            file_tree: Vec::new(),
+            comments: Vec::new(),
+            submitter: "Anonymous".into(),
+            information: None,
+            external_ids: ExternalIds::extract(description),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_find_by_external_id() {
+        let index = Index::open_in_memory();
+        let now = Utc::now();
+        index
+            .insert_view(
+                "nyaa",
+                &view(1, "https://anilist.co/anime/12345"),
+                None,
+                now,
+            )
+            .unwrap();
+        index
+            .insert_view(
+                "nyaa",
+                &view(
+                    2,
+                    "https://anilist.co/anime/12345 https://myanimelist.net/anime/99",
+                ),
+                None,
+                now,
+            )
+            .unwrap();
+        index
+            .insert_view(
+                "sukebei",
+                &view(3, "https://anilist.co/anime/12345"),
+                None,
+                now,
+            )
+            .unwrap();
+        // Re-indexing replaces the IDs found earlier.
+        index
+            .insert_view("nyaa", &view(1, "https://anilist.co/anime/1"), None, now)
+            .unwrap();
+
+        let found = index
+            .find_by_external_id("nyaa", ExternalSource::AniList, "12345")
+            .await
+            .unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].id, 2);
+        assert_eq!(found[0].external_ids.myanimelist, Some(99));
+        assert_eq!(found[0].external_ids.anilist, Some(12345));
+    }
+
+    fn list_item(id: usize, seeders: usize) -> nyaa_parser::ListItem {
+        nyaa_parser::ListItem {
+            title: format!("Torrent {}", id),
+            pub_date: DateTime::from_timestamp(1_700_000_000 + id as i64, 0).unwrap(),
+            id,
+            seeders,
+            category: "1_2".into(),
+            size: 1024,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_changes() {
+        let index = Index::open_in_memory();
+        let now = Utc::now();
+        index
+            .insert_list("nyaa", &[list_item(2, 5), list_item(1, 5)], false, now)
+            .unwrap();
+        index
+            .insert_list("sukebei", &[list_item(3, 5)], false, now)
+            .unwrap();
+        let ids = |items: &[ListedTorrent]| items.iter().map(|t| t.id).collect::<Vec<_>>();
+
+        let first = index.changes("nyaa", Cursor::default(), 1).await.unwrap();
+        assert_eq!(ids(&first), [1]);
+        let rest = index.changes("nyaa", first[0].cursor, 10).await.unwrap();
+        assert_eq!(ids(&rest), [2]);
+        let cursor = rest[0].cursor;
+        assert!(index.changes("nyaa", cursor, 10).await.unwrap().is_empty());
+
+        // Only changed torrents move past the cursor, even if the clock
+        // went back.
+        index
+            .insert_list(
+                "nyaa",
+                &[list_item(3, 0), list_item(2, 5), list_item(1, 9)],
+                false,
+                now,
+            )
+            .unwrap();
+        let changed = index.changes("nyaa", cursor, 10).await.unwrap();
+        assert_eq!(ids(&changed), [1, 3]);
+        assert_eq!(changed[0].seeders, 9);
+        assert!(changed[0].cursor > cursor);
+        assert_eq!(Cursor::decode(&cursor.encode()), Some(cursor));
+        assert_eq!(Cursor::decode("nope"), None);
+    }
+
+    #[tokio::test]
+    async fn test_swarm_stats() {
+        let index = Index::open_in_memory();
+        let at = |secs: i64| DateTime::from_timestamp(1_700_000_000 + secs, 0).unwrap();
+        for (secs, seeders) in [(0, 4), (60, 4), (120, 6), (4000, 6), (4060, 2)] {
+            index
+                .insert_list("nyaa", &[list_item(1, seeders)], false, at(secs))
+                .unwrap();
+        }
+        index
+            .insert_list("nyaa", &[list_item(2, 1)], false, at(0))
+            .unwrap();
+
+        // Repeats within the heartbeat are dropped.
+        let samples = index
+            .swarm_stats("nyaa", 1, at(0), at(5000), Duration::ZERO)
+            .await
+            .unwrap();
+        assert_eq!(
+            samples
+                .iter()
+                .map(|s| (s.at, s.seeders))
+                .collect::<Vec<_>>(),
+            [(at(0), 4), (at(120), 6), (at(4000), 6), (at(4060), 2)]
+        );
+        assert_eq!(
+            index.first_swarm_sample("nyaa", 1).await.unwrap(),
+            Some(at(0))
+        );
+        assert_eq!(index.first_swarm_sample("nyaa", 3).await.unwrap(), None);
+
+        let hourly = index
+            .swarm_stats("nyaa", 1, at(0), at(5000), Duration::from_secs(3600))
+            .await
+            .unwrap();
+        assert_eq!(hourly.len(), 2);
+        assert_eq!(hourly[0].seeders, 5);
+        assert_eq!(hourly[1].seeders, 4);
+
+        let growth = index.growth("nyaa", at(100)).await.unwrap();
+        assert_eq!(growth.len(), 1);
+        assert_eq!(growth[0].torrent.id, 1);
+        assert_eq!(growth[0].seeder_growth, -2);
+        assert_eq!(growth[0].observed_since, at(0));
+        assert!(index.growth("nyaa", at(4061)).await.unwrap().is_empty());
+
+        assert_eq!(index.prune_stats(at(100)).unwrap(), 2);
+        assert_eq!(
+            index.first_swarm_sample("nyaa", 1).await.unwrap(),
+            Some(at(120))
+        );
+        assert_eq!(index.first_swarm_sample("nyaa", 2).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_deletions() {
+        let index = Index::open_in_memory();
+        let now = Utc::now();
+        index
+            .insert_list(
+                "nyaa",
+                &[list_item(5, 1), list_item(4, 1), list_item(3, 1)],
+                false,
+                now,
+            )
+            .unwrap();
+        index
+            .insert_view("nyaa", &view(4, ""), Some("<html>"), now)
+            .unwrap();
+        let cursor = index.changes("nyaa", Cursor::default(), 10).await.unwrap()[2].cursor;
+
+        // Incomplete lists say nothing about missing torrents.
+        index
+            .insert_list("nyaa", &[list_item(5, 1), list_item(3, 1)], false, now)
+            .unwrap();
+        assert!(
+            index
+                .archived_view("nyaa", 4)
+                .await
+                .unwrap()
+                .unwrap()
+                .deletion
+                .is_none()
+        );
+
+        index
+            .insert_list(
+                "nyaa",
+                &[list_item(6, 1), list_item(5, 1), list_item(3, 1)],
+                true,
+                now,
+            )
+            .unwrap();
+        let archived = index.archived_view("nyaa", 4).await.unwrap().unwrap();
+        assert_eq!(archived.view.title, "Torrent 4");
+        assert_eq!(
+            archived.deletion.map(|d| d.reason),
+            Some(DeletionReason::MissingFromList)
+        );
+        let changed = index.changes("nyaa", cursor, 10).await.unwrap();
+        assert_eq!(
+            changed
+                .iter()
+                .map(|t| (t.id, t.deletion.is_some()))
+                .collect::<Vec<_>>(),
+            [(4, true), (6, false)]
+        );
+
+        // Later detections keep the first one, and a successful view clears it.
+        index
+            .insert_deletion("nyaa", 4, DeletionReason::NotFound, now)
+            .unwrap();
+        assert_eq!(
+            index
+                .archived_view("nyaa", 4)
+                .await
+                .unwrap()
+                .unwrap()
+                .deletion
+                .map(|d| d.reason),
+            Some(DeletionReason::MissingFromList)
+        );
+        index.insert_view("nyaa", &view(4, ""), None, now).unwrap();
+        assert!(
+            index
+                .archived_view("nyaa", 4)
+                .await
+                .unwrap()
+                .unwrap()
+                .deletion
+                .is_none()
+        );
+        assert!(index.archived_view("nyaa", 7).await.unwrap().is_none());
+
+        // Only torrents seen before are marked.
+        index
+            .insert_deletion("nyaa", 3, DeletionReason::NotFound, now)
+            .unwrap();
+        index
+            .insert_deletion("nyaa", 7, DeletionReason::NotFound, now)
+            .unwrap();
+        let deleted: Vec<usize> = index
+            .conn
+            .lock()
+            .unwrap()
+            .prepare("SELECT id FROM deleted_torrents ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(deleted, [3]);
+    }
+
+    #[tokio::test]
+    async fn test_archived_view_older_shape() {
+        let index = Index::open_in_memory();
+        // A view archived before the file tree, information and external IDs
+        // were parsed.
+        let view = r#"{
+            "title": "Torrent 8",
+            "link": "https://nyaa.si/view/8",
+            "pub_date": "2023-11-14T22:13:20Z",
+            "guid": "",
+            "id": 8,
+            "seeders": 1,
+            "leechers": 0,
+            "downloads": 3,
+            "info_hash": "0000000000000000000000000000000000000000",
+            "category": "1_2",
+            "size": 1024,
+            "trusted": false,
+            "remake": false,
+            "description_md": "",
+            "download_link": null,
+            "magnet_link": null,
+            "files": [{"id": 0, "name": "a.mkv", "path": "a.mkv", "size": 1024}],
+            "comments": [{"id": 1, "user": "someone", "date": "2023-11-15T00:00:00Z", "content": "thanks"}],
+            "submitter": "Anonymous"
+        }"#;
+        index
+            .conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO archived_views (mirror_id, id, view, archived_at) VALUES ('nyaa', 8, ?, ?)",
+                rusqlite::params![view, Utc::now()],
+            )
+            .unwrap();
+
+        let archived = index.archived_view("nyaa", 8).await.unwrap().unwrap();
+        assert_eq!(archived.view.title, "Torrent 8");
+        assert_eq!(archived.view.comments[0].content, "thanks");
+        assert!(archived.view.comments[0].avatar.is_none());
+        assert!(archived.view.file_tree.is_empty());
+        assert!(archived.view.information.is_none());
+        assert_eq!(archived.view.external_ids, ExternalIds::default());
+    }
+}
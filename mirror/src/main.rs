@@ -1,11 +1,18 @@
 use std::sync::Arc;
 
+use anyhow::Context;
+use auto_download::Rules;
 use axum::{Extension, Router};
 use clap::Parser;
 use cli::{MirrorConfig, MirrorType};
+use download_client::DownloadClients;
+use events::EventHub;
+use index::Index;
+use notification::Notifier;
 use rate_limiter::RateLimiter;
 use request_tracker::RequestTracker;
 use reqwest::Url;
+use saved_search::SavedSearches;
 use tokio::sync::Mutex;
 use tower_http::{
     compression::CompressionLayer,
@@ -14,21 +21,40 @@ use tower_http::{
 };
 
 mod api;
+mod auto_download;
 mod cache;
 mod cli;
 mod client;
+mod db;
+mod download_client;
+mod events;
+mod feed;
+mod index;
+mod notification;
+mod probe;
 mod rate_limiter;
+mod request_id;
 mod request_tracker;
+mod saved_search;
+mod scheduler;
+mod scrape;
+mod trending;
 
 #[derive(Debug, Clone)]
 pub struct Mirror {
     pub config: MirrorConfig,
     pub api_url: Url,
     pub client: Arc<Mutex<client::Client>>,
+    pub events: EventHub,
 }
 
 impl Mirror {
-    pub fn new(config: MirrorConfig, request_tracker: RequestTracker) -> anyhow::Result<Self> {
+    pub fn new(
+        config: MirrorConfig,
+        request_tracker: RequestTracker,
+        notifier: Notifier,
+        index: Index,
+    ) -> anyhow::Result<Self> {
         let api_url = if let Ok(url) = Url::parse(&config.url) {
             url
         } else if let Ok(url) = Url::parse(&format!("http://{}", config.url)) {
@@ -37,6 +63,21 @@ impl Mirror {
             Url::parse(&config.url)?
         };
 
+        let parser = nyaa_parser::ParserContext::new(config.layout_profile()?)
+            .with_context(|| format!("invalid layout profile for mirror {}", config.id))?;
+        let mut rate_limiter = RateLimiter::new(
+            config.window_requests.unwrap_or(10),
+            config
+                .window_size
+                .unwrap_or(std::time::Duration::from_secs(60)),
+        );
+        if config.probe_interval.is_some() {
+            rate_limiter = rate_limiter.reserve(
+                config
+                    .probe_budget_share
+                    .unwrap_or(probe::DEFAULT_BUDGET_SHARE),
+            );
+        }
         let client = client::Client::builder(&config.id, api_url.clone())
             .timeout(config.timeout.unwrap_or(std::time::Duration::from_secs(30)))
             .cache_dir(config.cache_dir.clone())
@@ -46,17 +87,18 @@ impl Mirror {
                     .cache_duration
                     .unwrap_or(std::time::Duration::from_secs(60)),
             )
-            .rate_limiter(RateLimiter::new(
-                config.window_requests.unwrap_or(10),
-                config
-                    .window_size
-                    .unwrap_or(std::time::Duration::from_secs(60)),
-            ))
+            .search_max_pages(config.search_max_pages.unwrap_or(10))
+            .rate_limiter(rate_limiter)
             .request_tracker(request_tracker)
+            .notifier(notifier)
+            .index(index)
+            .archive_html(config.archive_html.unwrap_or(false))
+            .parser(Arc::new(parser))
             .local_addr(config.local_addr.clone())
             .interface(config.interface.clone())
             .build();
         Ok(Self {
+            events: EventHub::new(&config.id),
             config,
             api_url,
             client: Arc::new(Mutex::new(client)),
@@ -78,6 +120,20 @@ impl Mirror {
     pub fn ty(&self) -> MirrorType {
         self.config.ty.clone()
     }
+
+    pub fn search_timeout(&self) -> std::time::Duration {
+        self.config
+            .search_timeout
+            .unwrap_or(std::time::Duration::from_secs(10))
+    }
+
+    pub fn scrape_overlay(&self) -> bool {
+        self.config.scrape_overlay.unwrap_or(false)
+    }
+
+    pub fn scrape_trackers(&self) -> &[String] {
+        self.config.scrape_trackers.as_deref().unwrap_or_default()
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -104,6 +160,17 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("loaded config: {:?}", config);
 
     let index_path = config.static_dir.join("index.html");
+    let index = Index::open(&config.index_db()).expect("failed to open index database");
+    index.spawn_maintenance(
+        config
+            .swarm_stats_retention
+            .unwrap_or(index::DEFAULT_STATS_RETENTION),
+    );
+    let saved_searches = SavedSearches::open(&config.saved_search_db())
+        .expect("failed to open saved search database");
+    let download_clients = DownloadClients::new(config.download_client.clone());
+    let rules = Rules::open(&config.auto_download_db(), config.watch_dir.clone())
+        .expect("failed to open auto-download database");
 
     let mut app = axum::Router::new()
         .route_service("/", ServeFile::new(index_path.clone()))
@@ -114,19 +181,93 @@ async fn main() -> anyhow::Result<()> {
                     "/mirror/{mirror}/list",
                     axum::routing::get(api::mirror::list::handler),
                 )
+                .route(
+                    "/mirror/{mirror}/list/stream",
+                    axum::routing::get(api::mirror::stream::handler),
+                )
                 .route(
                     "/mirror/{mirror}/view/{id}",
                     axum::routing::get(api::mirror::view::handler),
                 )
+                .route(
+                    "/mirror/{mirror}/view/{id}/stats",
+                    axum::routing::get(api::mirror::stats::handler),
+                )
+                .route(
+                    "/mirror/{mirror}/events",
+                    axum::routing::get(api::mirror::events::handler),
+                )
+                .route(
+                    "/mirror/{mirror}/changes",
+                    axum::routing::get(api::mirror::changes::handler),
+                )
+                .route(
+                    "/mirror/{mirror}/trending",
+                    axum::routing::get(api::mirror::trending::handler),
+                )
                 .route(
                     "/mirror/{mirror}/magnet/{id}",
                     axum::routing::get(api::mirror::magnet::handler),
                 )
+                .route(
+                    "/mirror/{mirror}/download/{id}",
+                    axum::routing::get(api::mirror::download::handler),
+                )
+                .route(
+                    "/mirror/{mirror}/send/{id}",
+                    axum::routing::post(api::mirror::send::handler),
+                )
+                .route(
+                    "/mirror/{mirror}/user/{name}",
+                    axum::routing::get(api::mirror::user::handler),
+                )
+                .route(
+                    "/mirror/{mirror}/external/{source}/{id}",
+                    axum::routing::get(api::mirror::external::handler),
+                )
                 .route("/mirror", axum::routing::get(api::mirror::handler))
-                .route("/health", axum::routing::get(api::health::handler)),
+                .route("/search", axum::routing::get(api::search::handler))
+                .route(
+                    "/saved-searches",
+                    axum::routing::get(api::saved_search::list).post(api::saved_search::create),
+                )
+                .route(
+                    "/saved-searches/{id}",
+                    axum::routing::get(api::saved_search::get).delete(api::saved_search::delete),
+                )
+                .route(
+                    "/rules",
+                    axum::routing::get(api::auto_download::list).post(api::auto_download::create),
+                )
+                .route(
+                    "/rules/preview",
+                    axum::routing::post(api::auto_download::preview),
+                )
+                .route(
+                    "/rules/{id}",
+                    axum::routing::get(api::auto_download::get)
+                        .put(api::auto_download::update)
+                        .delete(api::auto_download::delete),
+                )
+                .route(
+                    "/rules/{id}/history",
+                    axum::routing::get(api::auto_download::history),
+                )
+                .route("/health", axum::routing::get(api::health::handler))
+                .route(
+                    "/health/requests",
+                    axum::routing::get(api::health::requests::handler),
+                )
+                .route("/health/live", axum::routing::get(api::health::probe::live))
+                .route(
+                    "/health/ready",
+                    axum::routing::get(api::health::probe::ready),
+                ),
         )
+        .route("/feed/{file}", axum::routing::get(api::feed::handler))
         .route_service("/{*path}", ServeFile::new(index_path))
         .nest_service("/static", ServeDir::new(config.static_dir))
+        .layer(axum::middleware::from_fn(request_id::middleware))
         .layer(TraceLayer::new_for_http())
         .layer(CompressionLayer::new());
 
@@ -134,18 +275,66 @@ async fn main() -> anyhow::Result<()> {
         app = app.layer(tower_http::cors::CorsLayer::very_permissive());
     }
 
-    let request_tracker = request_tracker::RequestTracker::new(config.request_tracker_db.clone());
+    let mut request_tracker = RequestTracker::builder(config.request_tracker_db.clone());
+    if let Some(queue_size) = config.request_tracker_queue_size {
+        request_tracker = request_tracker.queue_size(queue_size);
+    }
+    if let Some(raw_retention) = config.request_tracker_raw_retention {
+        request_tracker = request_tracker.raw_retention(raw_retention);
+    }
+    if let Some(retention) = config.request_tracker_retention {
+        request_tracker = request_tracker.retention(retention);
+    }
+    let request_tracker = request_tracker.build();
+    let notifier = Notifier::new(config.notification.clone());
     let mext = MirrorExt {
         mirrors: config
             .mirror
             .iter()
             .map(|mirror_config| -> anyhow::Result<Mirror> {
-                Mirror::new(mirror_config.clone(), request_tracker.clone())
+                Mirror::new(
+                    mirror_config.clone(),
+                    request_tracker.clone(),
+                    notifier.clone(),
+                    index.clone(),
+                )
             })
             .collect::<Result<Vec<_>, _>>()?,
     };
 
-    let app = app.layer(Extension(mext)).layer(Extension(request_tracker));
+    for mirror in mext.iter() {
+        if let Some(interval) = mirror.config.probe_interval {
+            probe::spawn(mirror.clone(), interval);
+        }
+        events::spawn(
+            mirror.clone(),
+            mirror
+                .config
+                .events_interval
+                .unwrap_or(events::DEFAULT_INTERVAL),
+            mirror
+                .config
+                .events_budget_share
+                .unwrap_or(events::DEFAULT_BUDGET_SHARE),
+        );
+    }
+    saved_search::spawn(mext.clone(), saved_searches.clone(), notifier.clone());
+    auto_download::spawn(mext.clone(), rules.clone(), download_clients.clone());
+
+    let app = app
+        .layer(Extension(mext))
+        .layer(Extension(request_tracker))
+        .layer(Extension(index))
+        .layer(Extension(saved_searches))
+        .layer(Extension(download_clients))
+        .layer(Extension(rules))
+        .layer(Extension(trending::Trending::default()))
+        .layer(Extension(scrape::Scraper::new(
+            config.scrape_timeout.unwrap_or(scrape::DEFAULT_TIMEOUT),
+            config
+                .scrape_cache_duration
+                .unwrap_or(scrape::DEFAULT_CACHE_DURATION),
+        )));
 
     let listener = tokio::net::TcpListener::bind(config.listen_addr)
         .await
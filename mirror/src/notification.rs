@@ -0,0 +1,399 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+
+use crate::cli::{NotificationConfig, SinkConfig, SmtpConfig, SmtpTls};
+
+/// Minimum time between two notifications with the same deduplication key.
+const COOLDOWN: Duration = Duration::from_secs(60 * 60);
+
+/// Items listed in chat and email messages; webhooks get every item.
+const MAX_MESSAGE_ITEMS: usize = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventKind {
+    LayoutDrift,
+    SavedSearchMatch,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    LayoutDrift {
+        mirror_id: String,
+        url: String,
+        drift: nyaa_parser::canary::LayoutDrift,
+    },
+    /// New torrents matching a saved search.
+    SavedSearchMatch {
+        mirror_id: String,
+        search_id: i64,
+        search_name: String,
+        items: Vec<MatchedItem>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MatchedItem {
+    pub id: usize,
+    pub title: String,
+    /// Upstream page of the torrent.
+    pub url: String,
+    pub pub_date: chrono::DateTime<chrono::Utc>,
+    pub category: String,
+    pub size: u64,
+    pub seeders: usize,
+    pub leechers: usize,
+    pub trusted: bool,
+    pub remake: bool,
+    pub info_hash: Option<String>,
+    pub magnet_link: Option<String>,
+}
+
+impl From<&nyaa_parser::ListItem> for MatchedItem {
+    fn from(item: &nyaa_parser::ListItem) -> Self {
+        Self {
+            id: item.id,
+            title: item.title.clone(),
+            url: item.guid.clone(),
+            pub_date: item.pub_date,
+            category: item.category.clone(),
+            size: item.size,
+            seeders: item.seeders,
+            leechers: item.leechers,
+            trusted: item.trusted,
+            remake: item.remake,
+            info_hash: item.btih(),
+            magnet_link: item.magnet_link.clone(),
+        }
+    }
+}
+
+impl Event {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            Event::LayoutDrift { .. } => EventKind::LayoutDrift,
+            Event::SavedSearchMatch { .. } => EventKind::SavedSearchMatch,
+        }
+    }
+
+    /// Events sharing a key are only delivered once per cooldown period.
+    fn dedup_key(&self) -> String {
+        match self {
+            Event::LayoutDrift {
+                mirror_id, drift, ..
+            } => format!("layout_drift/{}/{:?}", mirror_id, drift.page),
+            Event::SavedSearchMatch {
+                search_id, items, ..
+            } => {
+                let ids = items
+                    .iter()
+                    .map(|item| item.id.to_string())
+                    .collect::<Vec<_>>();
+                format!("saved_search/{}/{}", search_id, ids.join(","))
+            }
+        }
+    }
+
+    /// One-line summary, used as the message title or email subject.
+    fn title(&self) -> String {
+        match self {
+            Event::LayoutDrift { mirror_id, .. } => {
+                format!("Layout drift on mirror {}", mirror_id)
+            }
+            Event::SavedSearchMatch {
+                mirror_id,
+                search_name,
+                items,
+                ..
+            } => format!(
+                "{} new {} for \"{}\" on {}",
+                items.len(),
+                if items.len() == 1 { "match" } else { "matches" },
+                search_name,
+                mirror_id
+            ),
+        }
+    }
+
+    /// Message lines, each with an optional link.
+    fn lines(&self) -> Vec<(String, Option<String>)> {
+        match self {
+            Event::LayoutDrift { url, drift, .. } => {
+                vec![(drift.to_string(), None), (url.clone(), Some(url.clone()))]
+            }
+            Event::SavedSearchMatch { items, .. } => {
+                let mut lines = items
+                    .iter()
+                    .take(MAX_MESSAGE_ITEMS)
+                    .map(|item| {
+                        (
+                            format!(
+                                "{} ({}, {} seeders)",
+                                item.title,
+                                crate::feed::format_size(item.size),
+                                item.seeders
+                            ),
+                            Some(item.url.clone()),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                if items.len() > MAX_MESSAGE_ITEMS {
+                    lines.push((
+                        format!("and {} more", items.len() - MAX_MESSAGE_ITEMS),
+                        None,
+                    ));
+                }
+                lines
+            }
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Payload<'a> {
+    timestamp: chrono::DateTime<chrono::Utc>,
+    #[serde(flatten)]
+    event: &'a Event,
+}
+
+#[derive(Debug)]
+struct Inner {
+    sinks: Vec<NotificationConfig>,
+    http: reqwest::Client,
+    last_sent: Mutex<HashMap<String, Instant>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    inner: Arc<Inner>,
+}
+
+impl Notifier {
+    pub fn new(sinks: Vec<NotificationConfig>) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                sinks,
+                http: reqwest::Client::builder()
+                    .timeout(Duration::from_secs(30))
+                    .build()
+                    .expect("failed to build HTTP client"),
+                last_sent: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Delivers `event` to every sink subscribed to its kind in the
+    /// background. Repeats within the cooldown period are dropped.
+    pub fn notify(&self, event: Event) {
+        let kind = event.kind();
+        let sinks = self
+            .inner
+            .sinks
+            .iter()
+            .filter(|sink| sink.events.as_ref().is_none_or(|e| e.contains(&kind)))
+            .cloned()
+            .collect::<Vec<_>>();
+        if sinks.is_empty() {
+            return;
+        }
+
+        let key = event.dedup_key();
+        let mut last_sent = self.inner.last_sent.lock().expect("notifier poisoned");
+        if !claim(&mut last_sent, &key, Instant::now()) {
+            tracing::debug!("suppressing repeated notification {}", key);
+            return;
+        }
+        drop(last_sent);
+
+        let inner = self.inner.clone();
+        tokio::spawn(async move {
+            let payload = Payload {
+                timestamp: chrono::Utc::now(),
+                event: &event,
+            };
+            for sink in sinks {
+                if let Err(e) = send(&inner.http, &sink.sink, &payload).await {
+                    tracing::warn!("failed to deliver {:?} notification: {:?}", kind, e);
+                }
+            }
+        });
+    }
+}
+
+/// Records `key` as sent at `now` unless it was sent within the cooldown
+/// period, forgetting keys whose cooldown has passed.
+fn claim(last_sent: &mut HashMap<String, Instant>, key: &str, now: Instant) -> bool {
+    last_sent.retain(|_, sent| now.duration_since(*sent) < COOLDOWN);
+    if last_sent.contains_key(key) {
+        return false;
+    }
+    last_sent.insert(key.to_string(), now);
+    true
+}
+
+async fn send(
+    http: &reqwest::Client,
+    sink: &SinkConfig,
+    payload: &Payload<'_>,
+) -> anyhow::Result<()> {
+    match sink {
+        SinkConfig::Webhook { url, secret } => {
+            let body = serde_json::to_vec(payload)?;
+            let mut request = http
+                .post(url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json");
+            if let Some(secret) = secret {
+                request = request.header(SIGNATURE_HEADER, sign(&secret.0, &body));
+            }
+            request.body(body).send().await?.error_for_status()?;
+        }
+        SinkConfig::Discord { url } => {
+            http.post(&url.0)
+                .json(&discord_payload(payload))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        SinkConfig::Slack { url } => {
+            http.post(&url.0)
+                .json(&slack_payload(payload))
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        SinkConfig::Smtp(config) => send_email(config, payload).await?,
+    }
+    Ok(())
+}
+
+/// Header carrying the webhook body signature.
+pub const SIGNATURE_HEADER: &str = "X-Signature-256";
+
+/// `sha256=` followed by the hex HMAC-SHA256 of `body` keyed with `secret`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    use hmac::Mac;
+
+    let mut mac = hmac::Hmac::<sha2::Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+fn discord_payload(payload: &Payload<'_>) -> serde_json::Value {
+    let description = payload
+        .event
+        .lines()
+        .into_iter()
+        .map(|(text, url)| match url {
+            Some(url) => format!("[{}]({})", escape_markdown(&text), url),
+            None => escape_markdown(&text),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    serde_json::json!({
+        "embeds": [{
+            "title": payload.event.title(),
+            "description": description,
+            "timestamp": payload.timestamp.to_rfc3339(),
+        }],
+    })
+}
+
+fn escape_markdown(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace('*', "\\*")
+        .replace('_', "\\_")
+}
+
+fn slack_payload(payload: &Payload<'_>) -> serde_json::Value {
+    let escape = |text: &str| {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    };
+    let mut text = format!("*{}*", escape(&payload.event.title()));
+    for (line, url) in payload.event.lines() {
+        text.push('\n');
+        match url {
+            Some(url) => text.push_str(&format!("• <{}|{}>", url, escape(&line))),
+            None => text.push_str(&format!("• {}", escape(&line))),
+        }
+    }
+    serde_json::json!({ "text": text })
+}
+
+async fn send_email(config: &SmtpConfig, payload: &Payload<'_>) -> anyhow::Result<()> {
+    use lettre::{
+        AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+        transport::smtp::authentication::Credentials,
+    };
+
+    let mut body = String::new();
+    for (line, url) in payload.event.lines() {
+        body.push_str(&line);
+        body.push('\n');
+        if let Some(url) = url {
+            body.push_str(&format!("  {}\n", url));
+        }
+    }
+
+    let mut message = Message::builder()
+        .from(config.from.parse().context("invalid from address")?)
+        .subject(payload.event.title());
+    for to in &config.to {
+        message = message.to(to.parse().context("invalid to address")?);
+    }
+    let message = message.body(body)?;
+
+    let mut transport = match config.tls {
+        SmtpTls::Starttls => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?,
+        SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+        SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+    };
+    if let Some(port) = config.port {
+        transport = transport.port(port);
+    }
+    if let (Some(username), Some(password)) = (&config.username, &config.password) {
+        transport = transport.credentials(Credentials::new(username.clone(), password.0.clone()));
+    }
+    transport.build().send(message).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sign() {
+        // Example from GitHub's webhook documentation.
+        assert_eq!(
+            sign("It's a Secret to Everybody", b"Hello, World!"),
+            "sha256=757107ea0eb2509fc211221cce984b8a37570b6d7586c22c46f4379c8b043e17"
+        );
+    }
+
+    #[test]
+    fn test_claim() {
+        let mut last_sent = HashMap::new();
+        let start = Instant::now();
+        assert!(claim(&mut last_sent, "a", start));
+        assert!(!claim(&mut last_sent, "a", start + COOLDOWN / 2));
+        assert!(claim(&mut last_sent, "b", start + COOLDOWN / 2));
+
+        // Expired keys are dropped, not only allowed again.
+        assert!(claim(&mut last_sent, "c", start + COOLDOWN));
+        assert_eq!(last_sent.len(), 2);
+        assert!(!last_sent.contains_key("a"));
+        assert!(claim(&mut last_sent, "a", start + COOLDOWN));
+    }
+}
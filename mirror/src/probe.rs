@@ -0,0 +1,36 @@
+use std::time::Duration;
+
+use crate::Mirror;
+
+/// Share of the rate limiter budget reserved for probes when not
+/// configured.
+pub const DEFAULT_BUDGET_SHARE: f64 = 0.1;
+
+/// Spawns a background task that periodically probes `mirror`'s upstream.
+pub fn spawn(mirror: Mirror, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+
+            let id = format!("probe-{}", uuid::Uuid::new_v4());
+            let outcome =
+                crate::request_id::scope(id, async { mirror.client.lock().await.probe().await })
+                    .await;
+
+            match outcome {
+                Some(outcome) => tracing::debug!(
+                    "probed mirror {}: success={}, elapsed_time={}",
+                    mirror.id(),
+                    outcome.success,
+                    outcome.elapsed_time
+                ),
+                None => tracing::debug!(
+                    "skipped probe of mirror {}: reserved rate limiter budget in use",
+                    mirror.id()
+                ),
+            }
+        }
+    });
+}
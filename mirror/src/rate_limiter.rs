@@ -5,7 +5,20 @@ use tokio::sync::Mutex;
 pub struct RateLimiter {
     pub max_requests: usize,
     pub time_window: Duration,
-    requests: Mutex<Vec<Instant>>,
+    /// Slots per window kept free for [`RateLimiter::try_acquire_reserved`].
+    reserved: usize,
+    /// Times of the requests in the current window, and whether each took a
+    /// reserved slot.
+    requests: Mutex<Vec<(Instant, bool)>>,
+}
+
+/// Requests in the window after dropping expired ones, and how many of the
+/// reserved slots are still free.
+fn usage(requests: &mut Vec<(Instant, bool)>, window: Duration, reserved: usize) -> (usize, usize) {
+    let cutoff = Instant::now() - window;
+    requests.retain(|&(timestamp, _)| timestamp > cutoff);
+    let reserved_in_use = requests.iter().filter(|&&(_, reserved)| reserved).count();
+    (requests.len(), reserved.saturating_sub(reserved_in_use))
 }
 
 impl RateLimiter {
@@ -13,10 +26,20 @@ impl RateLimiter {
         RateLimiter {
             max_requests,
             time_window,
+            reserved: 0,
             requests: Mutex::new(Vec::with_capacity(max_requests)),
         }
     }
 
+    /// Keeps `share` of `max_requests` (at least one) free for
+    /// [`RateLimiter::try_acquire_reserved`], so background work that must
+    /// run, like probes, is not starved by users. Other requests get the
+    /// rest of the window.
+    pub fn reserve(mut self, share: f64) -> Self {
+        self.reserved = self.share_limit(share);
+        self
+    }
+
     pub async fn acquire(&self) {
         loop {
             let can_proceed = self.try_acquire().await;
@@ -29,16 +52,107 @@ impl RateLimiter {
 
     pub async fn try_acquire(&self) -> bool {
         let mut requests = self.requests.lock().await;
-        let now = Instant::now();
+        let (used, reserved_free) = usage(&mut requests, self.time_window, self.reserved);
+
+        if used + reserved_free < self.max_requests {
+            requests.push((Instant::now(), false));
+            true
+        } else {
+            false
+        }
+    }
 
-        let cutoff = now - self.time_window;
-        requests.retain(|&timestamp| timestamp > cutoff);
+    /// Takes one of the reserved slots, which other requests never use.
+    /// Fails once all of them are in use in the current window.
+    pub async fn try_acquire_reserved(&self) -> bool {
+        let mut requests = self.requests.lock().await;
+        let (used, reserved_free) = usage(&mut requests, self.time_window, self.reserved);
 
-        if requests.len() < self.max_requests {
-            requests.push(now);
+        if reserved_free > 0 && used < self.max_requests {
+            requests.push((Instant::now(), true));
             true
         } else {
             false
         }
     }
+
+    fn share_limit(&self, share: f64) -> usize {
+        ((self.max_requests as f64 * share).ceil() as usize).clamp(1, self.max_requests)
+    }
+
+    /// Whether fewer than `share` of `max_requests` are in use in the
+    /// current window, without taking a slot. Used by background work that
+    /// must not compete with users.
+    pub async fn has_share(&self, share: f64) -> bool {
+        let limit = self.share_limit(share);
+        let requests = self.requests.lock().await;
+        let cutoff = Instant::now() - self.time_window;
+        requests
+            .iter()
+            .filter(|&&(timestamp, _)| timestamp > cutoff)
+            .count()
+            < limit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_has_share() {
+        let limiter = RateLimiter::new(4, Duration::from_millis(100));
+        assert_eq!(limiter.share_limit(0.5), 2);
+        assert_eq!(limiter.share_limit(0.1), 1);
+        assert_eq!(limiter.share_limit(2.0), 4);
+
+        assert!(limiter.has_share(0.5).await);
+        assert!(limiter.try_acquire().await);
+        assert!(limiter.has_share(0.5).await);
+        assert!(limiter.try_acquire().await);
+        assert!(!limiter.has_share(0.5).await);
+
+        // Users may still take the rest of the window.
+        assert!(limiter.try_acquire().await);
+        assert!(limiter.try_acquire().await);
+        assert!(!limiter.try_acquire().await);
+        assert!(!limiter.has_share(1.0).await);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(limiter.has_share(0.5).await);
+    }
+
+    #[tokio::test]
+    async fn test_has_share_does_not_acquire() {
+        let limiter = RateLimiter::new(1, Duration::from_secs(60));
+        assert!(limiter.has_share(0.1).await);
+        assert!(limiter.has_share(0.1).await);
+        assert!(limiter.try_acquire().await);
+        assert!(!limiter.has_share(0.1).await);
+    }
+
+    #[tokio::test]
+    async fn test_try_acquire_reserved() {
+        let limiter = RateLimiter::new(4, Duration::from_millis(100)).reserve(0.1);
+        assert!(
+            !RateLimiter::new(4, Duration::from_secs(1))
+                .try_acquire_reserved()
+                .await
+        );
+
+        // Users can't take the reserved slot, even with the window full.
+        assert!(limiter.try_acquire().await);
+        assert!(limiter.try_acquire().await);
+        assert!(limiter.try_acquire().await);
+        assert!(!limiter.try_acquire().await);
+        assert!(limiter.try_acquire_reserved().await);
+        assert!(!limiter.try_acquire_reserved().await);
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert!(limiter.try_acquire_reserved().await);
+        assert!(limiter.try_acquire().await);
+        assert!(limiter.try_acquire().await);
+        assert!(limiter.try_acquire().await);
+        assert!(!limiter.try_acquire().await);
+    }
 }
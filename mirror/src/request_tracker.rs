@@ -1,133 +1,1126 @@
-use std::path::PathBuf;
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
+use chrono::{DateTime, DurationRound, Utc};
 use reqwest::Url;
+use rusqlite::OptionalExtension;
+use tokio::sync::mpsc;
+
+/// Upper bounds (in seconds) of the latency histogram buckets kept for each
+/// hourly aggregate. The last bucket catches everything slower.
+pub const LATENCY_BUCKETS: [f64; 11] =
+    [0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0];
+
+/// Classification of why an upstream request failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    Timeout,
+    Connect,
+    /// 403, usually a bot challenge.
+    Forbidden,
+    NotFound,
+    RateLimited,
+    ServerError,
+    ClientError,
+    /// The response body could not be read.
+    Body,
+    UnsupportedContentType,
+    Parse,
+    /// The page parsed but contained no items.
+    Empty,
+    Request,
+}
+
+impl ErrorKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Connect => "connect",
+            ErrorKind::Forbidden => "forbidden",
+            ErrorKind::NotFound => "not_found",
+            ErrorKind::RateLimited => "rate_limited",
+            ErrorKind::ServerError => "server_error",
+            ErrorKind::ClientError => "client_error",
+            ErrorKind::Body => "body",
+            ErrorKind::UnsupportedContentType => "unsupported_content_type",
+            ErrorKind::Parse => "parse",
+            ErrorKind::Empty => "empty",
+            ErrorKind::Request => "request",
+        }
+    }
+
+    pub fn from_status(status: reqwest::StatusCode) -> Self {
+        match status.as_u16() {
+            403 => ErrorKind::Forbidden,
+            404 => ErrorKind::NotFound,
+            429 => ErrorKind::RateLimited,
+            500..=599 => ErrorKind::ServerError,
+            _ => ErrorKind::ClientError,
+        }
+    }
+
+    pub fn from_reqwest(error: &reqwest::Error) -> Self {
+        if error.is_timeout() {
+            ErrorKind::Timeout
+        } else if error.is_connect() {
+            ErrorKind::Connect
+        } else if error.is_body() || error.is_decode() {
+            ErrorKind::Body
+        } else if let Some(status) = error.status() {
+            ErrorKind::from_status(status)
+        } else {
+            ErrorKind::Request
+        }
+    }
+}
+
+impl std::str::FromStr for ErrorKind {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "timeout" => Ok(ErrorKind::Timeout),
+            "connect" => Ok(ErrorKind::Connect),
+            "forbidden" => Ok(ErrorKind::Forbidden),
+            "not_found" => Ok(ErrorKind::NotFound),
+            "rate_limited" => Ok(ErrorKind::RateLimited),
+            "server_error" => Ok(ErrorKind::ServerError),
+            "client_error" => Ok(ErrorKind::ClientError),
+            "body" => Ok(ErrorKind::Body),
+            "unsupported_content_type" => Ok(ErrorKind::UnsupportedContentType),
+            "parse" => Ok(ErrorKind::Parse),
+            "empty" => Ok(ErrorKind::Empty),
+            "request" => Ok(ErrorKind::Request),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Details about a single upstream request.
+#[derive(Debug, Clone, Default)]
+pub struct RequestOutcome {
+    pub success: bool,
+    pub elapsed_time: f64,
+    pub upstream_status: Option<u16>,
+    pub response_bytes: Option<u64>,
+    pub content_type: Option<String>,
+    pub error_kind: Option<ErrorKind>,
+    pub item_count: Option<usize>,
+    pub layout_drift: Option<nyaa_parser::canary::LayoutDrift>,
+}
+
+#[derive(Debug, Clone)]
+struct RequestRecord {
+    mirror_id: String,
+    timestamp: DateTime<Utc>,
+    path: String,
+    cache_hit: bool,
+    /// Background health probes are stored apart from user traffic.
+    probe: bool,
+    request_id: Option<String>,
+    outcome: RequestOutcome,
+}
 
 #[derive(Debug, Clone)]
 pub struct RequestTracker {
-    db_path: PathBuf,
+    sender: mpsc::Sender<RequestRecord>,
+    reader: Arc<Mutex<rusqlite::Connection>>,
 }
 
-impl RequestTracker {
-    pub fn new(db_path: PathBuf) -> Self {
-        let conn = rusqlite::Connection::open(db_path.clone()).expect("failed to open database");
-        conn.execute(
-            "CREATE TABLE IF NOT EXISTS requests (
-                id INTEGER PRIMARY KEY,
-                mirror_id TEXT NOT NULL,
-                timestamp TEXT NOT NULL,
-                path TEXT NOT NULL,
-                success INTEGER NOT NULL,
-                cache_hit INTEGER NOT NULL,
-                elapsed_time REAL NOT NULL
-            )",
-            [],
-        )
-        .expect("failed to create table");
+fn full_path<Q>(url: &Url, query: &Q) -> String
+where
+    Q: serde::Serialize,
+{
+    let mut url = url.clone();
+    match serde_urlencoded::to_string(query) {
+        // Queries already built into the URL are passed as `()`.
+        Ok(query_string) if query_string.is_empty() => {}
+        Ok(query_string) => url.set_query(Some(&query_string)),
+        Err(_) => url.set_query(None),
+    }
+    url.to_string()
+}
 
-        Self { db_path }
+impl RequestTracker {
+    pub fn builder(db_path: PathBuf) -> RequestTrackerBuilder {
+        RequestTrackerBuilder::new(db_path)
     }
 
     pub fn track_request_cached<Q>(&self, mirror_id: &str, url: &Url, query: &Q)
     where
         Q: serde::Serialize,
     {
-        let mut url = url.clone();
-        match serde_urlencoded::to_string(query) {
-            Ok(query_string) => url.set_query(Some(&query_string)),
-            Err(_) => url.set_query(None),
-        }
-        let full_path = url.as_str();
-        self.register(mirror_id, &full_path, true, true, 0.0);
+        let outcome = RequestOutcome {
+            success: true,
+            ..Default::default()
+        };
+        self.register(mirror_id, full_path(url, query), true, false, outcome);
     }
 
-    pub fn track_request<Q>(
-        &self,
-        mirror_id: &str,
-        url: &Url,
-        query: &Q,
-        success: bool,
-        elapsed_time: f64,
-    ) where
+    pub fn track_request<Q>(&self, mirror_id: &str, url: &Url, query: &Q, outcome: RequestOutcome)
+    where
         Q: serde::Serialize,
     {
-        let mut url = url.clone();
-        match serde_urlencoded::to_string(query) {
-            Ok(query_string) => url.set_query(Some(&query_string)),
-            Err(_) => url.set_query(None),
-        }
-        let full_path = url.as_str();
-        self.register(mirror_id, &full_path, success, false, elapsed_time);
+        self.register(mirror_id, full_path(url, query), false, false, outcome);
+    }
+
+    pub fn track_probe(&self, mirror_id: &str, url: &Url, outcome: RequestOutcome) {
+        self.register(mirror_id, url.to_string(), false, true, outcome);
     }
 
+    /// Queues a request for the background writer. This never blocks: when
+    /// the queue is full the record is dropped rather than slowing down the
+    /// request path.
     fn register(
         &self,
         mirror_id: &str,
-        path: &str,
-        success: bool,
+        path: String,
         cache_hit: bool,
-        elapsed_time: f64,
+        probe: bool,
+        outcome: RequestOutcome,
     ) {
+        let request_id = crate::request_id::current();
         tracing::trace!(
-            "registering request: mirror_id={}, path={}, success={}, cache_hit={}, elapsed_time={}",
+            "registering request: mirror_id={}, path={}, cache_hit={}, request_id={:?}, outcome={:?}",
             mirror_id,
             path,
-            success,
             cache_hit,
-            elapsed_time
+            request_id,
+            outcome
         );
-        let conn =
-            rusqlite::Connection::open(self.db_path.clone()).expect("failed to open database");
-        match conn.execute(
-            "INSERT INTO requests (mirror_id, timestamp, path, success, cache_hit, elapsed_time) VALUES (?, ?, ?, ?, ?, ?)",
-            rusqlite::params![
-                mirror_id,
-                chrono::Utc::now().to_rfc3339(),
-                path,
-                success as i32,
-                cache_hit as i32,
-                elapsed_time
-            ],
+        let record = RequestRecord {
+            mirror_id: mirror_id.to_string(),
+            timestamp: Utc::now(),
+            path,
+            cache_hit,
+            probe,
+            request_id,
+            outcome,
+        };
+        match self.sender.try_send(record) {
+            Ok(()) => {}
+            Err(mpsc::error::TrySendError::Full(_)) => {
+                tracing::warn!("request tracker queue is full, dropping request");
+            }
+            Err(mpsc::error::TrySendError::Closed(_)) => {
+                tracing::warn!("request tracker writer has stopped, dropping request");
+            }
+        }
+    }
+
+    /// Returns one page of raw requests matching `filter`, newest first,
+    /// together with the total number of matching requests.
+    pub async fn requests(&self, filter: RequestFilter) -> (usize, Vec<RequestLogEntry>) {
+        crate::db::blocking(&self.reader, move |conn| query_requests(conn, &filter)).await
+    }
+
+    /// Summarizes all requests for `mirror_id` since `since`, combining raw
+    /// rows with hourly aggregates for the part of the window that has
+    /// already been rolled up.
+    pub async fn summary(&self, mirror_id: &str, since: DateTime<Utc>) -> RequestSummary {
+        let mirror_id = mirror_id.to_string();
+        crate::db::blocking(&self.reader, move |conn| {
+            match summarize(conn, &mirror_id, since) {
+                Ok(summary) => summary,
+                Err(e) => {
+                    tracing::warn!("failed to summarize requests: {}", e);
+                    RequestSummary::default()
+                }
+            }
+        })
+        .await
+    }
+}
+
+impl RequestTracker {
+    /// Summarizes background probes for `mirror_id` since `since`. The last
+    /// probe is reported even when it is older than `since`.
+    pub async fn probe_summary(&self, mirror_id: &str, since: DateTime<Utc>) -> ProbeSummary {
+        let mirror_id = mirror_id.to_string();
+        crate::db::blocking(&self.reader, move |conn| {
+            match summarize_probes(conn, &mirror_id, since) {
+                Ok(summary) => summary,
+                Err(e) => {
+                    tracing::warn!("failed to summarize probes: {}", e);
+                    ProbeSummary::default()
+                }
+            }
+        })
+        .await
+    }
+
+    /// Whether the background writer is still accepting records.
+    pub fn is_alive(&self) -> bool {
+        !self.sender.is_closed()
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ProbeResult {
+    pub timestamp: DateTime<Utc>,
+    pub success: bool,
+    pub elapsed_time: f64,
+    pub upstream_status: Option<u16>,
+    pub error_kind: Option<ErrorKind>,
+    pub item_count: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ProbeSummary {
+    pub count: u64,
+    pub success_count: u64,
+    pub last: Option<ProbeResult>,
+}
+
+fn summarize_probes(
+    conn: &rusqlite::Connection,
+    mirror_id: &str,
+    since: DateTime<Utc>,
+) -> rusqlite::Result<ProbeSummary> {
+    let (count, success_count) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(success), 0) FROM probes WHERE mirror_id = ? AND timestamp >= ?",
+        rusqlite::params![mirror_id, since.to_rfc3339()],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    let last = conn
+        .query_row(
+            "SELECT timestamp, success, elapsed_time, upstream_status, error_kind, item_count FROM probes WHERE mirror_id = ? ORDER BY timestamp DESC LIMIT 1",
+            [mirror_id],
+            |row| {
+                Ok(ProbeResult {
+                    timestamp: row.get(0)?,
+                    success: row.get(1)?,
+                    elapsed_time: row.get(2)?,
+                    upstream_status: row.get(3)?,
+                    error_kind: row
+                        .get::<_, Option<String>>(4)?
+                        .and_then(|kind| kind.parse().ok()),
+                    item_count: row.get(5)?,
+                })
+            },
         )
-        {
-            Ok(_) => {}
-            Err(e) => {
-                tracing::warn!("failed to insert request into database: {}", e);
+        .optional()?;
+    Ok(ProbeSummary {
+        count,
+        success_count,
+        last,
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct RequestFilter {
+    pub mirror_id: Option<String>,
+    pub success: Option<bool>,
+    pub cache_hit: Option<bool>,
+    pub error_kind: Option<ErrorKind>,
+    pub request_id: Option<String>,
+    pub path: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub offset: usize,
+    pub limit: usize,
+}
+
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct RequestLogEntry {
+    pub timestamp: DateTime<Utc>,
+    pub mirror_id: String,
+    pub path: String,
+    pub success: bool,
+    pub cache_hit: bool,
+    pub elapsed_time: f64,
+    pub request_id: Option<String>,
+    pub upstream_status: Option<u16>,
+    pub response_bytes: Option<u64>,
+    pub content_type: Option<String>,
+    pub error_kind: Option<ErrorKind>,
+    pub item_count: Option<usize>,
+    pub layout_drift: Option<nyaa_parser::canary::LayoutDrift>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RequestSummary {
+    pub count: u64,
+    pub success_count: u64,
+    pub cache_hit_count: u64,
+    /// Requests that went to the upstream, i.e. were not served from cache.
+    pub upstream_count: u64,
+    pub upstream_success_count: u64,
+    pub latency_p50: Option<f64>,
+    pub latency_p95: Option<f64>,
+    pub latency_p99: Option<f64>,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_failure: Option<DateTime<Utc>>,
+    /// Upstream responses whose markup did not match the expected layout.
+    pub layout_drift_count: u64,
+}
+
+fn summarize(
+    conn: &rusqlite::Connection,
+    mirror_id: &str,
+    since: DateTime<Utc>,
+) -> rusqlite::Result<RequestSummary> {
+    let mut total = HourlyAggregate::default();
+    let mut has_aggregates = false;
+    {
+        // Only hours wholly inside the window are taken from the aggregates.
+        // Raw rows cover the rest, so an hour starting before `since` isn't
+        // counted once rolled up.
+        let hour = since
+            .duration_trunc(chrono::Duration::hours(1))
+            .unwrap_or(since);
+        let since_hour = if hour < since {
+            hour + chrono::Duration::hours(1)
+        } else {
+            hour
+        }
+        .to_rfc3339();
+        let mut stmt = conn.prepare_cached(
+            "SELECT count, success_count, cache_hit_count, elapsed_sum, elapsed_max, latency_histogram, last_success, last_failure, layout_drift_count FROM request_aggregates WHERE mirror_id = ? AND hour >= ?",
+        )?;
+        for aggregate in stmt.query_map(rusqlite::params![mirror_id, since_hour], read_aggregate)? {
+            total.merge(&aggregate?);
+            has_aggregates = true;
+        }
+    }
+
+    let mut latencies = Vec::new();
+    {
+        let mut stmt = conn.prepare_cached(
+            "SELECT timestamp, success, cache_hit, elapsed_time, layout_drift IS NOT NULL FROM requests WHERE mirror_id = ? AND timestamp >= ?",
+        )?;
+        let mut rows = stmt.query(rusqlite::params![mirror_id, since.to_rfc3339()])?;
+        while let Some(row) = rows.next()? {
+            let cache_hit = row.get::<_, bool>(2)?;
+            let elapsed_time = row.get::<_, f64>(3)?;
+            total.add(
+                row.get(0)?,
+                row.get(1)?,
+                cache_hit,
+                elapsed_time,
+                row.get(4)?,
+            );
+            if !cache_hit {
+                latencies.push(elapsed_time);
             }
         }
     }
 
-    pub fn get_requests(
-        &self,
-        mirror_id: &str,
-    ) -> Vec<(chrono::DateTime<chrono::Utc>, String, bool, bool, f64)> {
-        let conn =
-            rusqlite::Connection::open(self.db_path.clone()).expect("failed to open database");
-        let mut stmt = conn
-            .prepare("SELECT timestamp, path, success, cache_hit, elapsed_time FROM requests WHERE mirror_id = ? ORDER BY timestamp DESC LIMIT 250")
-            .expect("failed to prepare statement");
-        let rows = stmt.query_map([mirror_id], |row| {
-            Ok((
-                row.get::<_, chrono::DateTime<chrono::Utc>>(0)?,
-                row.get::<_, String>(1)?,
-                row.get::<_, bool>(2)?,
-                row.get::<_, bool>(3)?,
-                row.get::<_, f64>(4)?,
-            ))
-        });
-        let rows = match rows {
-            Ok(rows) => rows,
+    latencies.sort_by(f64::total_cmp);
+    let percentile = |p: f64| {
+        if has_aggregates {
+            histogram_percentile(&total.latency_histogram, total.elapsed_max, p)
+        } else {
+            exact_percentile(&latencies, p)
+        }
+    };
+
+    Ok(RequestSummary {
+        count: total.count,
+        success_count: total.success_count,
+        cache_hit_count: total.cache_hit_count,
+        upstream_count: total.count - total.cache_hit_count,
+        upstream_success_count: total.success_count.saturating_sub(total.cache_hit_count),
+        latency_p50: percentile(0.5),
+        latency_p95: percentile(0.95),
+        latency_p99: percentile(0.99),
+        last_success: total.last_success,
+        last_failure: total.last_failure,
+        layout_drift_count: total.layout_drift_count,
+    })
+}
+
+fn latency_bucket(elapsed_time: f64) -> usize {
+    LATENCY_BUCKETS
+        .iter()
+        .position(|&bound| elapsed_time <= bound)
+        .unwrap_or(LATENCY_BUCKETS.len())
+}
+
+/// Nearest-rank percentile over sorted raw latencies.
+fn exact_percentile(values: &[f64], p: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    let rank = ((p * values.len() as f64).ceil() as usize).clamp(1, values.len());
+    Some(values[rank - 1])
+}
+
+/// Percentile estimate from a latency histogram, reported as the upper bound
+/// of the bucket holding the requested rank (capped at the observed maximum).
+fn histogram_percentile(histogram: &[u64], max: f64, p: f64) -> Option<f64> {
+    let total = histogram.iter().sum::<u64>();
+    if total == 0 {
+        return None;
+    }
+    let rank = ((p * total as f64).ceil() as u64).clamp(1, total);
+    let mut cumulative = 0;
+    for (bucket, count) in histogram.iter().enumerate() {
+        cumulative += count;
+        if cumulative >= rank {
+            let bound = LATENCY_BUCKETS.get(bucket).copied().unwrap_or(max);
+            return Some(bound.min(max));
+        }
+    }
+    Some(max)
+}
+
+pub struct RequestTrackerBuilder {
+    db_path: PathBuf,
+    queue_size: usize,
+    batch_size: usize,
+    raw_retention: Duration,
+    retention: Duration,
+    maintenance_interval: Duration,
+}
+
+impl RequestTrackerBuilder {
+    pub fn new(db_path: PathBuf) -> Self {
+        Self {
+            db_path,
+            queue_size: 4096,
+            batch_size: 256,
+            raw_retention: Duration::from_secs(24 * 60 * 60),
+            retention: Duration::from_secs(30 * 24 * 60 * 60),
+            maintenance_interval: Duration::from_secs(5 * 60),
+        }
+    }
+
+    pub fn queue_size(mut self, queue_size: usize) -> Self {
+        self.queue_size = queue_size.max(1);
+        self
+    }
+
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// How long individual requests are kept before being rolled up into
+    /// hourly aggregates.
+    pub fn raw_retention(mut self, raw_retention: Duration) -> Self {
+        self.raw_retention = raw_retention;
+        self
+    }
+
+    /// How long hourly aggregates are kept before being deleted.
+    pub fn retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self
+    }
+
+    pub fn maintenance_interval(mut self, maintenance_interval: Duration) -> Self {
+        self.maintenance_interval = maintenance_interval;
+        self
+    }
+
+    /// Opens the database and spawns the background writer. Must be called
+    /// from within a tokio runtime.
+    pub fn build(self) -> RequestTracker {
+        let writer = crate::db::open(&self.db_path, MIGRATIONS, "request tracker")
+            .expect("failed to open database");
+        let reader = crate::db::open(&self.db_path, MIGRATIONS, "request tracker")
+            .expect("failed to open database");
+
+        let (sender, receiver) = mpsc::channel(self.queue_size);
+        let writer = Writer {
+            conn: writer,
+            batch_size: self.batch_size,
+            raw_retention: self.raw_retention,
+            retention: self.retention,
+        };
+        tokio::spawn(writer.run(receiver, self.maintenance_interval));
+
+        RequestTracker {
+            sender,
+            reader: Arc::new(Mutex::new(reader)),
+        }
+    }
+}
+
+struct Writer {
+    conn: rusqlite::Connection,
+    batch_size: usize,
+    raw_retention: Duration,
+    retention: Duration,
+}
+
+impl Writer {
+    async fn run(self, mut receiver: mpsc::Receiver<RequestRecord>, interval: Duration) {
+        let mut writer = self;
+        let mut batch = Vec::with_capacity(writer.batch_size);
+        let mut maintenance = tokio::time::interval(interval);
+        maintenance.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            tokio::select! {
+                received = receiver.recv_many(&mut batch, writer.batch_size) => {
+                    if received == 0 {
+                        break;
+                    }
+                    let records = std::mem::take(&mut batch);
+                    writer = writer.blocking(move |writer| {
+                        if let Err(e) = insert_requests(&mut writer.conn, &records) {
+                            tracing::warn!("failed to insert {} requests into database: {}", records.len(), e);
+                        }
+                    }).await;
+                }
+                _ = maintenance.tick() => {
+                    writer = writer.blocking(|writer| writer.maintain(Utc::now())).await;
+                }
+            }
+        }
+
+        tracing::debug!("request tracker writer stopped");
+    }
+
+    /// Runs `f` on the blocking thread pool, handing the writer (and with it
+    /// the connection) back once it is done.
+    async fn blocking(self, f: impl FnOnce(&mut Self) + Send + 'static) -> Self {
+        tokio::task::spawn_blocking(move || {
+            let mut writer = self;
+            f(&mut writer);
+            writer
+        })
+        .await
+        .expect("request tracker writer panicked")
+    }
+
+    fn maintain(&mut self, now: DateTime<Utc>) {
+        let raw_cutoff = now - chrono::Duration::from_std(self.raw_retention).unwrap_or_default();
+        match rollup_requests(&mut self.conn, raw_cutoff) {
+            Ok(0) => {}
+            Ok(count) => tracing::debug!("rolled up {} requests into hourly aggregates", count),
+            Err(e) => tracing::warn!("failed to roll up requests: {}", e),
+        }
+
+        let cutoff = now - chrono::Duration::from_std(self.retention).unwrap_or_default();
+        match prune(&self.conn, cutoff) {
+            Ok(0) => {}
+            Ok(count) => tracing::debug!("pruned {} hourly aggregates and probes", count),
+            Err(e) => tracing::warn!("failed to prune hourly aggregates and probes: {}", e),
+        }
+    }
+}
+
+fn query_requests(
+    conn: &rusqlite::Connection,
+    filter: &RequestFilter,
+) -> (usize, Vec<RequestLogEntry>) {
+    let mut conditions = Vec::new();
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+    if let Some(mirror_id) = &filter.mirror_id {
+        conditions.push("mirror_id = ?");
+        params.push(Box::new(mirror_id.clone()));
+    }
+    if let Some(success) = filter.success {
+        conditions.push("success = ?");
+        params.push(Box::new(success));
+    }
+    if let Some(cache_hit) = filter.cache_hit {
+        conditions.push("cache_hit = ?");
+        params.push(Box::new(cache_hit));
+    }
+    if let Some(error_kind) = filter.error_kind {
+        conditions.push("error_kind = ?");
+        params.push(Box::new(error_kind.as_str()));
+    }
+    if let Some(request_id) = &filter.request_id {
+        conditions.push("request_id = ?");
+        params.push(Box::new(request_id.clone()));
+    }
+    if let Some(path) = &filter.path {
+        conditions.push("instr(path, ?) > 0");
+        params.push(Box::new(path.clone()));
+    }
+    if let Some(since) = filter.since {
+        conditions.push("timestamp >= ?");
+        params.push(Box::new(since.to_rfc3339()));
+    }
+    if let Some(until) = filter.until {
+        conditions.push("timestamp < ?");
+        params.push(Box::new(until.to_rfc3339()));
+    }
+    let where_clause = if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    };
+
+    let total = conn.query_row(
+        &format!("SELECT COUNT(*) FROM requests {}", where_clause),
+        rusqlite::params_from_iter(params.iter()),
+        |row| row.get::<_, usize>(0),
+    );
+    let total = match total {
+        Ok(total) => total,
+        Err(e) => {
+            tracing::warn!("failed to count requests: {}", e);
+            return (0, vec![]);
+        }
+    };
+
+    params.push(Box::new(filter.limit));
+    params.push(Box::new(filter.offset));
+    let mut stmt = match conn.prepare(&format!(
+            "SELECT timestamp, mirror_id, path, success, cache_hit, elapsed_time, request_id, upstream_status, response_bytes, content_type, error_kind, item_count, layout_drift FROM requests {} ORDER BY timestamp DESC LIMIT ? OFFSET ?",
+            where_clause
+        )) {
+            Ok(stmt) => stmt,
             Err(e) => {
-                tracing::warn!("failed to query requests: {}", e);
-                return vec![];
+                tracing::warn!("failed to prepare statement: {}", e);
+                return (total, vec![]);
             }
         };
+    let rows = stmt.query_map(rusqlite::params_from_iter(params.iter()), |row| {
+        Ok(RequestLogEntry {
+            timestamp: row.get(0)?,
+            mirror_id: row.get(1)?,
+            path: row.get(2)?,
+            success: row.get(3)?,
+            cache_hit: row.get(4)?,
+            elapsed_time: row.get(5)?,
+            request_id: row.get(6)?,
+            upstream_status: row.get(7)?,
+            response_bytes: row.get(8)?,
+            content_type: row.get(9)?,
+            error_kind: row
+                .get::<_, Option<String>>(10)?
+                .and_then(|kind| kind.parse().ok()),
+            item_count: row.get(11)?,
+            layout_drift: row
+                .get::<_, Option<String>>(12)?
+                .and_then(|drift| serde_json::from_str(&drift).ok()),
+        })
+    });
+    let rows = match rows {
+        Ok(rows) => rows,
+        Err(e) => {
+            tracing::warn!("failed to query requests: {}", e);
+            return (total, vec![]);
+        }
+    };
 
-        match rows.collect::<Result<Vec<_>, _>>() {
-            Ok(requests) => requests,
-            Err(e) => {
-                tracing::warn!("failed to collect requests: {}", e);
-                vec![]
+    match rows.collect::<Result<Vec<_>, _>>() {
+        Ok(requests) => (total, requests),
+        Err(e) => {
+            tracing::warn!("failed to collect requests: {}", e);
+            (total, vec![])
+        }
+    }
+}
+
+/// Schema migrations of the request tracker database.
+const MIGRATIONS: &[&str] = &[
+    "CREATE TABLE IF NOT EXISTS requests (
+        id INTEGER PRIMARY KEY,
+        mirror_id TEXT NOT NULL,
+        timestamp TEXT NOT NULL,
+        path TEXT NOT NULL,
+        success INTEGER NOT NULL,
+        cache_hit INTEGER NOT NULL,
+        elapsed_time REAL NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS requests_mirror_timestamp
+        ON requests (mirror_id, timestamp);
+    CREATE TABLE IF NOT EXISTS request_aggregates (
+        mirror_id TEXT NOT NULL,
+        hour TEXT NOT NULL,
+        count INTEGER NOT NULL,
+        success_count INTEGER NOT NULL,
+        cache_hit_count INTEGER NOT NULL,
+        elapsed_sum REAL NOT NULL,
+        elapsed_max REAL NOT NULL,
+        latency_histogram TEXT NOT NULL,
+        last_success TEXT,
+        last_failure TEXT,
+        PRIMARY KEY (mirror_id, hour)
+    );",
+    "ALTER TABLE requests ADD COLUMN request_id TEXT;
+    ALTER TABLE requests ADD COLUMN upstream_status INTEGER;
+    ALTER TABLE requests ADD COLUMN response_bytes INTEGER;
+    ALTER TABLE requests ADD COLUMN content_type TEXT;
+    ALTER TABLE requests ADD COLUMN error_kind TEXT;
+    ALTER TABLE requests ADD COLUMN item_count INTEGER;
+    CREATE INDEX IF NOT EXISTS requests_request_id ON requests (request_id);",
+    "CREATE TABLE IF NOT EXISTS probes (
+        id INTEGER PRIMARY KEY,
+        mirror_id TEXT NOT NULL,
+        timestamp TEXT NOT NULL,
+        path TEXT NOT NULL,
+        success INTEGER NOT NULL,
+        elapsed_time REAL NOT NULL,
+        request_id TEXT,
+        upstream_status INTEGER,
+        response_bytes INTEGER,
+        error_kind TEXT,
+        item_count INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS probes_mirror_timestamp ON probes (mirror_id, timestamp);",
+    "ALTER TABLE requests ADD COLUMN layout_drift TEXT;
+    ALTER TABLE probes ADD COLUMN layout_drift TEXT;
+    ALTER TABLE request_aggregates ADD COLUMN layout_drift_count INTEGER NOT NULL DEFAULT 0;",
+];
+
+fn insert_requests(
+    conn: &mut rusqlite::Connection,
+    records: &[RequestRecord],
+) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    {
+        let mut request_stmt = tx.prepare_cached(
+            "INSERT INTO requests (mirror_id, timestamp, path, success, cache_hit, elapsed_time, request_id, upstream_status, response_bytes, content_type, error_kind, item_count, layout_drift) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )?;
+        let mut probe_stmt = tx.prepare_cached(
+            "INSERT INTO probes (mirror_id, timestamp, path, success, elapsed_time, request_id, upstream_status, response_bytes, error_kind, item_count, layout_drift) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )?;
+        for record in records {
+            let outcome = &record.outcome;
+            let layout_drift = outcome
+                .layout_drift
+                .as_ref()
+                .and_then(|drift| serde_json::to_string(drift).ok());
+            if record.probe {
+                probe_stmt.execute(rusqlite::params![
+                    record.mirror_id,
+                    record.timestamp.to_rfc3339(),
+                    record.path,
+                    outcome.success as i32,
+                    outcome.elapsed_time,
+                    record.request_id,
+                    outcome.upstream_status,
+                    outcome.response_bytes,
+                    outcome.error_kind.map(|kind| kind.as_str()),
+                    outcome.item_count,
+                    layout_drift,
+                ])?;
+                continue;
             }
+            request_stmt.execute(rusqlite::params![
+                record.mirror_id,
+                record.timestamp.to_rfc3339(),
+                record.path,
+                outcome.success as i32,
+                record.cache_hit as i32,
+                outcome.elapsed_time,
+                record.request_id,
+                outcome.upstream_status,
+                outcome.response_bytes,
+                outcome.content_type,
+                outcome.error_kind.map(|kind| kind.as_str()),
+                outcome.item_count,
+                layout_drift,
+            ])?;
+        }
+    }
+    tx.commit()
+}
+
+/// Hourly roll-up of raw request rows for a single mirror.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HourlyAggregate {
+    pub count: u64,
+    pub success_count: u64,
+    pub cache_hit_count: u64,
+    pub elapsed_sum: f64,
+    pub elapsed_max: f64,
+    pub latency_histogram: Vec<u64>,
+    pub last_success: Option<DateTime<Utc>>,
+    pub last_failure: Option<DateTime<Utc>>,
+    pub layout_drift_count: u64,
+}
+
+impl Default for HourlyAggregate {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            success_count: 0,
+            cache_hit_count: 0,
+            elapsed_sum: 0.0,
+            elapsed_max: 0.0,
+            latency_histogram: vec![0; LATENCY_BUCKETS.len() + 1],
+            last_success: None,
+            last_failure: None,
+            layout_drift_count: 0,
         }
     }
 }
+
+impl HourlyAggregate {
+    /// Adds a single request. Cache hits only count towards `count`,
+    /// `success_count` and `cache_hit_count`; latency and last success or
+    /// failure only reflect upstream requests.
+    fn add(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        success: bool,
+        cache_hit: bool,
+        elapsed_time: f64,
+        layout_drift: bool,
+    ) {
+        self.count += 1;
+        if success {
+            self.success_count += 1;
+        }
+        if cache_hit {
+            self.cache_hit_count += 1;
+            return;
+        }
+        if success {
+            self.last_success = self.last_success.max(Some(timestamp));
+        } else {
+            self.last_failure = self.last_failure.max(Some(timestamp));
+        }
+        if layout_drift {
+            self.layout_drift_count += 1;
+        }
+        self.elapsed_sum += elapsed_time;
+        self.elapsed_max = self.elapsed_max.max(elapsed_time);
+        self.latency_histogram[latency_bucket(elapsed_time)] += 1;
+    }
+
+    fn merge(&mut self, other: &HourlyAggregate) {
+        self.count += other.count;
+        self.success_count += other.success_count;
+        self.cache_hit_count += other.cache_hit_count;
+        self.elapsed_sum += other.elapsed_sum;
+        self.elapsed_max = self.elapsed_max.max(other.elapsed_max);
+        for (bucket, count) in self
+            .latency_histogram
+            .iter_mut()
+            .zip(other.latency_histogram.iter())
+        {
+            *bucket += count;
+        }
+        self.last_success = self.last_success.max(other.last_success);
+        self.last_failure = self.last_failure.max(other.last_failure);
+        self.layout_drift_count += other.layout_drift_count;
+    }
+}
+
+/// Moves all raw rows from complete hours before `cutoff` into
+/// `request_aggregates`. Returns the number of raw rows rolled up.
+fn rollup_requests(
+    conn: &mut rusqlite::Connection,
+    cutoff: DateTime<Utc>,
+) -> rusqlite::Result<usize> {
+    let cutoff = cutoff
+        .duration_trunc(chrono::Duration::hours(1))
+        .unwrap_or(cutoff)
+        .to_rfc3339();
+
+    let tx = conn.transaction()?;
+    let mut aggregates: HashMap<(String, String), HourlyAggregate> = HashMap::new();
+    {
+        let mut stmt = tx.prepare(
+            "SELECT mirror_id, timestamp, success, cache_hit, elapsed_time, layout_drift IS NOT NULL FROM requests WHERE timestamp < ?",
+        )?;
+        let mut rows = stmt.query([&cutoff])?;
+        while let Some(row) = rows.next()? {
+            let mirror_id = row.get::<_, String>(0)?;
+            let timestamp = row.get::<_, DateTime<Utc>>(1)?;
+            let hour = timestamp
+                .duration_trunc(chrono::Duration::hours(1))
+                .unwrap_or(timestamp)
+                .to_rfc3339();
+            aggregates.entry((mirror_id, hour)).or_default().add(
+                timestamp,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+            );
+        }
+    }
+
+    let count = aggregates.values().map(|a| a.count as usize).sum();
+    for ((mirror_id, hour), aggregate) in aggregates {
+        let mut merged = tx
+            .query_row(
+                "SELECT count, success_count, cache_hit_count, elapsed_sum, elapsed_max, latency_histogram, last_success, last_failure, layout_drift_count FROM request_aggregates WHERE mirror_id = ? AND hour = ?",
+                [&mirror_id, &hour],
+                read_aggregate,
+            )
+            .optional()?
+            .unwrap_or_default();
+        merged.merge(&aggregate);
+
+        tx.execute(
+            "INSERT OR REPLACE INTO request_aggregates (mirror_id, hour, count, success_count, cache_hit_count, elapsed_sum, elapsed_max, latency_histogram, last_success, last_failure, layout_drift_count) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            rusqlite::params![
+                mirror_id,
+                hour,
+                merged.count,
+                merged.success_count,
+                merged.cache_hit_count,
+                merged.elapsed_sum,
+                merged.elapsed_max,
+                serde_json::to_string(&merged.latency_histogram).unwrap_or_default(),
+                merged.last_success.map(|t| t.to_rfc3339()),
+                merged.last_failure.map(|t| t.to_rfc3339()),
+                merged.layout_drift_count,
+            ],
+        )?;
+    }
+
+    tx.execute("DELETE FROM requests WHERE timestamp < ?", [&cutoff])?;
+    tx.commit()?;
+    Ok(count)
+}
+
+fn prune(conn: &rusqlite::Connection, cutoff: DateTime<Utc>) -> rusqlite::Result<usize> {
+    let cutoff = cutoff.to_rfc3339();
+    let aggregates = conn.execute("DELETE FROM request_aggregates WHERE hour < ?", [&cutoff])?;
+    let probes = conn.execute("DELETE FROM probes WHERE timestamp < ?", [&cutoff])?;
+    Ok(aggregates + probes)
+}
+
+fn read_aggregate(row: &rusqlite::Row) -> rusqlite::Result<HourlyAggregate> {
+    let histogram = row.get::<_, String>(5)?;
+    let mut latency_histogram: Vec<u64> = serde_json::from_str(&histogram).unwrap_or_default();
+    latency_histogram.resize(LATENCY_BUCKETS.len() + 1, 0);
+    Ok(HourlyAggregate {
+        count: row.get(0)?,
+        success_count: row.get(1)?,
+        cache_hit_count: row.get(2)?,
+        elapsed_sum: row.get(3)?,
+        elapsed_max: row.get(4)?,
+        latency_histogram,
+        last_success: row.get(6)?,
+        last_failure: row.get(7)?,
+        layout_drift_count: row.get(8)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn record(minute: u32, success: bool, elapsed_time: f64) -> RequestRecord {
+        RequestRecord {
+            mirror_id: "nyaa".into(),
+            timestamp: Utc.with_ymd_and_hms(2025, 3, 29, 9, minute, 0).unwrap(),
+            path: "https://nyaa.si/".into(),
+            cache_hit: false,
+            probe: false,
+            request_id: None,
+            outcome: RequestOutcome {
+                success,
+                elapsed_time,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn test_rollup() {
+        let mut conn = crate::db::open_in_memory(MIGRATIONS);
+
+        insert_requests(
+            &mut conn,
+            &[
+                record(1, true, 0.2),
+                record(2, false, 3.0),
+                record(3, true, 0.4),
+            ],
+        )
+        .unwrap();
+        // Rows of the current (incomplete) hour stay raw.
+        let cutoff = Utc.with_ymd_and_hms(2025, 3, 29, 9, 30, 0).unwrap();
+        assert_eq!(rollup_requests(&mut conn, cutoff).unwrap(), 0);
+
+        let cutoff = Utc.with_ymd_and_hms(2025, 3, 29, 10, 30, 0).unwrap();
+        assert_eq!(rollup_requests(&mut conn, cutoff).unwrap(), 3);
+
+        insert_requests(&mut conn, &[record(4, true, 0.01)]).unwrap();
+        assert_eq!(rollup_requests(&mut conn, cutoff).unwrap(), 1);
+
+        let remaining: usize = conn
+            .query_row("SELECT COUNT(*) FROM requests", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(remaining, 0);
+
+        let aggregate = conn
+            .query_row(
+                "SELECT count, success_count, cache_hit_count, elapsed_sum, elapsed_max, latency_histogram, last_success, last_failure, layout_drift_count FROM request_aggregates WHERE mirror_id = 'nyaa'",
+                [],
+                read_aggregate,
+            )
+            .unwrap();
+        assert_eq!(aggregate.count, 4);
+        assert_eq!(aggregate.success_count, 3);
+        assert_eq!(aggregate.elapsed_max, 3.0);
+        assert_eq!(aggregate.latency_histogram.iter().sum::<u64>(), 4);
+        assert_eq!(aggregate.latency_histogram[0], 1);
+        assert_eq!(
+            aggregate.last_success,
+            Some(Utc.with_ymd_and_hms(2025, 3, 29, 9, 4, 0).unwrap())
+        );
+        assert_eq!(
+            aggregate.last_failure,
+            Some(Utc.with_ymd_and_hms(2025, 3, 29, 9, 2, 0).unwrap())
+        );
+
+        let cutoff = Utc.with_ymd_and_hms(2025, 3, 30, 0, 0, 0).unwrap();
+        assert_eq!(prune(&conn, cutoff).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_summary() {
+        let mut conn = crate::db::open_in_memory(MIGRATIONS);
+
+        let mut cached = record(5, true, 0.0);
+        cached.cache_hit = true;
+        insert_requests(
+            &mut conn,
+            &[
+                record(1, true, 0.2),
+                record(2, false, 3.0),
+                record(3, true, 0.4),
+                cached,
+            ],
+        )
+        .unwrap();
+
+        let since = Utc.with_ymd_and_hms(2025, 3, 29, 9, 0, 0).unwrap();
+        let summary = summarize(&conn, "nyaa", since).unwrap();
+        assert_eq!(summary.count, 4);
+        assert_eq!(summary.cache_hit_count, 1);
+        assert_eq!(summary.upstream_count, 3);
+        assert_eq!(summary.upstream_success_count, 2);
+        assert_eq!(summary.latency_p50, Some(0.4));
+        assert_eq!(summary.latency_p99, Some(3.0));
+        assert_eq!(
+            summary.last_success,
+            Some(Utc.with_ymd_and_hms(2025, 3, 29, 9, 3, 0).unwrap())
+        );
+
+        // Once rolled up, percentiles are estimated from the histogram.
+        let cutoff = Utc.with_ymd_and_hms(2025, 3, 29, 10, 30, 0).unwrap();
+        rollup_requests(&mut conn, cutoff).unwrap();
+        let rolled_up = summarize(&conn, "nyaa", since).unwrap();
+        assert_eq!(rolled_up.count, summary.count);
+        assert_eq!(rolled_up.upstream_success_count, 2);
+        assert_eq!(rolled_up.latency_p50, Some(0.5));
+        assert_eq!(rolled_up.latency_p99, Some(3.0));
+        assert_eq!(rolled_up.last_failure, summary.last_failure);
+
+        // An hour starting before the window is left out once rolled up,
+        // while raw rows inside it keep exact percentiles.
+        let mut later = record(10, true, 0.3);
+        later.timestamp += chrono::Duration::hours(1);
+        insert_requests(&mut conn, &[later]).unwrap();
+        let since = Utc.with_ymd_and_hms(2025, 3, 29, 9, 2, 30).unwrap();
+        let partial = summarize(&conn, "nyaa", since).unwrap();
+        assert_eq!(partial.count, 1);
+        assert_eq!(partial.latency_p50, Some(0.3));
+        assert_eq!(partial.last_failure, None);
+    }
+}
@@ -0,0 +1,373 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use rusqlite::OptionalExtension;
+
+use crate::{
+    MirrorExt,
+    api::mirror::list::ListRequest,
+    notification::{Event, MatchedItem, Notifier},
+};
+
+/// How often due searches are looked for.
+const TICK: Duration = Duration::from_secs(30);
+/// Check interval of a saved search when none is given.
+pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(15 * 60);
+pub const MIN_INTERVAL: Duration = Duration::from_secs(60);
+/// Share of the rate limiter budget saved searches may use when not
+/// configured.
+pub const DEFAULT_BUDGET_SHARE: f64 = 0.25;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct SavedSearch {
+    pub id: i64,
+    pub mirror_id: String,
+    pub name: String,
+    pub query: ListRequest,
+    #[serde(with = "humantime_serde")]
+    pub interval: Duration,
+    pub created_at: DateTime<Utc>,
+    pub last_checked_at: Option<DateTime<Utc>>,
+    /// Error of the last check, if it failed.
+    pub last_error: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewSavedSearch {
+    pub mirror_id: String,
+    pub name: String,
+    pub query: ListRequest,
+    pub interval: Duration,
+}
+
+/// Saved searches and the torrent ids each has already reported.
+#[derive(Debug, Clone)]
+pub struct SavedSearches {
+    conn: Arc<Mutex<rusqlite::Connection>>,
+}
+
+impl SavedSearches {
+    pub fn open(db_path: &Path) -> rusqlite::Result<Self> {
+        let conn = crate::db::open(db_path, MIGRATIONS, "saved search")?;
+        Ok(Self {
+            conn: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    #[cfg(test)]
+    fn open_in_memory() -> Self {
+        Self {
+            conn: Arc::new(Mutex::new(crate::db::open_in_memory(MIGRATIONS))),
+        }
+    }
+
+    pub async fn create(&self, search: NewSavedSearch) -> rusqlite::Result<SavedSearch> {
+        crate::db::blocking(&self.conn, move |conn| {
+            let now = Utc::now();
+            conn.execute(
+                "INSERT INTO saved_searches (mirror_id, name, query, interval_secs, created_at) VALUES (?, ?, ?, ?, ?)",
+                rusqlite::params![
+                    search.mirror_id,
+                    search.name,
+                    serde_json::to_string(&search.query).expect("query is serializable"),
+                    search.interval.as_secs(),
+                    now,
+                ],
+            )?;
+            Ok(SavedSearch {
+                id: conn.last_insert_rowid(),
+                mirror_id: search.mirror_id,
+                name: search.name,
+                query: search.query,
+                interval: search.interval,
+                created_at: now,
+                last_checked_at: None,
+                last_error: None,
+            })
+        })
+        .await
+    }
+
+    pub async fn list(&self) -> rusqlite::Result<Vec<SavedSearch>> {
+        crate::db::blocking(&self.conn, |conn| {
+            let mut stmt = conn.prepare_cached(
+                "SELECT id, mirror_id, name, query, interval_secs, created_at, last_checked_at, last_error
+                FROM saved_searches ORDER BY id",
+            )?;
+            stmt.query_map([], read_search)?.collect()
+        })
+        .await
+    }
+
+    pub async fn get(&self, id: i64) -> rusqlite::Result<Option<SavedSearch>> {
+        crate::db::blocking(&self.conn, move |conn| {
+            conn.query_row(
+                "SELECT id, mirror_id, name, query, interval_secs, created_at, last_checked_at, last_error
+                FROM saved_searches WHERE id = ?",
+                [id],
+                read_search,
+            )
+            .optional()
+        })
+        .await
+    }
+
+    /// Deletes a saved search and its seen ids. Returns whether it existed.
+    pub async fn delete(&self, id: i64) -> rusqlite::Result<bool> {
+        crate::db::blocking(&self.conn, move |conn| {
+            Ok(conn.execute("DELETE FROM saved_searches WHERE id = ?", [id])? > 0)
+        })
+        .await
+    }
+
+    /// Searches never checked, or last checked at least one interval
+    /// before `now`.
+    async fn due(&self, now: DateTime<Utc>) -> rusqlite::Result<Vec<SavedSearch>> {
+        Ok(self
+            .list()
+            .await?
+            .into_iter()
+            .filter(|search| {
+                search.last_checked_at.is_none_or(|checked| {
+                    now - checked
+                        >= chrono::Duration::from_std(search.interval)
+                            .unwrap_or(chrono::Duration::MAX)
+                })
+            })
+            .collect())
+    }
+
+    /// Records `ids` as seen by `search_id` and returns those that were not
+    /// seen before, in the given order.
+    async fn mark_seen(
+        &self,
+        search_id: i64,
+        ids: Vec<usize>,
+        now: DateTime<Utc>,
+    ) -> rusqlite::Result<Vec<usize>> {
+        crate::db::blocking(&self.conn, move |conn| {
+            let tx = conn.transaction()?;
+            let mut new = Vec::new();
+            {
+                let mut stmt = tx.prepare_cached(
+                    "INSERT OR IGNORE INTO saved_search_seen (search_id, torrent_id, seen_at) VALUES (?, ?, ?)",
+                )?;
+                for id in ids {
+                    if stmt.execute(rusqlite::params![search_id, id, now])? > 0 {
+                        new.push(id);
+                    }
+                }
+            }
+            tx.commit()?;
+            Ok(new)
+        })
+        .await
+    }
+
+    async fn record_check(
+        &self,
+        search_id: i64,
+        now: DateTime<Utc>,
+        error: Option<String>,
+    ) -> rusqlite::Result<()> {
+        crate::db::blocking(&self.conn, move |conn| {
+            conn.execute(
+                "UPDATE saved_searches SET last_checked_at = ?, last_error = ? WHERE id = ?",
+                rusqlite::params![now, error, search_id],
+            )?;
+            Ok(())
+        })
+        .await
+    }
+}
+
+fn read_search(row: &rusqlite::Row) -> rusqlite::Result<SavedSearch> {
+    let query: String = row.get(3)?;
+    let query = serde_json::from_str(&query).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e))
+    })?;
+    Ok(SavedSearch {
+        id: row.get(0)?,
+        mirror_id: row.get(1)?,
+        name: row.get(2)?,
+        query,
+        interval: Duration::from_secs(row.get(4)?),
+        created_at: row.get(5)?,
+        last_checked_at: row.get(6)?,
+        last_error: row.get(7)?,
+    })
+}
+
+/// Schema migrations of the saved search database.
+const MIGRATIONS: &[&str] = &["CREATE TABLE IF NOT EXISTS saved_searches (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        mirror_id TEXT NOT NULL,
+        name TEXT NOT NULL,
+        query TEXT NOT NULL,
+        interval_secs INTEGER NOT NULL,
+        created_at TEXT NOT NULL,
+        last_checked_at TEXT,
+        last_error TEXT
+    );
+    CREATE TABLE IF NOT EXISTS saved_search_seen (
+        search_id INTEGER NOT NULL REFERENCES saved_searches (id) ON DELETE CASCADE,
+        torrent_id INTEGER NOT NULL,
+        seen_at TEXT NOT NULL,
+        PRIMARY KEY (search_id, torrent_id)
+    );"];
+
+/// Spawns a background task that checks due saved searches and notifies
+/// about torrents they have not reported before. The first check of a
+/// search only records what is already listed.
+pub fn spawn(mext: MirrorExt, searches: SavedSearches, notifier: Notifier) {
+    let store = searches.clone();
+    crate::scheduler::spawn_due(
+        "saved-search",
+        TICK,
+        move |now| {
+            let searches = store.clone();
+            async move { searches.due(now).await }
+        },
+        move |search| {
+            let mext = mext.clone();
+            let searches = searches.clone();
+            let notifier = notifier.clone();
+            async move { check(&mext, &searches, &notifier, &search).await }
+        },
+    );
+}
+
+async fn check(
+    mext: &MirrorExt,
+    searches: &SavedSearches,
+    notifier: &Notifier,
+    search: &SavedSearch,
+) {
+    let now = Utc::now();
+    let result = match mext.find_by_id(&search.mirror_id) {
+        Some(mirror) => {
+            let budget_share = mirror
+                .config
+                .saved_search_budget_share
+                .unwrap_or(DEFAULT_BUDGET_SHARE);
+            let client = mirror.client.lock().await;
+            if !client.has_budget(budget_share).await {
+                tracing::debug!(
+                    "postponed saved search {}: rate limiter budget in use",
+                    search.id
+                );
+                return;
+            }
+            run(&client, &search.query).await
+        }
+        None => Err(anyhow::anyhow!("mirror {} not found", search.mirror_id)),
+    };
+
+    let error = match result {
+        Ok(items) => {
+            let ids = items.iter().map(|item| item.id).collect::<Vec<_>>();
+            match searches.mark_seen(search.id, ids, now).await {
+                Ok(new) if search.last_checked_at.is_some() && !new.is_empty() => {
+                    tracing::info!("saved search {} has {} new matches", search.id, new.len());
+                    let items = items
+                        .iter()
+                        .filter(|item| new.contains(&item.id))
+                        .map(MatchedItem::from)
+                        .collect();
+                    notifier.notify(Event::SavedSearchMatch {
+                        mirror_id: search.mirror_id.clone(),
+                        search_id: search.id,
+                        search_name: search.name.clone(),
+                        items,
+                    });
+                    None
+                }
+                Ok(_) => None,
+                Err(e) => Some(format!("failed to record seen torrents: {}", e)),
+            }
+        }
+        Err(e) => Some(format!("{:#}", e)),
+    };
+    if let Some(error) = &error {
+        tracing::warn!("saved search {} failed: {}", search.id, error);
+    }
+    if let Err(e) = searches.record_check(search.id, now, error).await {
+        tracing::warn!(
+            "failed to record check of saved search {}: {}",
+            search.id,
+            e
+        );
+    }
+}
+
+/// Runs `query` the way the list endpoint does, applying any predicates.
+pub async fn run(
+    client: &crate::client::Client,
+    query: &ListRequest,
+) -> anyhow::Result<Vec<nyaa_parser::ListItem>> {
+    let expression = nyaa_parser::dsl::parse(query.query.as_deref().unwrap_or_default())?;
+    let query = query.clone().into_query().term(expression.text.clone());
+    if expression.has_predicates() {
+        Ok(client.list_filtered(&query, &expression).await?.items)
+    } else {
+        client.list(&query).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_mark_seen() {
+        let searches = SavedSearches::open_in_memory();
+        let search = searches
+            .create(NewSavedSearch {
+                mirror_id: "nyaa".into(),
+                name: "cube".into(),
+                query: serde_json::from_str(r#"{"q": "super cube seeders:>10"}"#).unwrap(),
+                interval: DEFAULT_INTERVAL,
+            })
+            .await
+            .unwrap();
+        let now = Utc::now();
+        assert_eq!(searches.due(now).await.unwrap().len(), 1);
+
+        assert_eq!(
+            searches
+                .mark_seen(search.id, vec![3, 2, 1], now)
+                .await
+                .unwrap(),
+            vec![3, 2, 1]
+        );
+        assert_eq!(
+            searches
+                .mark_seen(search.id, vec![4, 3, 2], now)
+                .await
+                .unwrap(),
+            vec![4]
+        );
+        searches.record_check(search.id, now, None).await.unwrap();
+        assert!(searches.due(now).await.unwrap().is_empty());
+        assert_eq!(
+            searches
+                .due(now + chrono::Duration::minutes(15))
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let stored = searches.get(search.id).await.unwrap().unwrap();
+        assert_eq!(
+            stored.query.query.as_deref(),
+            Some("super cube seeders:>10")
+        );
+        assert!(searches.delete(search.id).await.unwrap());
+        assert!(searches.get(search.id).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,38 @@
+//! Background jobs that run when they fall due.
+
+use std::{future::Future, time::Duration};
+
+use chrono::{DateTime, Utc};
+
+/// Spawns a background task that every `tick` loads the jobs `due` at that
+/// time and runs them one after another. Each run gets its own request ID
+/// starting with `name`, so the requests it makes upstream can be told apart.
+pub fn spawn_due<J, D, DF, R, RF>(name: &'static str, tick: Duration, due: D, run: R)
+where
+    J: Send + 'static,
+    D: Fn(DateTime<Utc>) -> DF + Send + 'static,
+    DF: Future<Output = rusqlite::Result<Vec<J>>> + Send,
+    R: Fn(J) -> RF + Send + 'static,
+    RF: Future<Output = ()> + Send,
+{
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(tick);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+
+            let jobs = match due(Utc::now()).await {
+                Ok(jobs) => jobs,
+                Err(e) => {
+                    tracing::warn!("failed to load due {} jobs: {}", name, e);
+                    continue;
+                }
+            };
+
+            for job in jobs {
+                let id = format!("{}-{}", name, uuid::Uuid::new_v4());
+                crate::request_id::scope(id, run(job)).await;
+            }
+        }
+    });
+}
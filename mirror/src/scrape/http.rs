@@ -0,0 +1,233 @@
+//! HTTP tracker scrapes, answered with a bencoded dictionary of stats per
+//! info hash.
+
+use std::collections::HashMap;
+
+use anyhow::Context;
+use chrono::Utc;
+use reqwest::Url;
+
+use super::ScrapeStats;
+
+/// Scrape URL of an announce URL: by convention, `announce` at the start of
+/// the last path segment becomes `scrape`. Trackers not following it do not
+/// support scraping.
+fn scrape_url(announce: &Url) -> Option<Url> {
+    let (dir, last) = announce.path().rsplit_once('/')?;
+    let rest = last.strip_prefix("announce")?;
+    let mut url = announce.clone();
+    url.set_path(&format!("{}/scrape{}", dir, rest));
+    Some(url)
+}
+
+/// Percent-encodes raw bytes, leaving unreserved characters alone.
+fn percent_encode(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+pub async fn scrape(
+    client: &reqwest::Client,
+    tracker: &Url,
+    hashes: &[&str],
+) -> anyhow::Result<HashMap<String, ScrapeStats>> {
+    let url = scrape_url(tracker).context("tracker does not support scraping")?;
+    // Built by hand, since info hashes are raw bytes rather than text.
+    let mut url = url.to_string();
+    for (i, hash) in hashes.iter().enumerate() {
+        let separator = if i == 0 && !url.contains('?') {
+            '?'
+        } else {
+            '&'
+        };
+        url.push(separator);
+        url.push_str("info_hash=");
+        url.push_str(&percent_encode(&hex::decode(hash)?));
+    }
+    let body = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+
+    let response = bencode::decode(&body)?;
+    if let Some(reason) = response.get(b"failure reason").and_then(|v| v.as_bytes()) {
+        anyhow::bail!("tracker failure: {}", String::from_utf8_lossy(reason));
+    }
+    let files = response
+        .get(b"files")
+        .and_then(|files| files.as_dict())
+        .context("scrape response has no files")?;
+    let now = Utc::now();
+    let count = |stats: &bencode::Value, key: &[u8]| {
+        stats
+            .get(key)
+            .and_then(|value| value.as_int())
+            .unwrap_or(0)
+            .max(0) as usize
+    };
+    Ok(files
+        .iter()
+        .filter(|(hash, _)| hash.len() == 20)
+        .map(|(hash, stats)| {
+            (
+                hex::encode(hash),
+                ScrapeStats {
+                    seeders: count(stats, b"complete"),
+                    leechers: count(stats, b"incomplete"),
+                    downloads: count(stats, b"downloaded"),
+                    scraped_at: now,
+                },
+            )
+        })
+        .collect())
+}
+
+/// Just enough of bencode to read scrape responses.
+mod bencode {
+    #[derive(Debug, PartialEq)]
+    pub enum Value<'a> {
+        Int(i64),
+        Bytes(&'a [u8]),
+        List(Vec<Value<'a>>),
+        Dict(Vec<(&'a [u8], Value<'a>)>),
+    }
+
+    impl<'a> Value<'a> {
+        pub fn get(&self, key: &[u8]) -> Option<&Value<'a>> {
+            self.as_dict()?
+                .iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, value)| value)
+        }
+
+        pub fn as_dict(&self) -> Option<&[(&'a [u8], Value<'a>)]> {
+            match self {
+                Value::Dict(entries) => Some(entries),
+                _ => None,
+            }
+        }
+
+        pub fn as_bytes(&self) -> Option<&'a [u8]> {
+            match self {
+                Value::Bytes(bytes) => Some(bytes),
+                _ => None,
+            }
+        }
+
+        pub fn as_int(&self) -> Option<i64> {
+            match self {
+                Value::Int(value) => Some(*value),
+                _ => None,
+            }
+        }
+    }
+
+    pub fn decode(input: &[u8]) -> anyhow::Result<Value<'_>> {
+        let (value, rest) = parse(input)?;
+        anyhow::ensure!(rest.is_empty(), "trailing data after bencoded value");
+        Ok(value)
+    }
+
+    fn parse(input: &[u8]) -> anyhow::Result<(Value<'_>, &[u8])> {
+        match input.first() {
+            Some(b'i') => {
+                let end = input
+                    .iter()
+                    .position(|&b| b == b'e')
+                    .ok_or_else(|| anyhow::anyhow!("unterminated integer"))?;
+                let value = std::str::from_utf8(&input[1..end])?.parse()?;
+                Ok((Value::Int(value), &input[end + 1..]))
+            }
+            Some(b'l') => {
+                let mut rest = &input[1..];
+                let mut items = Vec::new();
+                while rest.first() != Some(&b'e') {
+                    let (item, next) = parse(rest)?;
+                    items.push(item);
+                    rest = next;
+                }
+                Ok((Value::List(items), &rest[1..]))
+            }
+            Some(b'd') => {
+                let mut rest = &input[1..];
+                let mut entries = Vec::new();
+                while rest.first() != Some(&b'e') {
+                    let (key, next) = parse_bytes(rest)?;
+                    let (value, next) = parse(next)?;
+                    entries.push((key, value));
+                    rest = next;
+                }
+                Ok((Value::Dict(entries), &rest[1..]))
+            }
+            Some(b'0'..=b'9') => {
+                let (bytes, rest) = parse_bytes(input)?;
+                Ok((Value::Bytes(bytes), rest))
+            }
+            _ => anyhow::bail!("invalid bencoded value"),
+        }
+    }
+
+    fn parse_bytes(input: &[u8]) -> anyhow::Result<(&[u8], &[u8])> {
+        let colon = input
+            .iter()
+            .position(|&b| b == b':')
+            .ok_or_else(|| anyhow::anyhow!("invalid byte string"))?;
+        let len: usize = std::str::from_utf8(&input[..colon])?.parse()?;
+        let rest = &input[colon + 1..];
+        anyhow::ensure!(rest.len() >= len, "truncated byte string");
+        Ok(rest.split_at(len))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::{Router, extract::RawQuery, routing::get};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scrape() {
+        let router = Router::new().route(
+            "/scrape.php",
+            get(|RawQuery(query): RawQuery| async move {
+                assert_eq!(
+                    query.as_deref(),
+                    Some("passkey=x&info_hash=%AA%AA%AA%AA%AA%AA%AA%AA%AA%AA%AA%AA%AA%AA%AA%AA%AA%AA%AA%AA&info_hash=0123456789abcdefghij")
+                );
+                let mut body = b"d5:filesd20:".to_vec();
+                body.extend([0xaa; 20]);
+                body.extend(b"d8:completei5e10:downloadedi50e10:incompletei2e4:name3:fooe20:0123456789abcdefghijd8:completei0eeee");
+                body
+            }),
+        );
+        let base = crate::download_client::stub_server(router).await;
+        let tracker = Url::parse(&format!("{}/announce.php?passkey=x", base)).unwrap();
+        let stats = scrape(
+            &reqwest::Client::new(),
+            &tracker,
+            &[&"aa".repeat(20), &hex::encode("0123456789abcdefghij")],
+        )
+        .await
+        .unwrap();
+        let aa = &stats[&"aa".repeat(20)];
+        assert_eq!((aa.seeders, aa.leechers, aa.downloads), (5, 2, 50));
+        assert_eq!(stats[&hex::encode("0123456789abcdefghij")].seeders, 0);
+
+        let unsupported = Url::parse(&format!("{}/tracker", base)).unwrap();
+        assert!(
+            scrape(&reqwest::Client::new(), &unsupported, &[])
+                .await
+                .is_err()
+        );
+    }
+}
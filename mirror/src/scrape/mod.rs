@@ -0,0 +1,294 @@
+//! Live swarm statistics scraped from BitTorrent trackers, over HTTP or the
+//! UDP tracker protocol (BEP 15), since the counts nyaa shows are often
+//! stale.
+
+mod http;
+mod udp;
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+
+/// Time allowed for a tracker to answer when none is configured.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long results are reused when not configured.
+pub const DEFAULT_CACHE_DURATION: Duration = Duration::from_secs(10 * 60);
+/// Most info hashes a tracker is asked about at once. That is what fits in
+/// a BEP 15 packet, and keeps HTTP scrape URLs reasonably short.
+const BATCH_SIZE: usize = 74;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct ScrapeStats {
+    pub seeders: usize,
+    pub leechers: usize,
+    pub downloads: usize,
+    pub scraped_at: DateTime<Utc>,
+}
+
+/// A torrent to scrape: its lowercase hex info hash and the trackers to ask.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    pub info_hash: String,
+    pub trackers: Vec<String>,
+}
+
+impl Target {
+    /// Takes the info hash and trackers from a magnet link, falling back to
+    /// `info_hash` and `default_trackers` for what it lacks.
+    pub fn new(
+        info_hash: Option<&str>,
+        magnet: Option<&str>,
+        default_trackers: &[String],
+    ) -> Option<Self> {
+        let mut target = Self {
+            info_hash: info_hash.unwrap_or_default().to_lowercase(),
+            trackers: Vec::new(),
+        };
+        if let Some(url) = magnet.and_then(|magnet| Url::parse(magnet).ok()) {
+            for (key, value) in url.query_pairs() {
+                match &*key {
+                    "xt" => {
+                        if let Some(hash) = value.strip_prefix("urn:btih:") {
+                            target.info_hash = hash.to_lowercase();
+                        }
+                    }
+                    "tr" => target.trackers.push(value.into_owned()),
+                    _ => {}
+                }
+            }
+        }
+        if target.trackers.is_empty() {
+            target.trackers = default_trackers.to_vec();
+        }
+        // Only hex info hashes can be scraped; nyaa never uses base32.
+        let valid = target.info_hash.len() == 40 && hex::decode(&target.info_hash).is_ok();
+        (valid && !target.trackers.is_empty()).then_some(target)
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    http: reqwest::Client,
+    timeout: Duration,
+    cache_duration: Duration,
+    /// Latest result per info hash, including torrents no tracker knew, so
+    /// they are not asked about again right away.
+    cache: Mutex<HashMap<String, (Instant, Option<ScrapeStats>)>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Scraper {
+    inner: Arc<Inner>,
+}
+
+impl Scraper {
+    pub fn new(timeout: Duration, cache_duration: Duration) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                http: reqwest::Client::builder()
+                    .timeout(timeout)
+                    .build()
+                    .expect("failed to build HTTP client"),
+                timeout,
+                cache_duration,
+                cache: Mutex::new(HashMap::new()),
+            }),
+        }
+    }
+
+    /// Live stats of `targets` by info hash, batched per tracker. When
+    /// several trackers answer, the one with the most seeders wins.
+    /// Torrents no tracker answered for are left out.
+    pub async fn scrape(&self, targets: &[Target]) -> HashMap<String, ScrapeStats> {
+        let now = Instant::now();
+        let mut found = HashMap::new();
+        let mut pending = Vec::new();
+        let mut by_tracker = HashMap::<&str, Vec<&str>>::new();
+        {
+            let cache = self.inner.cache.lock().unwrap();
+            for target in targets {
+                match cache.get(&target.info_hash) {
+                    Some((at, stats)) if now.duration_since(*at) < self.inner.cache_duration => {
+                        if let Some(stats) = stats {
+                            found.insert(target.info_hash.clone(), *stats);
+                        }
+                    }
+                    _ => {
+                        pending.push(target.info_hash.as_str());
+                        for tracker in &target.trackers {
+                            by_tracker
+                                .entry(tracker)
+                                .or_default()
+                                .push(&target.info_hash);
+                        }
+                    }
+                }
+            }
+        }
+
+        let requests = by_tracker.iter_mut().flat_map(|(tracker, hashes)| {
+            hashes.sort_unstable();
+            hashes.dedup();
+            hashes
+                .chunks(BATCH_SIZE)
+                .map(|chunk| self.scrape_tracker(tracker, chunk))
+        });
+        let mut scraped = HashMap::<String, ScrapeStats>::new();
+        for result in futures_util::future::join_all(requests).await {
+            for (info_hash, stats) in result {
+                scraped
+                    .entry(info_hash)
+                    .and_modify(|best| {
+                        if stats.seeders > best.seeders {
+                            *best = stats;
+                        }
+                    })
+                    .or_insert(stats);
+            }
+        }
+
+        let mut cache = self.inner.cache.lock().unwrap();
+        cache.retain(|_, (at, _)| now.duration_since(*at) < self.inner.cache_duration);
+        for info_hash in pending {
+            cache.insert(
+                info_hash.to_string(),
+                (now, scraped.get(info_hash).copied()),
+            );
+        }
+        found.extend(scraped);
+        found
+    }
+
+    async fn scrape_tracker(&self, tracker: &str, hashes: &[&str]) -> HashMap<String, ScrapeStats> {
+        let scrape = async {
+            let url = Url::parse(tracker)?;
+            match url.scheme() {
+                "http" | "https" => http::scrape(&self.inner.http, &url, hashes).await,
+                "udp" => udp::scrape(&url, hashes, self.inner.timeout).await,
+                scheme => Err(anyhow::anyhow!("unsupported tracker scheme: {}", scheme)),
+            }
+        };
+        match tokio::time::timeout(self.inner.timeout, scrape).await {
+            Ok(Ok(stats)) => stats,
+            Ok(Err(e)) => {
+                tracing::debug!("scrape of {} failed: {:#}", tracker, e);
+                HashMap::new()
+            }
+            Err(_) => {
+                tracing::debug!("scrape of {} timed out", tracker);
+                HashMap::new()
+            }
+        }
+    }
+
+    /// Scrapes the torrents of list `items`, returning what a tracker
+    /// answered for each, in order.
+    pub async fn scrape_list(
+        &self,
+        items: &[nyaa_parser::ListItem],
+        default_trackers: &[String],
+    ) -> Vec<Option<ScrapeStats>> {
+        let targets = items
+            .iter()
+            .map(|item| {
+                Target::new(
+                    item.btih().as_deref(),
+                    item.magnet_link.as_deref(),
+                    default_trackers,
+                )
+            })
+            .collect::<Vec<_>>();
+        let stats = self
+            .scrape(&targets.iter().flatten().cloned().collect::<Vec<_>>())
+            .await;
+        targets
+            .into_iter()
+            .map(|target| target.and_then(|target| stats.get(&target.info_hash).copied()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use axum::{Router, extract::RawQuery, routing::get};
+
+    use super::*;
+
+    #[test]
+    fn test_target() {
+        let hash = "84E064742FFE9F5EB4A739766A33D8631746310C";
+        let magnet = format!(
+            "magnet:?xt=urn:btih:{}&dn=Test&tr=http%3A%2F%2Fnyaa.tracker.wf%3A7777%2Fannounce&tr=udp%3A%2F%2Fopen.stealth.si%3A80%2Fannounce",
+            hash
+        );
+        let target = Target::new(None, Some(&magnet), &[]).unwrap();
+        assert_eq!(target.info_hash, hash.to_lowercase());
+        assert_eq!(
+            target.trackers,
+            [
+                "http://nyaa.tracker.wf:7777/announce",
+                "udp://open.stealth.si:80/announce"
+            ]
+        );
+
+        let defaults = vec!["udp://tracker:1337/announce".to_string()];
+        assert_eq!(
+            Target::new(Some(hash), None, &defaults).unwrap().trackers,
+            defaults
+        );
+        assert_eq!(Target::new(Some(hash), None, &[]), None);
+        assert_eq!(Target::new(Some("nope"), None, &defaults), None);
+    }
+
+    #[tokio::test]
+    async fn test_scrape() {
+        let known = "aa".repeat(20);
+        let unknown = "bb".repeat(20);
+        let requests = Arc::new(AtomicUsize::new(0));
+        let router = Router::new().route(
+            "/scrape",
+            get({
+                let requests = requests.clone();
+                let known = hex::decode(&known).unwrap();
+                move |RawQuery(_): RawQuery| async move {
+                    requests.fetch_add(1, Ordering::SeqCst);
+                    let mut body = b"d5:filesd20:".to_vec();
+                    body.extend(&known);
+                    body.extend(b"d8:completei3e10:downloadedi7e10:incompletei1eeee");
+                    body
+                }
+            }),
+        );
+        let http = format!(
+            "{}/announce",
+            crate::download_client::stub_server(router).await
+        );
+        let udp = format!(
+            "udp://{}/announce",
+            udp::tests::stub_tracker(12, false).await
+        );
+
+        let scraper = Scraper::new(Duration::from_secs(2), Duration::from_secs(60));
+        let targets = [
+            Target::new(Some(&known), None, &[http.clone(), udp]).unwrap(),
+            Target::new(Some(&unknown), None, &[http]).unwrap(),
+        ];
+        let stats = scraper.scrape(&targets).await;
+        // The UDP stub reports more seeders.
+        assert_eq!(stats[&known].seeders, 12);
+        assert!(!stats.contains_key(&unknown));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+
+        // Both the hit and the miss are cached.
+        let cached = scraper.scrape(&targets).await;
+        assert_eq!(cached, stats);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,195 @@
+//! UDP tracker scrapes (BEP 15): a connect exchange for a connection id,
+//! then a scrape of up to 74 info hashes, each resent once if lost.
+
+use std::{
+    collections::HashMap,
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr},
+    time::Duration,
+};
+
+use anyhow::Context;
+use chrono::Utc;
+use reqwest::Url;
+use tokio::net::UdpSocket;
+
+use super::ScrapeStats;
+
+const PROTOCOL_ID: u64 = 0x41727101980;
+const ACTION_CONNECT: u32 = 0;
+const ACTION_SCRAPE: u32 = 2;
+const ACTION_ERROR: u32 = 3;
+/// Times each request is sent before giving up.
+const ATTEMPTS: u32 = 2;
+/// Exchanges in a scrape: connect, then scrape.
+const EXCHANGES: u32 = 2;
+
+fn transaction_id() -> u32 {
+    uuid::Uuid::new_v4().as_u128() as u32
+}
+
+/// Scrapes `hashes` from `tracker` within `timeout`. Whatever is left of it
+/// once the tracker is resolved is split evenly between every attempt of
+/// both exchanges, so a lost packet is resent before the scrape runs out of
+/// time.
+pub async fn scrape(
+    tracker: &Url,
+    hashes: &[&str],
+    timeout: Duration,
+) -> anyhow::Result<HashMap<String, ScrapeStats>> {
+    let deadline = tokio::time::Instant::now() + timeout;
+    let host = tracker.host_str().context("tracker has no host")?;
+    let port = tracker.port().context("tracker has no port")?;
+    let addr = tokio::net::lookup_host((host, port))
+        .await?
+        .next()
+        .context("tracker host did not resolve")?;
+    let local: SocketAddr = if addr.is_ipv4() {
+        (Ipv4Addr::UNSPECIFIED, 0).into()
+    } else {
+        (Ipv6Addr::UNSPECIFIED, 0).into()
+    };
+    let socket = UdpSocket::bind(local).await?;
+    socket.connect(addr).await?;
+    let timeout =
+        deadline.saturating_duration_since(tokio::time::Instant::now()) / (EXCHANGES * ATTEMPTS);
+
+    let mut request = Vec::with_capacity(16);
+    request.extend(PROTOCOL_ID.to_be_bytes());
+    request.extend(ACTION_CONNECT.to_be_bytes());
+    let response = exchange(&socket, request, ACTION_CONNECT, timeout).await?;
+    let connection_id = response
+        .get(..8)
+        .context("connect response too short")?
+        .to_vec();
+
+    let mut request = Vec::with_capacity(16 + 20 * hashes.len());
+    request.extend(connection_id);
+    request.extend(ACTION_SCRAPE.to_be_bytes());
+    for hash in hashes {
+        request.extend(hex::decode(hash)?);
+    }
+    let response = exchange(&socket, request, ACTION_SCRAPE, timeout).await?;
+    anyhow::ensure!(
+        response.len() >= 12 * hashes.len(),
+        "scrape response too short"
+    );
+
+    let now = Utc::now();
+    let count = |offset: usize| {
+        i32::from_be_bytes(response[offset..offset + 4].try_into().unwrap()).max(0) as usize
+    };
+    Ok(hashes
+        .iter()
+        .enumerate()
+        .map(|(i, hash)| {
+            (
+                hash.to_string(),
+                ScrapeStats {
+                    seeders: count(12 * i),
+                    downloads: count(12 * i + 4),
+                    leechers: count(12 * i + 8),
+                    scraped_at: now,
+                },
+            )
+        })
+        .collect())
+}
+
+/// Sends `request` with a fresh transaction id, resending it when no
+/// answer comes in time, and returns the payload of the response.
+async fn exchange(
+    socket: &UdpSocket,
+    mut request: Vec<u8>,
+    action: u32,
+    timeout: Duration,
+) -> anyhow::Result<Vec<u8>> {
+    // The transaction id follows the 8-byte connection or protocol id and
+    // the action.
+    let id = transaction_id().to_be_bytes();
+    request.splice(12..12, id);
+
+    let mut buf = [0u8; 2048];
+    for _ in 0..ATTEMPTS {
+        socket.send(&request).await?;
+        let deadline = tokio::time::Instant::now() + timeout;
+        while let Ok(received) = tokio::time::timeout_at(deadline, socket.recv(&mut buf)).await {
+            let len = received?;
+            // Late answers to an earlier attempt are skipped.
+            if len < 8 || buf[4..8] != id {
+                continue;
+            }
+            let got = u32::from_be_bytes(buf[..4].try_into().unwrap());
+            if got == ACTION_ERROR {
+                anyhow::bail!("tracker error: {}", String::from_utf8_lossy(&buf[8..len]));
+            }
+            anyhow::ensure!(got == action, "unexpected tracker action {}", got);
+            return Ok(buf[8..len].to_vec());
+        }
+    }
+    anyhow::bail!("tracker did not respond")
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    const CONNECTION_ID: u64 = 0x1234_5678_9abc_def0;
+
+    /// Starts a tracker reporting `seeders` for every info hash, returning
+    /// its address. With `drop_first`, the first packet goes unanswered.
+    pub async fn stub_tracker(seeders: i32, drop_first: bool) -> SocketAddr {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr = socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 2048];
+            let mut dropped = !drop_first;
+            loop {
+                let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+                if !dropped {
+                    dropped = true;
+                    continue;
+                }
+                let connection = u64::from_be_bytes(buf[..8].try_into().unwrap());
+                let action = u32::from_be_bytes(buf[8..12].try_into().unwrap());
+                let mut response = buf[8..16].to_vec();
+                match action {
+                    ACTION_CONNECT => {
+                        assert_eq!(connection, PROTOCOL_ID);
+                        response.extend(CONNECTION_ID.to_be_bytes());
+                    }
+                    ACTION_SCRAPE if connection == CONNECTION_ID => {
+                        for _ in buf[16..len].chunks(20) {
+                            response.extend(seeders.to_be_bytes());
+                            response.extend(100i32.to_be_bytes());
+                            response.extend(2i32.to_be_bytes());
+                        }
+                    }
+                    _ => {
+                        response[..4].copy_from_slice(&ACTION_ERROR.to_be_bytes());
+                        response.extend(b"bad request");
+                    }
+                }
+                socket.send_to(&response, peer).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_scrape() {
+        let tracker =
+            Url::parse(&format!("udp://{}/announce", stub_tracker(7, true).await)).unwrap();
+        let hashes = ["aa".repeat(20), "bb".repeat(20)];
+        let hashes = hashes.iter().map(String::as_str).collect::<Vec<_>>();
+        let started = std::time::Instant::now();
+        let stats = scrape(&tracker, &hashes, Duration::from_millis(400))
+            .await
+            .unwrap();
+        // The lost connect is resent after a quarter of the timeout, leaving
+        // the scrape exchange room for a resend of its own.
+        assert!(started.elapsed() < Duration::from_millis(200));
+        assert_eq!(stats.len(), 2);
+        let aa = &stats[hashes[0]];
+        assert_eq!((aa.seeders, aa.leechers, aa.downloads), (7, 2, 100));
+    }
+}
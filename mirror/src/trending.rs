@@ -0,0 +1,171 @@
+//! Rankings of torrents by how fast their swarm grows, computed from the
+//! stats recorded in the index.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+
+use crate::index::{Index, SwarmGrowth};
+
+/// How long computed rankings are reused.
+const CACHE_DURATION: Duration = Duration::from_secs(5 * 60);
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize,
+)]
+pub enum Window {
+    #[serde(rename = "1h")]
+    Hour,
+    #[default]
+    #[serde(rename = "24h")]
+    Day,
+    #[serde(rename = "7d")]
+    Week,
+}
+
+impl Window {
+    pub fn duration(&self) -> Duration {
+        match self {
+            Window::Hour => Duration::from_secs(60 * 60),
+            Window::Day => Duration::from_secs(24 * 60 * 60),
+            Window::Week => Duration::from_secs(7 * 24 * 60 * 60),
+        }
+    }
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    #[default]
+    Downloads,
+    Seeders,
+}
+
+impl Metric {
+    fn growth(&self, item: &SwarmGrowth) -> i64 {
+        match self {
+            Metric::Downloads => item.download_growth,
+            Metric::Seeders => item.seeder_growth,
+        }
+    }
+}
+
+/// A ranking and when it was computed.
+#[derive(Debug)]
+pub struct Ranking {
+    computed: Instant,
+    pub computed_at: chrono::DateTime<Utc>,
+    /// Every torrent that grew, fastest first. Rankings are shared by all
+    /// requests for a mirror, window and metric, so they are never filtered
+    /// or truncated; requests filter them and then apply their limit.
+    pub items: Vec<SwarmGrowth>,
+}
+
+/// Sorts torrents that grew by `metric`, fastest first, breaking ties by
+/// the other metric and then by newest id.
+pub fn rank(mut items: Vec<SwarmGrowth>, metric: Metric) -> Vec<SwarmGrowth> {
+    let other = match metric {
+        Metric::Downloads => Metric::Seeders,
+        Metric::Seeders => Metric::Downloads,
+    };
+    items.retain(|item| metric.growth(item) > 0);
+    items.sort_by(|a, b| {
+        metric
+            .growth(b)
+            .cmp(&metric.growth(a))
+            .then(other.growth(b).cmp(&other.growth(a)))
+            .then(b.torrent.id.cmp(&a.torrent.id))
+    });
+    items
+}
+
+type Key = (String, Window, Metric);
+
+/// Cache of computed rankings per mirror, window and metric.
+#[derive(Debug, Clone, Default)]
+pub struct Trending {
+    rankings: Arc<Mutex<HashMap<Key, Arc<Ranking>>>>,
+}
+
+impl Trending {
+    /// Returns the cached ranking, computing it again once it expired.
+    pub async fn ranking(
+        &self,
+        index: &Index,
+        mirror_id: &str,
+        window: Window,
+        metric: Metric,
+    ) -> rusqlite::Result<Arc<Ranking>> {
+        let key = (mirror_id.to_string(), window, metric);
+        let now = Instant::now();
+        if let Some(ranking) = self.rankings.lock().unwrap().get(&key)
+            && now.duration_since(ranking.computed) < CACHE_DURATION
+        {
+            return Ok(ranking.clone());
+        }
+
+        let computed_at = Utc::now();
+        let since = computed_at - window.duration();
+        let ranking = Arc::new(Ranking {
+            computed: now,
+            computed_at,
+            items: rank(index.growth(mirror_id, since).await?, metric),
+        });
+        let mut rankings = self.rankings.lock().unwrap();
+        rankings.retain(|_, ranking| now.duration_since(ranking.computed) < CACHE_DURATION);
+        rankings.insert(key, ranking.clone());
+        Ok(ranking)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::index::{Cursor, ListedTorrent};
+
+    fn growth(id: usize, seeders: i64, downloads: i64) -> SwarmGrowth {
+        SwarmGrowth {
+            torrent: ListedTorrent {
+                id,
+                title: format!("Torrent {}", id),
+                pub_date: Utc::now(),
+                description: None,
+                category: "1_2".into(),
+                size: 0,
+                seeders: 0,
+                leechers: 0,
+                downloads: 0,
+                comments: 0,
+                trusted: false,
+                remake: false,
+                info_hash: None,
+                first_seen_at: Utc::now(),
+                updated_at: Utc::now(),
+                deletion: None,
+                cursor: Cursor::default(),
+            },
+            seeder_growth: seeders,
+            download_growth: downloads,
+            observed_since: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_rank() {
+        let items = vec![
+            growth(1, 5, 10),
+            growth(2, 9, 10),
+            growth(3, 20, 0),
+            growth(4, -3, 30),
+        ];
+        let ids = |items: Vec<SwarmGrowth>| items.iter().map(|i| i.torrent.id).collect::<Vec<_>>();
+        assert_eq!(ids(rank(items.clone(), Metric::Downloads)), [4, 2, 1]);
+        assert_eq!(ids(rank(items, Metric::Seeders)), [3, 2, 1]);
+    }
+}
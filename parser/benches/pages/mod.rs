@@ -0,0 +1,231 @@
+//! Synthetic pages following the nyaa.si markup.
+
+use std::fmt::Write;
+
+pub fn list_page(rows: usize) -> String {
+    let mut html = String::from(
+        r##"<!DOCTYPE html>
+<html lang="en">
+<head><title>Nyaa</title></head>
+<body>
+<div class="container">
+    <div class="table-responsive">
+        <table class="table table-bordered table-hover table-striped torrent-list">
+            <thead>
+                <tr>
+                    <th class="hdr-category text-center" style="width:80px;">Category</th>
+                    <th class="hdr-name" style="width:auto;">Name</th>
+                    <th class="hdr-comments sorting text-center" title="Comments" style="width:50px;"><a href="/?s=comments&amp;o=desc"></a><i class="fa fa-comments-o"></i></th>
+                    <th class="hdr-link text-center" style="width:70px;">Link</th>
+                    <th class="hdr-size sorting text-center" style="width:100px;"><a href="/?s=size&amp;o=desc"></a>Size</th>
+                    <th class="hdr-date sorting_desc text-center" title="In UTC" style="width:140px;"><a href="/?s=id&amp;o=asc"></a>Date</th>
+                    <th class="hdr-seeders sorting text-center" title="Seeders" style="width:50px;"><a href="/?s=seeders&amp;o=desc"></a><i class="fa fa-arrow-up" aria-hidden="true"></i></th>
+                    <th class="hdr-leechers sorting text-center" title="Leechers" style="width:50px;"><a href="/?s=leechers&amp;o=desc"></a><i class="fa fa-arrow-down" aria-hidden="true"></i></th>
+                    <th class="hdr-downloads sorting text-center" title="Completed downloads" style="width:50px;"><a href="/?s=downloads&amp;o=desc"></a><i class="fa fa-check" aria-hidden="true"></i></th>
+                </tr>
+            </thead>
+            <tbody>
+"##,
+    );
+    for row in 0..rows {
+        let id = 1_950_000 + row;
+        let class = ["default", "success", "danger"][row % 3];
+        let comments = if row % 4 == 0 {
+            format!(
+                r##"<a href="/view/{id}#comments" class="comments" title="{n} comments"><i class="fa fa-comments-o"></i>{n}</a>"##,
+                n = row % 7 + 1
+            )
+        } else {
+            String::new()
+        };
+        write!(
+            html,
+            r##"
+                <tr class="{class}">
+                    <td>
+                        <a href="/?c=1_2" title="Anime - English-translated">
+                            <img src="/static/img/icons/nyaa/1_2.png" alt="Anime - English-translated" class="category-icon">
+                        </a>
+                    </td>
+                    <td colspan="2">
+                        {comments}
+                        <a href="/view/{id}" title="[Group] Show - {row:02} (1080p) [ABCDEF{row:02}].mkv">[Group] Show - {row:02} (1080p) [ABCDEF{row:02}].mkv</a>
+                    </td>
+                    <td class="text-center">
+                        <a href="/download/{id}.torrent"><i class="fa fa-fw fa-download"></i></a>
+                        <a href="magnet:?xt=urn:btih:{id:040x}&amp;dn=%5BGroup%5D%20Show&amp;tr=http%3A%2F%2Fnyaa.tracker.wf%3A7777%2Fannounce"><i class="fa fa-fw fa-magnet"></i></a>
+                    </td>
+                    <td class="text-center">1.{row} GiB</td>
+                    <td class="text-center" data-timestamp="{timestamp}">2025-03-29 09:14</td>
+                    <td class="text-center">{seeders}</td>
+                    <td class="text-center">{leechers}</td>
+                    <td class="text-center">{downloads}</td>
+                </tr>"##,
+            timestamp = 1_743_239_642 - row * 60,
+            seeders = row * 3,
+            leechers = row * 2,
+            downloads = row * 11,
+        )
+        .unwrap();
+    }
+    html.push_str(
+        r##"
+            </tbody>
+        </table>
+    </div>
+</div>
+</body>
+</html>
+"##,
+    );
+    html
+}
+
+pub fn view_page(comments: usize, files: usize) -> String {
+    let mut html = String::from(
+        r##"<!DOCTYPE html>
+<html lang="en">
+<head><title>Nyaa</title></head>
+<body>
+<div class="container">
+    <div class="panel panel-success">
+        <div class="panel-heading">
+            <h3 class="panel-title">
+                [Group] Show - Batch (1080p)
+            </h3>
+        </div>
+        <div class="panel-body">
+            <div class="row">
+                <div class="col-md-1">Category:</div>
+                <div class="col-md-5">
+                    <a href="/?c=1_0">Anime</a> - <a href="/?c=1_2">English-translated</a>
+                </div>
+                <div class="col-md-1">Date:</div>
+                <div class="col-md-5" data-timestamp="1743239642">2025-03-29 09:14 UTC</div>
+            </div>
+            <div class="row">
+                <div class="col-md-1">Submitter:</div>
+                <div class="col-md-5">
+                    <a class="text-success" href="/user/group" data-toggle="tooltip" title="Trusted">group</a>
+                </div>
+                <div class="col-md-1">Seeders:</div>
+                <div class="col-md-5"><span style="color: green;">123</span></div>
+            </div>
+            <div class="row">
+                <div class="col-md-1">Information:</div>
+                <div class="col-md-5">
+                    <a href="https://myanimelist.net/anime/1/">https://myanimelist.net/anime/1/</a>
+                </div>
+                <div class="col-md-1">Leechers:</div>
+                <div class="col-md-5"><span style="color: red;">45</span></div>
+            </div>
+            <div class="row">
+                <div class="col-md-1">File size:</div>
+                <div class="col-md-5">14.2 GiB</div>
+                <div class="col-md-1">Completed:</div>
+                <div class="col-md-5">6789</div>
+            </div>
+            <div class="row">
+                <div class="col-md-offset-6 col-md-1">Info hash:</div>
+                <div class="col-md-5"><kbd>84e064742ffe9f5eb4a739766a33d8631746310c</kbd></div>
+            </div>
+        </div>
+        <div class="panel-footer clearfix">
+            <a href="/download/1953481.torrent"><i class="fa fa-download fa-fw"></i>Download Torrent</a> or
+            <a href="magnet:?xt=urn:btih:84e064742ffe9f5eb4a739766a33d8631746310c&amp;dn=Show" class="card-footer-item"><i class="fa fa-magnet fa-fw"></i>Magnet</a>
+        </div>
+    </div>
+    <div class="panel panel-default">
+        <div markdown-text class="panel-body" id="torrent-description">**Show** batch
+---
+Encoded by [Group](https://example.org)</div>
+    </div>
+    <div class="panel panel-default">
+        <div class="panel-heading">
+            <h3 class="panel-title">File list</h3>
+        </div>
+        <div class="torrent-file-list panel-body">
+            <ul>
+                <li><a href="" class="folder"><i class="fa fa-folder-open"></i>[Group] Show</a>
+                    <ul data-show="yes">
+"##,
+    );
+    for file in 0..files {
+        write!(
+            html,
+            r##"
+                        <li><i class="fa fa-file"></i>[Group] Show - {file:03} (1080p).mkv <span class="file-size">({size} MiB)</span></li>"##,
+            size = 300 + file,
+        )
+        .unwrap();
+    }
+    write!(
+        html,
+        r##"
+                    </ul>
+                </li>
+            </ul>
+        </div>
+    </div>
+    <div id="comments" class="panel panel-default">
+        <div class="panel-heading">
+            <a class="collapsed" data-toggle="collapse" href="#collapse-comments" role="button">
+                <h3 class="panel-title">Comments - {comments}</h3>
+            </a>
+        </div>
+        <div class="collapse in" id="collapse-comments">"##
+    )
+    .unwrap();
+    for comment in 0..comments {
+        let id = 3_000_000 + comment;
+        let avatar = if comment % 2 == 0 {
+            "/static/img/avatars/default.png".to_string()
+        } else {
+            format!("https://i.example.org/avatar{comment}.png")
+        };
+        let edited = if comment % 5 == 0 {
+            format!(
+                r##"<small data-timestamp-swap data-timestamp="{}">(edited)</small>"##,
+                1_743_240_000 + comment * 90
+            )
+        } else {
+            String::new()
+        };
+        write!(
+            html,
+            r##"
+            <div class="panel panel-default comment-panel" id="com-{id}">
+                <div class="panel-body">
+                    <div class="col-md-2">
+                        <p>
+                            <a class="text-default" href="/user/user{comment}" data-toggle="tooltip" title="User">user{comment}</a>
+                        </p>
+                        <img class="avatar" src="{avatar}" alt="User">
+                    </div>
+                    <div class="col-md-10 comment">
+                        <div class="row comment-details">
+                            <a href="#com-{id}"><small data-timestamp-swap data-timestamp="{timestamp}">2025-03-29 09:15 UTC</small></a>
+                            {edited}
+                        </div>
+                        <div class="row comment-body">
+                            <div markdown-text class="comment-content" id="torrent-comment{id}">Thanks for episode {comment}!
+Looking forward to the next one.</div>
+                        </div>
+                    </div>
+                </div>
+            </div>"##,
+            timestamp = 1_743_239_700 + comment * 60,
+        )
+        .unwrap();
+    }
+    html.push_str(
+        r##"
+        </div>
+    </div>
+</div>
+</body>
+</html>
+"##,
+    );
+    html
+}
@@ -0,0 +1,40 @@
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use nyaa_parser::ParserContext;
+
+mod pages;
+
+fn list(c: &mut Criterion) {
+    let mut group = c.benchmark_group("list");
+    for rows in [20, 75] {
+        let html = pages::list_page(rows);
+        group.throughput(Throughput::Bytes(html.len() as u64));
+        group.bench_with_input(BenchmarkId::new("parse", rows), &html, |b, html| {
+            b.iter(|| nyaa_parser::list::html::parse("https://nyaa.si", html).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("parse_checked", rows), &html, |b, html| {
+            let context = ParserContext::nyaa();
+            b.iter(|| context.parse_list_checked("https://nyaa.si", html))
+        });
+    }
+    group.finish();
+}
+
+fn view(c: &mut Criterion) {
+    let mut group = c.benchmark_group("view");
+    for (comments, files) in [(10, 10), (300, 300)] {
+        let html = pages::view_page(comments, files);
+        let id = format!("{}x{}", comments, files);
+        group.throughput(Throughput::Bytes(html.len() as u64));
+        group.bench_with_input(BenchmarkId::new("parse", &id), &html, |b, html| {
+            b.iter(|| nyaa_parser::view::html::parse("https://nyaa.si", html).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("parse_checked", &id), &html, |b, html| {
+            let context = ParserContext::nyaa();
+            b.iter(|| context.parse_view_checked("https://nyaa.si", html))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, list, view);
+criterion_main!(benches);
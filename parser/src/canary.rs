@@ -0,0 +1,317 @@
+//! Structural checks that detect when the upstream markup no longer matches
+//! what the HTML parsers expect, ideally before parsing starts to fail.
+
+use scraper::Html;
+use serde::{Deserialize, Serialize};
+
+use crate::ParserContext;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageKind {
+    List,
+    View,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum DriftIssue {
+    MissingTable,
+    MissingHeader {
+        class: String,
+    },
+    /// A header is present but not in the expected column.
+    MovedHeader {
+        class: String,
+        expected: usize,
+        found: usize,
+    },
+    ColumnCount {
+        row: usize,
+        expected: usize,
+        found: usize,
+    },
+    MissingLabel {
+        label: String,
+    },
+    MissingElement {
+        selector: String,
+    },
+}
+
+impl std::fmt::Display for DriftIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DriftIssue::MissingTable => write!(f, "torrent table not found"),
+            DriftIssue::MissingHeader { class } => write!(f, "header {} not found", class),
+            DriftIssue::MovedHeader {
+                class,
+                expected,
+                found,
+            } => write!(
+                f,
+                "header {} moved from column {} to {}",
+                class, expected, found
+            ),
+            DriftIssue::ColumnCount {
+                row,
+                expected,
+                found,
+            } => write!(
+                f,
+                "row {} has {} columns, expected {}",
+                row, found, expected
+            ),
+            DriftIssue::MissingLabel { label } => write!(f, "label {:?} not found", label),
+            DriftIssue::MissingElement { selector } => {
+                write!(f, "element {} not found", selector)
+            }
+        }
+    }
+}
+
+/// Deviations of a page from the layout the parsers expect.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayoutDrift {
+    pub page: PageKind,
+    pub issues: Vec<DriftIssue>,
+}
+
+impl std::fmt::Display for LayoutDrift {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let page = match self.page {
+            PageKind::List => "list",
+            PageKind::View => "view",
+        };
+        write!(f, "{} page layout drift: ", page)?;
+        for (index, issue) in self.issues.iter().enumerate() {
+            if index > 0 {
+                write!(f, "; ")?;
+            }
+            write!(f, "{}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+fn drift(page: PageKind, issues: Vec<DriftIssue>) -> Option<LayoutDrift> {
+    if issues.is_empty() {
+        None
+    } else {
+        Some(LayoutDrift { page, issues })
+    }
+}
+
+/// Checks a list page against the nyaa.si layout. Returns `None` when the
+/// layout matches.
+pub fn check_list(data: &str) -> Option<LayoutDrift> {
+    ParserContext::nyaa().check_list(data)
+}
+
+pub fn check_list_document(context: &ParserContext, document: &Html) -> Option<LayoutDrift> {
+    let layout = &context.profile().list;
+    let selectors = &context.list;
+    if document.select(&selectors.table).next().is_none() {
+        // Searches without results render a message instead of the table.
+        let body = document.root_element().text().collect::<String>();
+        if body.contains(&layout.no_results) {
+            return None;
+        }
+        return drift(PageKind::List, vec![DriftIssue::MissingTable]);
+    }
+
+    let mut issues = Vec::new();
+
+    let headers = document
+        .select(&selectors.header_cells)
+        .map(|th| th.value().classes().map(str::to_string).collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    for (expected, class) in layout.headers.iter().enumerate() {
+        match headers
+            .iter()
+            .position(|classes| classes.iter().any(|c| c == class))
+        {
+            None => issues.push(DriftIssue::MissingHeader {
+                class: class.to_string(),
+            }),
+            Some(found) if found != expected => issues.push(DriftIssue::MovedHeader {
+                class: class.to_string(),
+                expected,
+                found,
+            }),
+            Some(_) => {}
+        }
+    }
+
+    let rows = document
+        .select(&selectors.rows)
+        .flat_map(|tbody| tbody.select(&selectors.tr))
+        .collect::<Vec<_>>();
+    if rows.is_empty() {
+        issues.push(DriftIssue::MissingElement {
+            selector: layout.rows.clone(),
+        });
+    }
+    // One mismatching row is enough to report; avoid flooding the diagnostic.
+    if let Some((row, found)) = rows
+        .iter()
+        .map(|tr| tr.select(&selectors.td).count())
+        .enumerate()
+        .find(|(_, found)| *found != layout.columns.count)
+    {
+        issues.push(DriftIssue::ColumnCount {
+            row,
+            expected: layout.columns.count,
+            found,
+        });
+    }
+
+    drift(PageKind::List, issues)
+}
+
+/// Checks a view page against the nyaa.si layout. Returns `None` when the
+/// layout matches.
+pub fn check_view(data: &str) -> Option<LayoutDrift> {
+    ParserContext::nyaa().check_view(data)
+}
+
+pub fn check_view_document(context: &ParserContext, document: &Html) -> Option<LayoutDrift> {
+    let layout = &context.profile().view;
+    let selectors = &context.view;
+    let mut issues = Vec::new();
+
+    for (selector, compiled) in [
+        (&layout.title, &selectors.title),
+        (&layout.timestamp, &selectors.timestamp),
+        (&layout.download, &selectors.download),
+        (&layout.magnet, &selectors.magnet),
+    ] {
+        if document.select(compiled).next().is_none() {
+            issues.push(DriftIssue::MissingElement {
+                selector: selector.to_string(),
+            });
+        }
+    }
+
+    for label in layout.labels.all() {
+        if crate::view::html::find_value(document, context, label).is_err() {
+            issues.push(DriftIssue::MissingLabel {
+                label: label.to_string(),
+            });
+        }
+    }
+
+    drift(PageKind::View, issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIST: &str = r#"
+<table class="table table-bordered table-hover table-striped torrent-list">
+    <thead>
+        <tr>
+            <th class="hdr-category text-center">Category</th>
+            <th class="hdr-name">Name</th>
+            <th class="hdr-comments sorting text-center"></th>
+            <th class="hdr-link text-center">Link</th>
+            <th class="hdr-size sorting text-center">Size</th>
+            <th class="hdr-date sorting_desc text-center">Date</th>
+            <th class="hdr-seeders sorting text-center"></th>
+            <th class="hdr-leechers sorting text-center"></th>
+            <th class="hdr-downloads sorting text-center"></th>
+        </tr>
+    </thead>
+    <tbody>
+        <tr class="default">
+            <td><a href="/?c=1_2"></a></td>
+            <td colspan="2"><a href="/view/1" title="A">A</a></td>
+            <td><a href="/download/1.torrent"></a><a href="magnet:?xt=urn:btih:00"></a></td>
+            <td>1.0 GiB</td>
+            <td data-timestamp="1743239642">2025-03-29 09:14</td>
+            <td>1</td>
+            <td>2</td>
+            <td>3</td>
+        </tr>
+    </tbody>
+</table>
+"#;
+
+    #[test]
+    fn test_check_list() {
+        assert_eq!(check_list(LIST), None);
+        assert_eq!(check_list("<h3>No results found</h3>"), None);
+        assert_eq!(
+            check_list("<div></div>"),
+            Some(LayoutDrift {
+                page: PageKind::List,
+                issues: vec![DriftIssue::MissingTable],
+            })
+        );
+
+        let drifted = LIST
+            .replace("hdr-size", "hdr-filesize")
+            .replace("<td>3</td>", "");
+        let drift = check_list(&drifted).unwrap();
+        assert_eq!(
+            drift.issues,
+            vec![
+                DriftIssue::MissingHeader {
+                    class: "hdr-size".into()
+                },
+                DriftIssue::ColumnCount {
+                    row: 0,
+                    expected: 8,
+                    found: 7
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_view() {
+        let view = r#"
+<div class="panel panel-default">
+    <div class="panel-heading"><h3 class="panel-title">Title</h3></div>
+    <div class="panel-body">
+        <div class="row">
+            <div class="col-md-1">Date:</div>
+            <div class="col-md-5" data-timestamp="1743239642">2025-03-29 09:14</div>
+            <div class="col-md-1">Seeders:</div>
+            <div class="col-md-5">1</div>
+        </div>
+        <div class="row">
+            <div class="col-md-1">Submitter:</div>
+            <div class="col-md-5">Anonymous</div>
+            <div class="col-md-1">Leechers:</div>
+            <div class="col-md-5">2</div>
+        </div>
+        <div class="row">
+            <div class="col-md-1">File size:</div>
+            <div class="col-md-5">1.0 GiB</div>
+            <div class="col-md-1">Completed:</div>
+            <div class="col-md-5">3</div>
+        </div>
+        <div class="row">
+            <div class="col-md-1">Info hash:</div>
+            <div class="col-md-5"><kbd>00</kbd></div>
+        </div>
+    </div>
+    <div class="panel-footer">
+        <a href="/download/1.torrent">Download</a>
+        <a href="magnet:?xt=urn:btih:00">Magnet</a>
+    </div>
+</div>
+"#;
+        assert_eq!(check_view(view), None);
+
+        let drift = check_view(&view.replace("Completed:", "Snatched:")).unwrap();
+        assert_eq!(
+            drift.issues,
+            vec![DriftIssue::MissingLabel {
+                label: "Completed:".into()
+            }]
+        );
+    }
+}
@@ -0,0 +1,193 @@
+//! Precompiled selectors for a [`LayoutProfile`], shared across parses.
+
+use std::sync::OnceLock;
+
+use scraper::{Html, Selector};
+
+use crate::LayoutProfile;
+use crate::ListItem;
+use crate::Result;
+use crate::UserPage;
+use crate::View;
+use crate::canary::LayoutDrift;
+use crate::profile::selector_from;
+
+/// A layout profile together with its compiled selectors. Building one
+/// compiles every selector, so it should be created once and reused; it is
+/// `Send` and `Sync` and can be shared between threads.
+#[derive(Debug)]
+pub struct ParserContext {
+    profile: LayoutProfile,
+    pub(crate) list: ListSelectors,
+    pub(crate) view: ViewSelectors,
+    pub(crate) user: UserSelectors,
+}
+
+#[derive(Debug)]
+pub(crate) struct ListSelectors {
+    pub table: Selector,
+    pub rows: Selector,
+    pub header_cells: Selector,
+    pub tr: Selector,
+    pub td: Selector,
+    pub a: Selector,
+}
+
+#[derive(Debug)]
+pub(crate) struct ViewSelectors {
+    pub title: Selector,
+    pub timestamp: Selector,
+    pub download: Selector,
+    pub magnet: Selector,
+    pub category: Selector,
+    pub description: Selector,
+    pub info_row: Selector,
+    pub info_cell: Selector,
+    pub file_list: Selector,
+    pub folder: Selector,
+    pub file_size: Selector,
+    pub trusted: Selector,
+    pub remake: Selector,
+    pub comment: Selector,
+    pub comment_user: Selector,
+    pub comment_content: Selector,
+    pub comment_avatar: Selector,
+}
+
+#[derive(Debug)]
+pub(crate) struct UserSelectors {
+    pub name: Selector,
+    pub avatar: Selector,
+    pub badges: Selector,
+}
+
+impl ParserContext {
+    /// Validates `profile` and compiles its selectors.
+    pub fn new(profile: LayoutProfile) -> Result<Self> {
+        profile.validate()?;
+
+        let list = &profile.list;
+        let list = ListSelectors {
+            table: selector_from(&list.table)?,
+            rows: selector_from(&list.rows)?,
+            header_cells: selector_from(&list.header_cells)?,
+            tr: selector_from("tr")?,
+            td: selector_from("td")?,
+            a: selector_from("a")?,
+        };
+
+        let view = &profile.view;
+        let view = ViewSelectors {
+            title: selector_from(&view.title)?,
+            timestamp: selector_from(&view.timestamp)?,
+            download: selector_from(&view.download)?,
+            magnet: selector_from(&view.magnet)?,
+            category: selector_from(&view.category)?,
+            description: selector_from(&view.description)?,
+            info_row: selector_from(&view.info_row)?,
+            info_cell: selector_from(&view.info_cell)?,
+            file_list: selector_from(&view.file_list)?,
+            folder: selector_from(&view.folder)?,
+            file_size: selector_from(&view.file_size)?,
+            trusted: selector_from(&view.trusted)?,
+            remake: selector_from(&view.remake)?,
+            comment: selector_from(&view.comment)?,
+            comment_user: selector_from(&view.comment_user)?,
+            comment_content: selector_from(&view.comment_content)?,
+            comment_avatar: selector_from(&view.comment_avatar)?,
+        };
+
+        let user = &profile.user;
+        let user = UserSelectors {
+            name: selector_from(&user.name)?,
+            avatar: selector_from(&user.avatar)?,
+            badges: selector_from(&user.badges)?,
+        };
+
+        Ok(Self {
+            profile,
+            list,
+            view,
+            user,
+        })
+    }
+
+    /// Context for the nyaa.si layout, built on first use.
+    pub fn nyaa() -> &'static ParserContext {
+        static NYAA: OnceLock<ParserContext> = OnceLock::new();
+        NYAA.get_or_init(|| {
+            ParserContext::new(LayoutProfile::nyaa()).expect("built-in profile is valid")
+        })
+    }
+
+    pub fn profile(&self) -> &LayoutProfile {
+        &self.profile
+    }
+
+    pub fn parse_list(&self, url: &str, data: &str) -> Result<Vec<ListItem>> {
+        crate::list::html::parse_document(self, url, &Html::parse_document(data))
+    }
+
+    pub fn parse_view(&self, url: &str, data: &str) -> Result<View> {
+        crate::view::html::parse_document(self, url, &Html::parse_document(data))
+    }
+
+    pub fn parse_user(&self, url: &str, data: &str) -> Result<UserPage> {
+        crate::user::html::parse_document(self, url, &Html::parse_document(data))
+    }
+
+    pub fn check_list(&self, data: &str) -> Option<LayoutDrift> {
+        crate::canary::check_list_document(self, &Html::parse_document(data))
+    }
+
+    pub fn check_view(&self, data: &str) -> Option<LayoutDrift> {
+        crate::canary::check_view_document(self, &Html::parse_document(data))
+    }
+
+    /// Checks and parses a list page, parsing the HTML only once.
+    pub fn parse_list_checked(
+        &self,
+        url: &str,
+        data: &str,
+    ) -> (Option<LayoutDrift>, Result<Vec<ListItem>>) {
+        let document = Html::parse_document(data);
+        (
+            crate::canary::check_list_document(self, &document),
+            crate::list::html::parse_document(self, url, &document),
+        )
+    }
+
+    /// Checks the torrent table of a user page and parses the page, parsing
+    /// the HTML only once.
+    pub fn parse_user_checked(
+        &self,
+        url: &str,
+        data: &str,
+    ) -> (Option<LayoutDrift>, Result<UserPage>) {
+        let document = Html::parse_document(data);
+        (
+            crate::canary::check_list_document(self, &document),
+            crate::user::html::parse_document(self, url, &document),
+        )
+    }
+
+    /// Checks and parses a view page, parsing the HTML only once.
+    pub fn parse_view_checked(&self, url: &str, data: &str) -> (Option<LayoutDrift>, Result<View>) {
+        let document = Html::parse_document(data);
+        (
+            crate::canary::check_view_document(self, &document),
+            crate::view::html::parse_document(self, url, &document),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_is_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<ParserContext>();
+    }
+}
@@ -0,0 +1,434 @@
+//! A small search language layered on top of nyaa's text search, for
+//! example `"super cube" seeders:>20 size:<2GiB -remake after:2025-03-01`.
+//!
+//! Free text, quoted phrases and unknown `key:value` terms are sent upstream
+//! unchanged, and searches without known keys are sent exactly as given.
+//! Known keys become predicates applied to the parsed results:
+//!
+//! - `seeders:`, `leechers:`, `downloads:`, `comments:` and `size:` take a
+//!   number (sizes with a unit such as `2GiB` or `700MB`), optionally
+//!   prefixed with `>`, `>=`, `<`, `<=` or `=`
+//! - `trusted:` and `remake:` take `yes` or `no`; `-trusted` and `-remake`
+//!   are short for the `no` forms
+//! - `after:` and `before:` take a date (`2025-03-01`) or an RFC 3339 time
+//! - `group:` matches a bracketed tag in the title, such as `[Sokudo]`
+//!
+//! Any predicate can be negated with a leading `-`.
+
+use chrono::{DateTime, NaiveDate, Utc};
+
+use crate::ListItem;
+
+/// A parse error, with the character range of the offending input.
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
+#[error("{message} at {start}..{end}")]
+pub struct SyntaxError {
+    pub message: String,
+    /// Index of the first character of the offending input.
+    pub start: usize,
+    /// Index just past the last character of the offending input.
+    pub end: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Op {
+    Lt,
+    Le,
+    Eq,
+    Ge,
+    Gt,
+}
+
+impl Op {
+    fn test(&self, left: u64, right: u64) -> bool {
+        match self {
+            Op::Lt => left < right,
+            Op::Le => left <= right,
+            Op::Eq => left == right,
+            Op::Ge => left >= right,
+            Op::Gt => left > right,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CountField {
+    Seeders,
+    Leechers,
+    Downloads,
+    Comments,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Condition {
+    Count(CountField, Op, u64),
+    Size(Op, u64),
+    Trusted(bool),
+    Remake(bool),
+    After(DateTime<Utc>),
+    Before(DateTime<Utc>),
+    /// Lowercased group name.
+    Group(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Predicate {
+    pub negated: bool,
+    pub condition: Condition,
+}
+
+impl Predicate {
+    pub fn matches(&self, item: &ListItem) -> bool {
+        let matches = match &self.condition {
+            Condition::Count(field, op, value) => {
+                let count = match field {
+                    CountField::Seeders => item.seeders,
+                    CountField::Leechers => item.leechers,
+                    CountField::Downloads => item.downloads,
+                    CountField::Comments => item.comments,
+                };
+                op.test(count as u64, *value)
+            }
+            Condition::Size(op, value) => op.test(item.size, *value),
+            Condition::Trusted(trusted) => item.trusted == *trusted,
+            Condition::Remake(remake) => item.remake == *remake,
+            Condition::After(date) => item.pub_date >= *date,
+            Condition::Before(date) => item.pub_date < *date,
+            Condition::Group(group) => groups(&item.title).any(|tag| tag == *group),
+        };
+        matches != self.negated
+    }
+}
+
+/// A parsed search: the text sent upstream and the predicates applied to
+/// the results.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Expression {
+    pub text: String,
+    pub predicates: Vec<Predicate>,
+}
+
+impl Expression {
+    pub fn has_predicates(&self) -> bool {
+        !self.predicates.is_empty()
+    }
+
+    pub fn matches(&self, item: &ListItem) -> bool {
+        self.predicates
+            .iter()
+            .all(|predicate| predicate.matches(item))
+    }
+}
+
+/// Lowercased contents of the bracketed tags in a title.
+fn groups(title: &str) -> impl Iterator<Item = String> + '_ {
+    title.split('[').skip(1).filter_map(|rest| {
+        rest.split_once(']')
+            .map(|(tag, _)| tag.trim().to_lowercase())
+    })
+}
+
+struct Token {
+    /// Source text, quotes included.
+    raw: String,
+    start: usize,
+    end: usize,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, SyntaxError> {
+    let chars = input.chars().collect::<Vec<_>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i].is_whitespace() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < chars.len() && !chars[i].is_whitespace() {
+            if chars[i] == '"' {
+                let quote = i;
+                i += 1;
+                while i < chars.len() && chars[i] != '"' {
+                    i += 1;
+                }
+                if i == chars.len() {
+                    return Err(SyntaxError {
+                        message: "unterminated quote".into(),
+                        start: quote,
+                        end: chars.len(),
+                    });
+                }
+            }
+            i += 1;
+        }
+        tokens.push(Token {
+            raw: chars[start..i].iter().collect(),
+            start,
+            end: i,
+        });
+    }
+    Ok(tokens)
+}
+
+fn unquote(value: &str) -> String {
+    value.replace('"', "")
+}
+
+fn parse_comparison(value: &str) -> (Op, &str) {
+    for (prefix, op) in [
+        (">=", Op::Ge),
+        ("<=", Op::Le),
+        (">", Op::Gt),
+        ("<", Op::Lt),
+        ("=", Op::Eq),
+    ] {
+        if let Some(rest) = value.strip_prefix(prefix) {
+            return (op, rest);
+        }
+    }
+    (Op::Eq, value)
+}
+
+/// Sizes are written without a space, as in `2GiB` or `1.5gb`.
+fn parse_size(value: &str) -> Option<u64> {
+    let split = value
+        .find(|c: char| !c.is_ascii_digit() && c != '.' && c != ',')
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    if unit.is_empty() {
+        return number.parse().ok();
+    }
+    crate::parse_size(&format!("{} {}", number, unit)).ok()
+}
+
+fn parse_date(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some(date.and_hms_opt(0, 0, 0)?.and_utc());
+    }
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// Keys turned into predicates; other `key:value` terms are plain text.
+const KEYS: &[&str] = &[
+    "seeders",
+    "leechers",
+    "downloads",
+    "completed",
+    "comments",
+    "size",
+    "trusted",
+    "remake",
+    "after",
+    "before",
+    "group",
+];
+
+/// Whether any word of `input` uses a known key, even if it would not
+/// parse.
+fn mentions_keys(input: &str) -> bool {
+    input.split_whitespace().any(|word| {
+        let body = word.strip_prefix('-').unwrap_or(word);
+        let key = body.split_once(':').map_or("", |(key, _)| key);
+        KEYS.contains(&key.to_lowercase().as_str())
+            || (body.len() < word.len() && matches!(body, "remake" | "trusted"))
+    })
+}
+
+/// Parses a search. Searches without predicates are sent upstream exactly as
+/// given, whatever their syntax. Otherwise fails on unterminated quotes and
+/// on invalid values for known keys.
+pub fn parse(input: &str) -> Result<Expression, SyntaxError> {
+    let plain = || Expression {
+        text: input.to_string(),
+        predicates: Vec::new(),
+    };
+    let tokens = match tokenize(input) {
+        Ok(tokens) => tokens,
+        Err(_) if !mentions_keys(input) => return Ok(plain()),
+        Err(err) => return Err(err),
+    };
+
+    let mut text = Vec::new();
+    let mut predicates = Vec::new();
+    for token in tokens {
+        let (negated, body) = match token.raw.strip_prefix('-') {
+            Some(body) if !body.is_empty() => (true, body),
+            _ => (false, token.raw.as_str()),
+        };
+        let body_start = token.start + usize::from(negated);
+
+        let (key, value) = match body.split_once(':') {
+            Some((key, value)) if !key.starts_with('"') => (key.to_lowercase(), value),
+            _ if negated && matches!(body, "remake" | "trusted") => (body.to_string(), "yes"),
+            _ => {
+                text.push(token.raw);
+                continue;
+            }
+        };
+        let value_start = body_start + key.chars().count() + 1;
+        let error = |message: String| SyntaxError {
+            message,
+            start: value_start.min(token.end),
+            end: token.end,
+        };
+
+        if !KEYS.contains(&key.as_str()) {
+            text.push(token.raw);
+            continue;
+        }
+        let value = unquote(value);
+        if value.is_empty() {
+            return Err(SyntaxError {
+                message: format!("missing value for {}", key),
+                start: body_start,
+                end: token.end,
+            });
+        }
+
+        let condition = match key.as_str() {
+            "seeders" | "leechers" | "downloads" | "completed" | "comments" => {
+                let field = match key.as_str() {
+                    "seeders" => CountField::Seeders,
+                    "leechers" => CountField::Leechers,
+                    "comments" => CountField::Comments,
+                    _ => CountField::Downloads,
+                };
+                let (op, number) = parse_comparison(&value);
+                let number = number
+                    .parse()
+                    .map_err(|_| error(format!("expected a number for {}", key)))?;
+                Condition::Count(field, op, number)
+            }
+            "size" => {
+                let (op, size) = parse_comparison(&value);
+                let size = parse_size(size)
+                    .ok_or_else(|| error(format!("expected a size for {}", key)))?;
+                Condition::Size(op, size)
+            }
+            "trusted" | "remake" => {
+                let flag = crate::parse_boolean(&value.to_lowercase())
+                    .map_err(|_| error(format!("expected yes or no for {}", key)))?;
+                if key == "trusted" {
+                    Condition::Trusted(flag)
+                } else {
+                    Condition::Remake(flag)
+                }
+            }
+            "after" | "before" => {
+                let date = parse_date(&value)
+                    .ok_or_else(|| error(format!("expected a date for {}", key)))?;
+                if key == "after" {
+                    Condition::After(date)
+                } else {
+                    Condition::Before(date)
+                }
+            }
+            "group" => Condition::Group(value.to_lowercase()),
+            _ => unreachable!("checked against KEYS"),
+        };
+        predicates.push(Predicate { negated, condition });
+    }
+
+    if predicates.is_empty() {
+        return Ok(plain());
+    }
+    Ok(Expression {
+        text: text.join(" "),
+        predicates,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(title: &str, seeders: usize, size: u64, remake: bool) -> ListItem {
+        ListItem {
+            title: title.into(),
+            pub_date: DateTime::from_timestamp(1_743_239_642, 0).unwrap(),
+            id: 1,
+            seeders,
+            category: "1_2".into(),
+            size,
+            trusted: true,
+            remake,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let expression = parse(
+            "\"super cube\" seeders:>20 size:<2GiB -remake trusted:yes after:2025-03-01 group:Sokudo Re:Zero -dub",
+        )
+        .unwrap();
+        assert_eq!(expression.text, "\"super cube\" Re:Zero -dub");
+        assert_eq!(
+            expression.predicates,
+            vec![
+                Predicate {
+                    negated: false,
+                    condition: Condition::Count(CountField::Seeders, Op::Gt, 20),
+                },
+                Predicate {
+                    negated: false,
+                    condition: Condition::Size(Op::Lt, 2 * 1024 * 1024 * 1024),
+                },
+                Predicate {
+                    negated: true,
+                    condition: Condition::Remake(true),
+                },
+                Predicate {
+                    negated: false,
+                    condition: Condition::Trusted(true),
+                },
+                Predicate {
+                    negated: false,
+                    condition: Condition::After(
+                        DateTime::parse_from_rfc3339("2025-03-01T00:00:00Z")
+                            .unwrap()
+                            .into()
+                    ),
+                },
+                Predicate {
+                    negated: false,
+                    condition: Condition::Group("sokudo".into()),
+                },
+            ]
+        );
+
+        let matching = item("[Sokudo] Super Cube - 01 [1080p]", 21, 1 << 30, false);
+        assert!(expression.matches(&matching));
+        assert!(!expression.matches(&item("[Sokudo] Super Cube - 01", 20, 1 << 30, false)));
+        assert!(!expression.matches(&item("[Sokudo] Super Cube - 01", 21, 3 << 30, false)));
+        assert!(!expression.matches(&item("[Sokudo] Super Cube - 01", 21, 1 << 30, true)));
+        assert!(!expression.matches(&item("[Other] Super Cube - 01", 21, 1 << 30, false)));
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        let error = parse("cube seeders:>abc").unwrap_err();
+        assert_eq!((error.start, error.end), (13, 17));
+
+        let error = parse("\"super cube size:1GB").unwrap_err();
+        assert_eq!(error.message, "unterminated quote");
+        assert_eq!((error.start, error.end), (0, 20));
+
+        let error = parse("ünï -trusted:maybe").unwrap_err();
+        assert_eq!((error.start, error.end), (13, 18));
+
+        let error = parse("after:").unwrap_err();
+        assert_eq!((error.start, error.end), (0, 6));
+        assert!(parse("size:2 parsecs").is_ok());
+        assert!(parse("size:2parsecs").is_err());
+
+        // Searches without predicates go upstream untouched.
+        for input in ["\"super cube", "  super   cube ", "Re:Zero -dub"] {
+            assert_eq!(parse(input).unwrap().text, input);
+        }
+        assert!(parse("\"super cube -remake").is_err());
+    }
+}
@@ -0,0 +1,273 @@
+//! IDs of entries on anime and film databases, pulled out of the links
+//! uploaders put in descriptions and in the information field.
+
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+
+/// A database torrents can link to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExternalSource {
+    MyAnimeList,
+    AniList,
+    AniDb,
+    Kitsu,
+    Imdb,
+    Tvdb,
+    Tmdb,
+}
+
+impl ExternalSource {
+    pub const ALL: [ExternalSource; 7] = [
+        ExternalSource::MyAnimeList,
+        ExternalSource::AniList,
+        ExternalSource::AniDb,
+        ExternalSource::Kitsu,
+        ExternalSource::Imdb,
+        ExternalSource::Tvdb,
+        ExternalSource::Tmdb,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ExternalSource::MyAnimeList => "myanimelist",
+            ExternalSource::AniList => "anilist",
+            ExternalSource::AniDb => "anidb",
+            ExternalSource::Kitsu => "kitsu",
+            ExternalSource::Imdb => "imdb",
+            ExternalSource::Tvdb => "tvdb",
+            ExternalSource::Tmdb => "tmdb",
+        }
+    }
+}
+
+impl std::str::FromStr for ExternalSource {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        ExternalSource::ALL
+            .into_iter()
+            .find(|source| source.as_str() == s)
+            .or(match s {
+                "mal" => Some(ExternalSource::MyAnimeList),
+                _ => None,
+            })
+            .ok_or_else(|| Error::ParseString(s.to_string()))
+    }
+}
+
+/// IDs found for a torrent. When several links point to the same database,
+/// the first one wins.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExternalIds {
+    pub myanimelist: Option<u64>,
+    pub anilist: Option<u64>,
+    pub anidb: Option<u64>,
+    /// Numeric ID or slug, Kitsu accepts both.
+    pub kitsu: Option<String>,
+    /// `tt`-prefixed title ID.
+    pub imdb: Option<String>,
+    /// Numeric ID or series slug.
+    pub tvdb: Option<String>,
+    /// `movie/<id>` or `tv/<id>`, as TMDB numbers movies and shows
+    /// separately.
+    pub tmdb: Option<String>,
+}
+
+impl ExternalIds {
+    /// Collects the IDs linked from `text`, which may be markdown, HTML or a
+    /// bare URL.
+    pub fn extract(text: &str) -> Self {
+        let mut ids = Self::default();
+        ids.extend_from(text);
+        ids
+    }
+
+    /// Adds the IDs linked from `text` for databases not seen yet.
+    pub fn extend_from(&mut self, text: &str) {
+        for url in urls(text) {
+            if let Some((source, id)) = parse_url(url) {
+                self.insert(source, id);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries().is_empty()
+    }
+
+    pub fn get(&self, source: ExternalSource) -> Option<String> {
+        match source {
+            ExternalSource::MyAnimeList => self.myanimelist.map(|id| id.to_string()),
+            ExternalSource::AniList => self.anilist.map(|id| id.to_string()),
+            ExternalSource::AniDb => self.anidb.map(|id| id.to_string()),
+            ExternalSource::Kitsu => self.kitsu.clone(),
+            ExternalSource::Imdb => self.imdb.clone(),
+            ExternalSource::Tvdb => self.tvdb.clone(),
+            ExternalSource::Tmdb => self.tmdb.clone(),
+        }
+    }
+
+    /// The IDs that are set, as strings.
+    pub fn entries(&self) -> Vec<(ExternalSource, String)> {
+        ExternalSource::ALL
+            .into_iter()
+            .filter_map(|source| self.get(source).map(|id| (source, id)))
+            .collect()
+    }
+
+    /// Sets the ID for `source` unless one is set already. Non-numeric IDs
+    /// for numeric sources are ignored.
+    pub fn insert(&mut self, source: ExternalSource, id: String) {
+        fn numeric(slot: &mut Option<u64>, id: &str) {
+            if slot.is_none() {
+                *slot = id.parse().ok();
+            }
+        }
+        fn string(slot: &mut Option<String>, id: String) {
+            slot.get_or_insert(id);
+        }
+
+        match source {
+            ExternalSource::MyAnimeList => numeric(&mut self.myanimelist, &id),
+            ExternalSource::AniList => numeric(&mut self.anilist, &id),
+            ExternalSource::AniDb => numeric(&mut self.anidb, &id),
+            ExternalSource::Kitsu => string(&mut self.kitsu, id),
+            ExternalSource::Imdb => string(&mut self.imdb, id),
+            ExternalSource::Tvdb => string(&mut self.tvdb, id),
+            ExternalSource::Tmdb => string(&mut self.tmdb, id),
+        }
+    }
+}
+
+/// Finds everything that looks like an http(s) URL in `text`.
+fn urls(text: &str) -> impl Iterator<Item = &str> {
+    text.match_indices("http").filter_map(|(start, _)| {
+        let rest = &text[start..];
+        if !rest.starts_with("http://") && !rest.starts_with("https://") {
+            return None;
+        }
+        let end = rest
+            .find(|c: char| c.is_whitespace() || "<>\"'()[]{}|\\^`".contains(c))
+            .unwrap_or(rest.len());
+        Some(rest[..end].trim_end_matches(['.', ',', ';', ':', '!', '?', '*']))
+    })
+}
+
+/// Splits a URL into its host (without `www.`), path segments and query.
+fn split_url(url: &str) -> Option<(String, Vec<&str>, &str)> {
+    let rest = url.split_once("://")?.1;
+    let rest = rest.split('#').next().unwrap_or_default();
+    let (location, query) = rest.split_once('?').unwrap_or((rest, ""));
+    let mut parts = location.split('/');
+    let host = parts.next()?.to_ascii_lowercase();
+    let host = host.split(':').next().unwrap_or_default();
+    let host = host.strip_prefix("www.").unwrap_or(host).to_string();
+    let path = parts.filter(|part| !part.is_empty()).collect();
+    Some((host, path, query))
+}
+
+fn query_value<'a>(query: &'a str, key: &str) -> Option<&'a str> {
+    query
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(name, _)| *name == key)
+        .map(|(_, value)| value)
+}
+
+/// Leading digits of a path segment, so `12345-some-title` gives `12345`.
+fn leading_number(segment: &str) -> Option<String> {
+    let digits: String = segment.chars().take_while(char::is_ascii_digit).collect();
+    (!digits.is_empty()).then_some(digits)
+}
+
+fn parse_url(url: &str) -> Option<(ExternalSource, String)> {
+    let (host, path, query) = split_url(url)?;
+    let number_after = |kinds: &[&str]| {
+        path.windows(2)
+            .find(|pair| kinds.contains(&pair[0]))
+            .and_then(|pair| leading_number(pair[1]))
+    };
+
+    match host.as_str() {
+        "myanimelist.net" => number_after(&["anime"])
+            .or_else(|| match path.first() {
+                Some(&"anime.php") => query_value(query, "id").and_then(leading_number),
+                _ => None,
+            })
+            .map(|id| (ExternalSource::MyAnimeList, id)),
+        "anilist.co" => number_after(&["anime"]).map(|id| (ExternalSource::AniList, id)),
+        "anidb.net" => number_after(&["anime"])
+            .or_else(|| match path.first() {
+                Some(segment) if segment.starts_with('a') => leading_number(&segment[1..]),
+                _ => None,
+            })
+            .or_else(|| query_value(query, "aid").and_then(leading_number))
+            .map(|id| (ExternalSource::AniDb, id)),
+        "kitsu.io" | "kitsu.app" => match path.as_slice() {
+            ["anime", id, ..] => Some((ExternalSource::Kitsu, id.to_string())),
+            _ => None,
+        },
+        "imdb.com" | "m.imdb.com" => path
+            .windows(2)
+            .find(|pair| pair[0] == "title" && pair[1].starts_with("tt"))
+            .and_then(|pair| leading_number(&pair[1][2..]))
+            .map(|id| (ExternalSource::Imdb, format!("tt{}", id))),
+        "thetvdb.com" => match path.as_slice() {
+            ["dereferrer", "series", id, ..] => leading_number(id),
+            ["series", slug, ..] => Some(slug.to_string()),
+            _ if query_value(query, "tab") == Some("series") => {
+                query_value(query, "id").and_then(leading_number)
+            }
+            _ => None,
+        }
+        .map(|id| (ExternalSource::Tvdb, id)),
+        "themoviedb.org" => path
+            .windows(2)
+            .find(|pair| pair[0] == "movie" || pair[0] == "tv")
+            .and_then(|pair| leading_number(pair[1]).map(|id| format!("{}/{}", pair[0], id)))
+            .map(|id| (ExternalSource::Tmdb, id)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extract() {
+        let ids = ExternalIds::extract(
+            "MAL: [link](https://myanimelist.net/anime/52991/Sousou_no_Frieren)\n\
+             <a href=\"https://anilist.co/anime/154587/\">AniList</a>\n\
+             https://anidb.net/anime/17617, http://kitsu.io/anime/sousou-no-frieren.\n\
+             IMDb: https://www.imdb.com/title/tt22248376/?ref_=nv_sr_srsg_0\n\
+             https://thetvdb.com/series/frieren-beyond-journeys-end\n\
+             https://www.themoviedb.org/tv/209867-frieren?language=en-US\n\
+             Second MAL link: https://myanimelist.net/anime.php?id=1",
+        );
+        assert_eq!(
+            ids,
+            ExternalIds {
+                myanimelist: Some(52991),
+                anilist: Some(154587),
+                anidb: Some(17617),
+                kitsu: Some("sousou-no-frieren".into()),
+                imdb: Some("tt22248376".into()),
+                tvdb: Some("frieren-beyond-journeys-end".into()),
+                tmdb: Some("tv/209867".into()),
+            }
+        );
+
+        let ids = ExternalIds::extract(
+            "http://anidb.net/perl-bin/animedb.pl?show=anime&aid=69 https://anidb.net/a1 \
+             https://myanimelist.net/anime.php?id=21 https://thetvdb.com/?tab=series&id=81797",
+        );
+        assert_eq!(ids.anidb, Some(69));
+        assert_eq!(ids.myanimelist, Some(21));
+        assert_eq!(ids.tvdb.as_deref(), Some("81797"));
+        assert!(ExternalIds::extract("https://example.org/anime/1 No information.").is_empty());
+    }
+}
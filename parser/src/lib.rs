@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ListItem {
     pub title: String,
     pub link: String,
@@ -25,10 +25,28 @@ pub struct ListItem {
 pub struct ViewFile {
     pub id: usize,
     pub name: String,
+    /// Folders leading to the file and its name, joined with `/`.
+    pub path: String,
     pub size: u64,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// An entry of the file list on a view page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum FileNode {
+    Folder {
+        name: String,
+        #[serde(default)]
+        children: Vec<FileNode>,
+    },
+    File {
+        name: String,
+        size: u64,
+    },
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ViewComment {
     pub id: usize,
     pub user: String,
@@ -38,7 +56,10 @@ pub struct ViewComment {
     pub avatar: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// A torrent's view page. Missing fields deserialize to their defaults, so
+/// views archived before a field was added still load.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct View {
     pub title: String,
     pub link: String,
@@ -56,9 +77,74 @@ pub struct View {
     pub description_md: String,
     pub download_link: Option<String>,
     pub magnet_link: Option<String>,
-    pub files: Vec<ViewFile>,
+    pub file_tree: Vec<FileNode>,
     pub comments: Vec<ViewComment>,
     pub submitter: String,
+    /// Link from the information field, if the uploader set one.
+    pub information: Option<String>,
+    /// IDs linked from the information field and the description.
+    pub external_ids: ExternalIds,
+}
+
+impl ListItem {
+    /// Lowercased info hash, taken from the feed or from the btih of the
+    /// magnet link.
+    pub fn btih(&self) -> Option<String> {
+        self.info_hash
+            .clone()
+            .or_else(|| {
+                let magnet = self.magnet_link.as_deref()?;
+                let (_, rest) = magnet.split_once("urn:btih:")?;
+                Some(rest.split('&').next().unwrap_or_default().to_string())
+            })
+            .map(|hash| hash.trim().to_lowercase())
+            .filter(|hash| !hash.is_empty())
+    }
+}
+
+impl View {
+    /// Files of `file_tree` in document order, without the folders.
+    pub fn files(&self) -> Vec<ViewFile> {
+        fn walk(nodes: &[FileNode], prefix: &str, files: &mut Vec<ViewFile>) {
+            for node in nodes {
+                match node {
+                    FileNode::Folder { name, children } => {
+                        walk(children, &format!("{}{}/", prefix, name), files);
+                    }
+                    FileNode::File { name, size } => files.push(ViewFile {
+                        id: files.len(),
+                        name: name.clone(),
+                        path: format!("{}{}", prefix, name),
+                        size: *size,
+                    }),
+                }
+            }
+        }
+
+        let mut files = Vec::new();
+        walk(&self.file_tree, "", &mut files);
+        files
+    }
+}
+
+/// An uploader as shown on their user page.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserProfile {
+    pub name: String,
+    /// User level, such as `User`, `Trusted` or `Moderator`.
+    pub level: Option<String>,
+    pub trusted: bool,
+    pub badges: Vec<String>,
+    pub avatar: Option<String>,
+    /// Number of torrents matching the current filters.
+    pub torrent_count: Option<usize>,
+}
+
+/// One page of an uploader's torrents.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserPage {
+    pub user: UserProfile,
+    pub items: Vec<ListItem>,
 }
 
 #[derive(thiserror::Error, Debug)]
@@ -78,6 +164,10 @@ pub enum Error {
     HtmlMissingAttribute(String),
     #[error("HTML unexpected element: {0}")]
     HtmlUnexpectedElement(String),
+    #[error("Invalid selector {0:?}: {1}")]
+    InvalidSelector(String, String),
+    #[error("Invalid layout profile: {0}")]
+    InvalidProfile(String),
 
     #[error("Unable to parse string: {0:?}")]
     ParseString(String),
@@ -89,15 +179,30 @@ pub enum Error {
     ParseSize(String, #[source] Option<std::num::ParseFloatError>),
     #[error("Failed to parse category: {0:?}")]
     ParseCategory(String),
+    #[error("Invalid search query: {0}")]
+    ParseQuery(String),
     #[error("Failed to timestamp: {0:?}")]
     ParseTimestamp(String),
 }
 
 pub type Result<T> = std::result::Result<T, Error>;
 
+pub mod canary;
+pub mod context;
+pub mod dsl;
+pub mod external;
 pub mod list;
+pub mod markdown;
+pub mod profile;
+pub mod query;
+pub mod user;
 pub mod view;
 
+pub use context::ParserContext;
+pub use external::{ExternalIds, ExternalSource};
+pub use profile::LayoutProfile;
+pub use query::{Category, Filter, Order, SearchQuery, Sort};
+
 fn parse_boolean(value: &str) -> Result<bool> {
     match value {
         "0" => Ok(false),
@@ -126,6 +231,8 @@ fn parse_size(value: &str) -> Result<u64> {
         (x, 1u64)
     } else if let Some(x) = value.strip_suffix(" kn") {
         (x, 1000u64)
+    } else if let Some(x) = value.strip_suffix(" kb") {
+        (x, 1000u64)
     } else if let Some(x) = value.strip_suffix(" mb") {
         (x, 1000u64 * 1000u64)
     } else if let Some(x) = value.strip_suffix(" gb") {
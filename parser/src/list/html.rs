@@ -1,17 +1,21 @@
 use crate::Error;
 use crate::ListItem;
+use crate::ParserContext;
 use crate::Result;
+use crate::profile::ListLayout;
 use scraper::ElementRef;
 
-struct HtmlParser {
-    url: String,
-    a_selector: scraper::Selector,
+struct HtmlParser<'a> {
+    url: &'a str,
+    layout: &'a ListLayout,
+    a_selector: &'a scraper::Selector,
+    td_selector: &'a scraper::Selector,
 }
 
-impl HtmlParser {
+impl HtmlParser<'_> {
     fn parse_category(&self, element: &scraper::ElementRef) -> Result<String> {
         let a = element
-            .select(&self.a_selector)
+            .select(self.a_selector)
             .next()
             .ok_or_else(|| Error::HtmlMissingElement("a".into()))?;
 
@@ -29,7 +33,7 @@ impl HtmlParser {
     }
 
     fn parse_title(&self, element: &scraper::ElementRef) -> Result<(String, String, usize)> {
-        let mut a_it = element.select(&self.a_selector);
+        let mut a_it = element.select(self.a_selector);
 
         let a0 = a_it
             .next()
@@ -55,12 +59,7 @@ impl HtmlParser {
             .to_string();
 
         let comments = if let Some(comment_a) = comment_a {
-            let comment = comment_a
-                .text()
-                .collect::<Vec<_>>()
-                .join("")
-                .trim()
-                .to_string();
+            let comment = comment_a.text().collect::<String>().trim().to_string();
 
             comment.parse::<usize>()?
         } else {
@@ -71,7 +70,7 @@ impl HtmlParser {
     }
 
     fn parse_download(&self, element: &scraper::ElementRef) -> Result<(String, String)> {
-        let mut a = element.select(&self.a_selector);
+        let mut a = element.select(self.a_selector);
 
         let download = a
             .next()
@@ -93,12 +92,7 @@ impl HtmlParser {
     }
 
     fn parse_size(&self, element: &scraper::ElementRef) -> Result<String> {
-        let size = element
-            .text()
-            .collect::<Vec<_>>()
-            .join("")
-            .trim()
-            .to_string();
+        let size = element.text().collect::<String>().trim().to_string();
 
         Ok(size)
     }
@@ -124,12 +118,7 @@ impl HtmlParser {
         T: std::str::FromStr,
         T::Err: std::fmt::Display,
     {
-        let integer = element
-            .text()
-            .collect::<Vec<_>>()
-            .join("")
-            .trim()
-            .to_string();
+        let integer = element.text().collect::<String>().trim().to_string();
 
         integer
             .parse::<T>()
@@ -137,32 +126,32 @@ impl HtmlParser {
     }
 
     fn parse_tr(&self, element: scraper::ElementRef) -> Result<ListItem> {
-        let td_selector = scraper::Selector::parse("td").unwrap();
-        let td_list = element.select(&td_selector).collect::<Vec<_>>();
+        let columns = &self.layout.columns;
+        let td_list = element.select(self.td_selector).collect::<Vec<_>>();
 
         let category = td_list
-            .get(0)
+            .get(columns.category)
             .ok_or_else(|| Error::HtmlMissingElement("td (category)".into()))?;
         let title = td_list
-            .get(1)
+            .get(columns.name)
             .ok_or_else(|| Error::HtmlMissingElement("td (title)".into()))?;
         let download = td_list
-            .get(2)
+            .get(columns.links)
             .ok_or_else(|| Error::HtmlMissingElement("td (download)".into()))?;
         let size = td_list
-            .get(3)
+            .get(columns.size)
             .ok_or_else(|| Error::HtmlMissingElement("td (size)".into()))?;
         let date = td_list
-            .get(4)
+            .get(columns.date)
             .ok_or_else(|| Error::HtmlMissingElement("td (date)".into()))?;
         let seeders = td_list
-            .get(5)
+            .get(columns.seeders)
             .ok_or_else(|| Error::HtmlMissingElement("td (seeders)".into()))?;
         let leechers = td_list
-            .get(6)
+            .get(columns.leechers)
             .ok_or_else(|| Error::HtmlMissingElement("td (leechers)".into()))?;
         let downloads = td_list
-            .get(7)
+            .get(columns.downloads)
             .ok_or_else(|| Error::HtmlMissingElement("td (downloads)".into()))?;
 
         let category = self.parse_category(category)?;
@@ -177,13 +166,13 @@ impl HtmlParser {
         let trusted = element
             .value()
             .attr("class")
-            .map(|s| s.contains("success"))
+            .map(|s| s.contains(&self.layout.trusted_class))
             .unwrap_or(false);
 
         let remake = element
             .value()
             .attr("class")
-            .map(|s| s.contains("danger"))
+            .map(|s| s.contains(&self.layout.remake_class))
             .unwrap_or(false);
 
         let url = format!("{}{}", self.url.trim_end_matches("/"), url);
@@ -222,17 +211,25 @@ impl HtmlParser {
     }
 }
 
+/// Parses a list page with the nyaa.si layout.
 pub fn parse(url: &str, data: &str) -> Result<Vec<ListItem>> {
+    ParserContext::nyaa().parse_list(url, data)
+}
+
+pub fn parse_document(
+    context: &ParserContext,
+    url: &str,
+    document: &scraper::Html,
+) -> Result<Vec<ListItem>> {
     let parser = HtmlParser {
-        url: url.to_string(),
-        a_selector: scraper::Selector::parse("a").unwrap(),
+        url,
+        layout: &context.profile().list,
+        a_selector: &context.list.a,
+        td_selector: &context.list.td,
     };
 
-    let document = scraper::Html::parse_document(data);
-    let selector = scraper::Selector::parse(".table > tbody:nth-child(2)").unwrap();
     let mut items = Vec::new();
-
-    for element in document.select(&selector) {
+    for element in document.select(&context.list.rows) {
         for child in element.children() {
             if let Some(element) = ElementRef::wrap(child) {
                 if element.value().name() == "tr" {
@@ -325,4 +322,45 @@ mod tests {
         assert_eq!(item.magnet_link, Some("magnet:?xt=urn:btih:84e064742ffe9f5eb4a739766a33d8631746310c&dn=%5BSweetSub%5D%5B%E5%88%B9%E9%82%A3%E4%B9%8B%E8%8A%B1%5D%5BMomentary%20Lily%5D%5B12%5D%5BWebRip%5D%5B1080P%5D%5BAVC%208bit%5D%5B%E7%AE%80%E6%97%A5%E5%86%85%E5%B5%8C%5D&tr=http%3A%2F%2Fnyaa.tracker.wf%3A7777%2Fannounce&tr=udp%3A%2F%2Fopen.stealth.si%3A80%2Fannounce&tr=udp%3A%2F%2Ftracker.opentrackr.org%3A1337%2Fannounce&tr=udp%3A%2F%2Fexodus.desync.com%3A6969%2Fannounce&tr=udp%3A%2F%2Ftracker.torrent.eu.org%3A451%2Fannounce".to_string()));
         assert_eq!(item.info_hash, None);
     }
+
+    #[test]
+    fn test_parse_with_profile() {
+        let html = r#"
+<table class="torrents">
+    <tbody>
+        <tr class="trusted">
+            <td><a href="/?c=1_2"></a></td>
+            <td><a href="/view/42" title="Title">Title</a></td>
+            <td><a href="/download/42.torrent"></a><a href="magnet:?xt=urn:btih:00"></a></td>
+            <td data-timestamp="1743239642">2025-03-29 09:14</td>
+            <td>512 MiB</td>
+            <td>3</td>
+            <td>2</td>
+            <td>1</td>
+        </tr>
+    </tbody>
+</table>
+"#;
+        let mut profile = crate::LayoutProfile::nyaa();
+        profile.list.rows = ".torrents > tbody".into();
+        profile.list.columns.date = 3;
+        profile.list.columns.size = 4;
+        profile.list.trusted_class = "trusted".into();
+
+        let context = crate::ParserContext::new(profile).unwrap();
+        let results = context.parse_list("https://example.org/", html).unwrap();
+        assert_eq!(results.len(), 1);
+        let item = &results[0];
+        assert_eq!(item.id, 42);
+        assert_eq!(item.guid, "https://example.org/view/42");
+        assert_eq!(item.size, 512 * 1024 * 1024);
+        assert_eq!(item.seeders, 3);
+        assert!(item.trusted);
+
+        assert!(
+            super::parse("https://example.org/", html)
+                .unwrap()
+                .is_empty()
+        );
+    }
 }
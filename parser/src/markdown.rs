@@ -0,0 +1,142 @@
+//! Rendering of the markdown nyaa stores for descriptions and comments.
+
+use std::collections::HashSet;
+use std::sync::OnceLock;
+
+use pulldown_cmark::{Event, Options, Parser, TagEnd};
+
+/// Tags allowed in rendered HTML. Anything else, including scripts, styles,
+/// iframes and forms, is stripped.
+const ALLOWED_TAGS: &[&str] = &[
+    "a",
+    "b",
+    "blockquote",
+    "br",
+    "code",
+    "del",
+    "em",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "hr",
+    "i",
+    "img",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "s",
+    "strong",
+    "sub",
+    "sup",
+    "table",
+    "tbody",
+    "td",
+    "th",
+    "thead",
+    "tr",
+    "ul",
+];
+
+fn options() -> Options {
+    Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH
+}
+
+/// nyaa renders line breaks within a paragraph as `<br>`.
+fn events(markdown: &str) -> impl Iterator<Item = Event<'_>> {
+    Parser::new_ext(markdown, options()).map(|event| match event {
+        Event::SoftBreak => Event::HardBreak,
+        event => event,
+    })
+}
+
+fn sanitizer() -> &'static ammonia::Builder<'static> {
+    static SANITIZER: OnceLock<ammonia::Builder<'static>> = OnceLock::new();
+    SANITIZER.get_or_init(|| {
+        let mut builder = ammonia::Builder::empty();
+        builder
+            .tags(ALLOWED_TAGS.iter().copied().collect())
+            .tag_attributes(
+                [
+                    ("a", ["href", "title"].into_iter().collect::<HashSet<_>>()),
+                    ("img", ["src", "alt", "title"].into_iter().collect()),
+                    ("td", ["align"].into_iter().collect()),
+                    ("th", ["align"].into_iter().collect()),
+                ]
+                .into_iter()
+                .collect(),
+            )
+            .url_schemes(["http", "https", "mailto"].into_iter().collect())
+            .link_rel(Some("noopener noreferrer nofollow"));
+        builder
+    })
+}
+
+/// Renders markdown to HTML that is safe to embed: only allowlisted tags and
+/// attributes are kept and links get `rel="noopener noreferrer nofollow"`.
+pub fn to_html(markdown: &str) -> String {
+    let mut html = String::with_capacity(markdown.len() * 3 / 2);
+    pulldown_cmark::html::push_html(&mut html, events(markdown));
+    sanitizer().clean(&html).to_string()
+}
+
+/// Renders markdown to plain text, keeping paragraph and line breaks.
+pub fn to_text(markdown: &str) -> String {
+    fn break_line(text: &mut String, blank: bool) {
+        if text.is_empty() {
+            return;
+        }
+        let wanted = if blank { "\n\n" } else { "\n" };
+        while !text.ends_with(wanted) {
+            text.push('\n');
+        }
+    }
+
+    let mut text = String::with_capacity(markdown.len());
+    for event in events(markdown) {
+        match event {
+            Event::Text(value) | Event::Code(value) => text.push_str(&value),
+            Event::HardBreak | Event::SoftBreak => text.push('\n'),
+            Event::Rule => break_line(&mut text, true),
+            Event::End(
+                TagEnd::Paragraph
+                | TagEnd::Heading(_)
+                | TagEnd::CodeBlock
+                | TagEnd::BlockQuote(_)
+                | TagEnd::List(_)
+                | TagEnd::Table,
+            ) => break_line(&mut text, true),
+            Event::End(TagEnd::Item | TagEnd::TableHead | TagEnd::TableRow) => {
+                break_line(&mut text, false)
+            }
+            Event::End(TagEnd::TableCell) => text.push('\t'),
+            _ => {}
+        }
+    }
+    text.trim_end().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_html() {
+        let html = to_html(
+            "**Bold** [link](https://example.org)\nnext line\n\n<script>alert(1)</script><iframe src=\"https://example.org\"></iframe>\n\n[x](javascript:alert(1))",
+        );
+        assert_eq!(
+            html,
+            "<p><strong>Bold</strong> <a href=\"https://example.org\" rel=\"noopener noreferrer nofollow\">link</a><br>\nnext line</p>\n\n<p><a rel=\"noopener noreferrer nofollow\">x</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn test_to_text() {
+        let text = to_text("# Title\n\n> quoted & **bold**\n\n- one\n- two\n\n`code`");
+        assert_eq!(text, "Title\n\nquoted & bold\n\none\ntwo\n\ncode");
+    }
+}
@@ -0,0 +1,364 @@
+//! Selectors and column mapping describing the markup of a nyaa-compatible
+//! site, so differently themed upstreams can be parsed without code changes.
+
+use scraper::Selector;
+use serde::{Deserialize, Serialize};
+
+use crate::Error;
+use crate::Result;
+
+/// Everything the HTML parsers and the canary need to know about a site's
+/// markup. Missing fields fall back to the nyaa.si layout when deserialized,
+/// so custom profiles only have to list what differs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LayoutProfile {
+    pub name: String,
+    pub list: ListLayout,
+    pub view: ViewLayout,
+    pub user: UserLayout,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListLayout {
+    /// Selector for the torrent table.
+    pub table: String,
+    /// Selector for the `tbody` holding one `tr` per torrent.
+    pub rows: String,
+    /// Selector for the header cells of the torrent table.
+    pub header_cells: String,
+    /// Header classes expected on the torrent table, in column order.
+    pub headers: Vec<String>,
+    pub columns: ListColumns,
+    /// Row class marking torrents from trusted uploaders.
+    pub trusted_class: String,
+    /// Row class marking remakes.
+    pub remake_class: String,
+    /// Text shown instead of the table when a search has no results.
+    pub no_results: String,
+}
+
+/// Index of each `td` in a torrent row.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ListColumns {
+    pub category: usize,
+    pub name: usize,
+    pub links: usize,
+    pub size: usize,
+    pub date: usize,
+    pub seeders: usize,
+    pub leechers: usize,
+    pub downloads: usize,
+    /// Number of `td` cells every row is expected to have.
+    pub count: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewLayout {
+    pub title: String,
+    pub timestamp: String,
+    pub download: String,
+    pub magnet: String,
+    pub category: String,
+    pub description: String,
+    /// Selector for the rows of label/value pairs in the torrent panel.
+    pub info_row: String,
+    /// Selector for the label and value cells within an info row.
+    pub info_cell: String,
+    pub labels: ViewLabels,
+    /// Selector for the container of the nested file list.
+    pub file_list: String,
+    /// Selector for the name element of a folder entry.
+    pub folder: String,
+    /// Selector for the size element of a file entry.
+    pub file_size: String,
+    pub trusted: String,
+    pub remake: String,
+    pub comment: String,
+    pub comment_user: String,
+    pub comment_content: String,
+    pub comment_avatar: String,
+}
+
+/// Panel labels preceding each value on the view page.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewLabels {
+    pub seeders: String,
+    pub leechers: String,
+    pub completed: String,
+    pub submitter: String,
+    pub info_hash: String,
+    pub file_size: String,
+    /// Label of the optional information field. Not checked by the canary.
+    pub information: String,
+}
+
+/// Header of an uploader's page. The torrent table below it uses the list
+/// layout.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct UserLayout {
+    /// Selector for the element holding the user name, with the user level
+    /// in its `title` attribute.
+    pub name: String,
+    pub avatar: String,
+    /// Selector for badges shown next to the user name.
+    pub badges: String,
+    /// User levels whose uploads are marked as trusted.
+    pub trusted_levels: Vec<String>,
+}
+
+impl Default for LayoutProfile {
+    fn default() -> Self {
+        Self::nyaa()
+    }
+}
+
+impl Default for ListLayout {
+    fn default() -> Self {
+        Self {
+            table: ".torrent-list".into(),
+            rows: ".table > tbody:nth-child(2)".into(),
+            header_cells: ".torrent-list thead th".into(),
+            headers: [
+                "hdr-category",
+                "hdr-name",
+                "hdr-comments",
+                "hdr-link",
+                "hdr-size",
+                "hdr-date",
+                "hdr-seeders",
+                "hdr-leechers",
+                "hdr-downloads",
+            ]
+            .into_iter()
+            .map(String::from)
+            .collect(),
+            columns: ListColumns::default(),
+            trusted_class: "success".into(),
+            remake_class: "danger".into(),
+            no_results: "No results found".into(),
+        }
+    }
+}
+
+impl Default for ListColumns {
+    fn default() -> Self {
+        Self {
+            category: 0,
+            name: 1,
+            links: 2,
+            size: 3,
+            date: 4,
+            seeders: 5,
+            leechers: 6,
+            downloads: 7,
+            count: 8,
+        }
+    }
+}
+
+impl Default for ViewLayout {
+    fn default() -> Self {
+        Self {
+            title: ".panel-title".into(),
+            timestamp: "[data-timestamp]".into(),
+            download: "a[href^='/download/']".into(),
+            magnet: "a[href^='magnet:']".into(),
+            category: ".col-md-5 a[href^='/?c=']".into(),
+            description: "#torrent-description".into(),
+            info_row: ".row".into(),
+            info_cell: "div".into(),
+            labels: ViewLabels::default(),
+            file_list: ".torrent-file-list".into(),
+            folder: "a.folder".into(),
+            file_size: ".file-size".into(),
+            trusted: ".panel-success".into(),
+            remake: ".panel-danger".into(),
+            comment: ".comment-panel".into(),
+            comment_user: ".col-md-2 a".into(),
+            comment_content: ".comment-content".into(),
+            comment_avatar: ".avatar".into(),
+        }
+    }
+}
+
+impl Default for UserLayout {
+    fn default() -> Self {
+        Self {
+            name: "h3 > span[data-toggle]".into(),
+            avatar: "img.avatar".into(),
+            badges: "h3 > .label, h3 > .badge".into(),
+            trusted_levels: ["Trusted", "Moderator", "Administrator"]
+                .into_iter()
+                .map(String::from)
+                .collect(),
+        }
+    }
+}
+
+impl Default for ViewLabels {
+    fn default() -> Self {
+        Self {
+            seeders: "Seeders:".into(),
+            leechers: "Leechers:".into(),
+            completed: "Completed:".into(),
+            submitter: "Submitter:".into(),
+            info_hash: "Info Hash:".into(),
+            file_size: "File size:".into(),
+            information: "Information:".into(),
+        }
+    }
+}
+
+impl ViewLabels {
+    pub fn all(&self) -> [&str; 6] {
+        [
+            &self.seeders,
+            &self.leechers,
+            &self.completed,
+            &self.submitter,
+            &self.info_hash,
+            &self.file_size,
+        ]
+    }
+}
+
+impl LayoutProfile {
+    /// Layout of nyaa.si.
+    pub fn nyaa() -> Self {
+        Self {
+            name: "nyaa".into(),
+            list: ListLayout::default(),
+            view: ViewLayout::default(),
+            user: UserLayout::default(),
+        }
+    }
+
+    /// Layout of sukebei.nyaa.si, which runs the same software as nyaa.si.
+    pub fn sukebei() -> Self {
+        Self {
+            name: "sukebei".into(),
+            ..Self::nyaa()
+        }
+    }
+
+    /// Looks up a built-in profile by name.
+    pub fn builtin(name: &str) -> Option<Self> {
+        match name {
+            "nyaa" => Some(Self::nyaa()),
+            "sukebei" => Some(Self::sukebei()),
+            _ => None,
+        }
+    }
+
+    /// Checks that every selector parses and that the column mapping fits
+    /// within the expected column count.
+    pub fn validate(&self) -> Result<()> {
+        let list = &self.list;
+        let view = &self.view;
+        let user = &self.user;
+        for selector in [&list.table, &list.rows, &list.header_cells]
+            .into_iter()
+            .chain([
+                &view.title,
+                &view.timestamp,
+                &view.download,
+                &view.magnet,
+                &view.category,
+                &view.description,
+                &view.info_row,
+                &view.info_cell,
+                &view.file_list,
+                &view.folder,
+                &view.file_size,
+                &view.trusted,
+                &view.remake,
+                &view.comment,
+                &view.comment_user,
+                &view.comment_content,
+                &view.comment_avatar,
+            ])
+            .chain([&user.name, &user.avatar, &user.badges])
+        {
+            selector_from(selector)?;
+        }
+
+        let columns = &list.columns;
+        for (name, index) in [
+            ("category", columns.category),
+            ("name", columns.name),
+            ("links", columns.links),
+            ("size", columns.size),
+            ("date", columns.date),
+            ("seeders", columns.seeders),
+            ("leechers", columns.leechers),
+            ("downloads", columns.downloads),
+        ] {
+            if index >= columns.count {
+                return Err(Error::InvalidProfile(format!(
+                    "column {} ({}) is out of range for {} columns",
+                    name, index, columns.count
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn selector_from(selector: &str) -> Result<Selector> {
+    Selector::parse(selector)
+        .map_err(|e| Error::InvalidSelector(selector.to_string(), e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_partial_profile() {
+        let profile: LayoutProfile = toml::from_str(
+            r#"
+name = "custom"
+
+[list]
+rows = ".torrents > tbody"
+
+[list.columns]
+downloads = 8
+count = 9
+"#,
+        )
+        .unwrap();
+        assert_eq!(profile.name, "custom");
+        assert_eq!(profile.list.rows, ".torrents > tbody");
+        assert_eq!(profile.list.columns.downloads, 8);
+        assert_eq!(profile.list.columns.seeders, 5);
+        assert_eq!(profile.view, ViewLayout::default());
+        profile.validate().unwrap();
+
+        let mut invalid = LayoutProfile::nyaa();
+        invalid.view.title = "..".into();
+        assert!(matches!(
+            invalid.validate(),
+            Err(Error::InvalidSelector(..))
+        ));
+        invalid = LayoutProfile::nyaa();
+        invalid.list.columns.count = 7;
+        assert!(matches!(invalid.validate(), Err(Error::InvalidProfile(_))));
+    }
+
+    #[test]
+    fn test_builtin_profiles() {
+        for name in ["nyaa", "sukebei"] {
+            let profile = LayoutProfile::builtin(name).unwrap();
+            assert_eq!(profile.name, name);
+            profile.validate().unwrap();
+        }
+        assert!(LayoutProfile::builtin("anidex").is_none());
+    }
+}
@@ -0,0 +1,466 @@
+//! Typed search parameters and the upstream URLs they map to.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use crate::Error;
+use crate::Result;
+
+/// Column results are sorted by.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Sort {
+    /// Upload order.
+    #[default]
+    Id,
+    Size,
+    Seeders,
+    Leechers,
+    Downloads,
+    Comments,
+}
+
+impl Sort {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Sort::Id => "id",
+            Sort::Size => "size",
+            Sort::Seeders => "seeders",
+            Sort::Leechers => "leechers",
+            Sort::Downloads => "downloads",
+            Sort::Comments => "comments",
+        }
+    }
+}
+
+impl FromStr for Sort {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "id" => Ok(Sort::Id),
+            "size" => Ok(Sort::Size),
+            "seeders" => Ok(Sort::Seeders),
+            "leechers" => Ok(Sort::Leechers),
+            "downloads" => Ok(Sort::Downloads),
+            "comments" => Ok(Sort::Comments),
+            _ => Err(Error::ParseQuery(format!("unknown sort: {:?}", s))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Order {
+    Asc,
+    #[default]
+    Desc,
+}
+
+impl Order {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Order::Asc => "asc",
+            Order::Desc => "desc",
+        }
+    }
+}
+
+impl FromStr for Order {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "asc" => Ok(Order::Asc),
+            "desc" => Ok(Order::Desc),
+            _ => Err(Error::ParseQuery(format!("unknown order: {:?}", s))),
+        }
+    }
+}
+
+/// Which torrents to hide. Serialized as nyaa's `f` values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Filter {
+    #[default]
+    #[serde(rename = "0")]
+    NoFilter,
+    #[serde(rename = "1")]
+    NoRemakes,
+    #[serde(rename = "2")]
+    TrustedOnly,
+}
+
+impl Filter {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Filter::NoFilter => "0",
+            Filter::NoRemakes => "1",
+            Filter::TrustedOnly => "2",
+        }
+    }
+}
+
+impl FromStr for Filter {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "0" => Ok(Filter::NoFilter),
+            "1" => Ok(Filter::NoRemakes),
+            "2" => Ok(Filter::TrustedOnly),
+            _ => Err(Error::ParseQuery(format!("unknown filter: {:?}", s))),
+        }
+    }
+}
+
+/// A category as `main_sub`, with `0` standing for all. The codes are kept
+/// numeric because nyaa and sukebei assign different meanings to them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Category {
+    pub main: u8,
+    pub sub: u8,
+}
+
+impl Category {
+    pub const ALL: Category = Category { main: 0, sub: 0 };
+
+    pub fn new(main: u8, sub: u8) -> Self {
+        Self { main, sub }
+    }
+
+    pub fn is_all(&self) -> bool {
+        *self == Self::ALL
+    }
+
+    /// Whether listing this category includes torrents of `other`, such as
+    /// `1_0` including `1_2`.
+    pub fn contains(&self, other: &Category) -> bool {
+        self.is_all() || (self.main == other.main && (self.sub == 0 || self.sub == other.sub))
+    }
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}_{}", self.main, self.sub)
+    }
+}
+
+impl FromStr for Category {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::ParseCategory(s.to_string());
+        let (main, sub) = s.split_once('_').ok_or_else(invalid)?;
+        let category = Category {
+            main: main.parse().map_err(|_| invalid())?,
+            sub: sub.parse().map_err(|_| invalid())?,
+        };
+        if category.main == 0 && category.sub != 0 {
+            return Err(invalid());
+        }
+        Ok(category)
+    }
+}
+
+impl Serialize for Category {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Category {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// A search on a nyaa-compatible site, optionally scoped to an uploader.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SearchQuery {
+    pub term: Option<String>,
+    pub category: Category,
+    pub filter: Filter,
+    pub sort: Sort,
+    pub order: Order,
+    /// 1-based page number.
+    #[serde(deserialize_with = "deserialize_page")]
+    pub page: usize,
+    /// Uploader whose torrents to list.
+    pub user: Option<String>,
+    /// Request the RSS feed instead of the HTML page. Feeds are always
+    /// newest first and unpaginated, so sort, order and page are not sent.
+    pub rss: bool,
+}
+
+/// Clamps deserialized pages like [`SearchQuery::page`] does, so page 0
+/// reads as the first page.
+fn deserialize_page<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<usize, D::Error> {
+    usize::deserialize(deserializer).map(|page| page.max(1))
+}
+
+impl Default for SearchQuery {
+    fn default() -> Self {
+        Self {
+            term: None,
+            category: Category::ALL,
+            filter: Filter::NoFilter,
+            sort: Sort::Id,
+            order: Order::Desc,
+            page: 1,
+            user: None,
+            rss: false,
+        }
+    }
+}
+
+impl SearchQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn term(mut self, term: impl Into<String>) -> Self {
+        let term = term.into();
+        self.term = Some(term).filter(|term| !term.is_empty());
+        self
+    }
+
+    pub fn category(mut self, category: Category) -> Self {
+        self.category = category;
+        self
+    }
+
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn sort(mut self, sort: Sort, order: Order) -> Self {
+        self.sort = sort;
+        self.order = order;
+        self
+    }
+
+    pub fn page(mut self, page: usize) -> Self {
+        self.page = page.max(1);
+        self
+    }
+
+    pub fn user(mut self, user: impl Into<String>) -> Self {
+        let user = user.into();
+        self.user = Some(user).filter(|user| !user.is_empty());
+        self
+    }
+
+    pub fn rss(mut self, rss: bool) -> Self {
+        self.rss = rss;
+        self
+    }
+
+    /// Whether the results hold every torrent between their lowest and
+    /// highest id: the unfiltered listing, newest first.
+    pub fn is_complete_listing(&self) -> bool {
+        self.term.is_none()
+            && self.category.is_all()
+            && self.filter == Filter::NoFilter
+            && (self.rss || (self.sort == Sort::Id && self.order == Order::Desc))
+            && self.user.is_none()
+    }
+
+    /// Builds the upstream URL for this query on the site at `base`.
+    /// Parameters left at their defaults are omitted.
+    pub fn to_url(&self, base: &Url) -> Url {
+        let mut url = base.clone();
+        url.set_fragment(None);
+        url.set_query(None);
+        if let Ok(mut segments) = url.path_segments_mut() {
+            segments.clear();
+            if let (Some(user), false) = (&self.user, self.rss) {
+                segments.push("user").push(user);
+            } else {
+                segments.push("");
+            }
+        }
+
+        let mut pairs = Vec::new();
+        if self.rss {
+            pairs.push(("page", "rss".to_string()));
+        }
+        if self.filter != Filter::NoFilter {
+            pairs.push(("f", self.filter.as_str().to_string()));
+        }
+        if !self.category.is_all() {
+            pairs.push(("c", self.category.to_string()));
+        }
+        if let Some(term) = &self.term {
+            pairs.push(("q", term.clone()));
+        }
+        if self.rss {
+            if let Some(user) = &self.user {
+                pairs.push(("u", user.clone()));
+            }
+        } else {
+            if self.sort != Sort::Id {
+                pairs.push(("s", self.sort.as_str().to_string()));
+            }
+            if self.order != Order::Desc {
+                pairs.push(("o", self.order.as_str().to_string()));
+            }
+            if self.page > 1 {
+                pairs.push(("p", self.page.to_string()));
+            }
+        }
+
+        if !pairs.is_empty() {
+            url.query_pairs_mut().extend_pairs(pairs);
+        }
+        url
+    }
+
+    /// Reads a query back from a search, user or RSS URL. Unknown
+    /// parameters are ignored; known ones with invalid values are errors.
+    pub fn from_url(url: &Url) -> Result<Self> {
+        let mut query = Self::default();
+
+        let segments = url
+            .path_segments()
+            .map(|segments| segments.filter(|s| !s.is_empty()).collect::<Vec<_>>())
+            .unwrap_or_default();
+        match segments.as_slice() {
+            [] => {}
+            ["user", user] => {
+                let user = percent_encoding::percent_decode_str(user)
+                    .decode_utf8()
+                    .map_err(|_| Error::ParseString(user.to_string()))?;
+                query = query.user(user);
+            }
+            _ => {
+                return Err(Error::ParseQuery(format!(
+                    "not a search URL: {}",
+                    url.path()
+                )));
+            }
+        }
+
+        for (key, value) in url.query_pairs() {
+            match key.as_ref() {
+                "page" => query.rss = value == "rss",
+                "f" => query.filter = value.parse()?,
+                "c" => query.category = value.parse()?,
+                "q" => query = query.term(value),
+                "s" => query.sort = value.parse()?,
+                "o" => query.order = value.parse()?,
+                "p" => {
+                    let page = value
+                        .parse::<usize>()
+                        .map_err(|_| Error::ParseInteger(value.to_string()))?;
+                    query = query.page(page);
+                }
+                "u" => query = query.user(value),
+                _ => {}
+            }
+        }
+        Ok(query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Url {
+        Url::parse("https://nyaa.si").unwrap()
+    }
+
+    #[test]
+    fn test_to_url() {
+        assert_eq!(
+            SearchQuery::new().to_url(&base()).as_str(),
+            "https://nyaa.si/"
+        );
+
+        let query = SearchQuery::new()
+            .term("one piece & more")
+            .category(Category::new(1, 2))
+            .filter(Filter::TrustedOnly)
+            .sort(Sort::Seeders, Order::Asc)
+            .page(3);
+        assert_eq!(
+            query.to_url(&base()).as_str(),
+            "https://nyaa.si/?f=2&c=1_2&q=one+piece+%26+more&s=seeders&o=asc&p=3"
+        );
+
+        let query = SearchQuery::new().user("Some User").term("1080p");
+        assert_eq!(
+            query.to_url(&base()).as_str(),
+            "https://nyaa.si/user/Some%20User?q=1080p"
+        );
+        assert_eq!(
+            query.rss(true).to_url(&base()).as_str(),
+            "https://nyaa.si/?page=rss&q=1080p&u=Some+User"
+        );
+    }
+
+    #[test]
+    fn test_category_contains() {
+        assert!(Category::ALL.contains(&Category::new(2, 1)));
+        assert!(Category::new(1, 0).contains(&Category::new(1, 2)));
+        assert!(Category::new(1, 2).contains(&Category::new(1, 2)));
+        assert!(!Category::new(1, 2).contains(&Category::new(1, 3)));
+        assert!(!Category::new(1, 0).contains(&Category::new(2, 0)));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let queries = [
+            SearchQuery::new(),
+            SearchQuery::new()
+                .term("[Group] Show - 01")
+                .category(Category::new(1, 0))
+                .filter(Filter::NoRemakes)
+                .sort(Sort::Size, Order::Desc)
+                .page(2),
+            SearchQuery::new()
+                .user("uploader_name")
+                .sort(Sort::Comments, Order::Asc),
+            SearchQuery::new().user("ünïcödé user").page(5),
+            SearchQuery::new().user("a+b&c=d%"),
+            SearchQuery::new()
+                .rss(true)
+                .user("uploader")
+                .term("batch")
+                .category(Category::new(2, 1)),
+        ];
+        for query in queries {
+            let url = query.to_url(&base());
+            assert_eq!(SearchQuery::from_url(&url).unwrap(), query, "{}", url);
+        }
+
+        let query: SearchQuery = toml::from_str("page = 0").unwrap();
+        assert_eq!(query, SearchQuery::new());
+
+        let url = Url::parse("https://nyaa.si/?f=0&c=0_0&q=&s=id&o=desc").unwrap();
+        assert_eq!(SearchQuery::from_url(&url).unwrap(), SearchQuery::new());
+
+        for invalid in [
+            "https://nyaa.si/?s=name",
+            "https://nyaa.si/?c=0_1",
+            "https://nyaa.si/?p=x",
+            "https://nyaa.si/view/1",
+        ] {
+            let url = Url::parse(invalid).unwrap();
+            assert!(SearchQuery::from_url(&url).is_err(), "{}", invalid);
+        }
+    }
+}
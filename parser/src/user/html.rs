@@ -0,0 +1,119 @@
+use scraper::Html;
+
+use crate::Error;
+use crate::ParserContext;
+use crate::Result;
+use crate::UserPage;
+use crate::UserProfile;
+
+/// Parses a user page with the nyaa.si layout.
+pub fn parse(url: &str, data: &str) -> Result<UserPage> {
+    ParserContext::nyaa().parse_user(url, data)
+}
+
+pub fn parse_document(context: &ParserContext, url: &str, document: &Html) -> Result<UserPage> {
+    let layout = &context.profile().user;
+    let selectors = &context.user;
+
+    let name_element = document
+        .select(&selectors.name)
+        .next()
+        .ok_or_else(|| Error::HtmlMissingElement(layout.name.clone()))?;
+    let name = name_element.text().collect::<String>().trim().to_string();
+    let level = name_element
+        .value()
+        .attr("title")
+        .map(|title| title.trim().to_string())
+        .filter(|title| !title.is_empty());
+    let trusted = level
+        .as_ref()
+        .is_some_and(|level| layout.trusted_levels.contains(level));
+
+    // nyaa appends the number of matching torrents to the heading, as in
+    // "Browsing user's torrents (123)".
+    let torrent_count = name_element
+        .parent()
+        .and_then(scraper::ElementRef::wrap)
+        .and_then(|heading| {
+            let text = heading.text().collect::<String>();
+            let (_, count) = text.rsplit_once('(')?;
+            count
+                .split_once(')')?
+                .0
+                .trim()
+                .replace(',', "")
+                .parse()
+                .ok()
+        });
+
+    let mut badges = level.iter().cloned().collect::<Vec<_>>();
+    for badge in document.select(&selectors.badges) {
+        let badge = badge.text().collect::<String>().trim().to_string();
+        if !badge.is_empty() && !badges.contains(&badge) {
+            badges.push(badge);
+        }
+    }
+
+    let avatar = document
+        .select(&selectors.avatar)
+        .next()
+        .and_then(|el| el.value().attr("src"))
+        .filter(|src| !src.contains("default.png"))
+        .map(str::to_string);
+
+    let items = crate::list::html::parse_document(context, url, document)?;
+
+    Ok(UserPage {
+        user: UserProfile {
+            name,
+            level,
+            trusted,
+            badges,
+            avatar,
+            torrent_count,
+        },
+        items,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_profile() {
+        let html = r#"
+<div class="container">
+    <div class="row">
+        <div class="col-md-2">
+            <img class="avatar" src="https://i.example.org/avatar/group.png">
+        </div>
+        <div class="col-md-10">
+            <h3>
+                Browsing <span class="text-success" data-toggle="tooltip" title="Trusted">group</span>'s torrents (1,234)
+                <span class="label label-info">A+</span>
+            </h3>
+        </div>
+    </div>
+    <div class="table-responsive">
+        <table class="table table-bordered table-hover table-striped torrent-list">
+            <thead><tr><th class="hdr-category">Category</th></tr></thead>
+            <tbody></tbody>
+        </table>
+    </div>
+</div>
+"#;
+        let page = parse("https://nyaa.si", html).unwrap();
+        let user = page.user;
+        assert_eq!(user.name, "group");
+        assert_eq!(user.level.as_deref(), Some("Trusted"));
+        assert!(user.trusted);
+        assert_eq!(user.badges, vec!["Trusted", "A+"]);
+        assert_eq!(
+            user.avatar.as_deref(),
+            Some("https://i.example.org/avatar/group.png")
+        );
+        assert_eq!(user.torrent_count, Some(1234));
+        assert!(page.items.is_empty());
+    }
+}
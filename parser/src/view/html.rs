@@ -1,21 +1,38 @@
 use chrono::TimeZone;
-use scraper::{Html, Selector};
+use scraper::{ElementRef, Html};
 
 use crate::Error;
+use crate::ExternalIds;
+use crate::FileNode;
+use crate::ParserContext;
 use crate::Result;
 use crate::View;
 use crate::ViewComment;
-use crate::ViewFile;
 use crate::parse_size;
 
+/// Parses a view page with the nyaa.si layout.
 pub fn parse(url: &str, data: &str) -> Result<View> {
-    let data = data.replace("\t", " ").replace("\n", " ");
+    ParserContext::nyaa().parse_view(url, data)
+}
+
+/// Tabs and line breaks in extracted text are turned into spaces.
+fn flatten(value: &str) -> String {
+    value.replace(['\t', '\n'], " ")
+}
+
+/// nyaa ships descriptions and comments as markdown in the text of an
+/// element and renders them client-side; the text nodes hold the decoded
+/// source.
+fn markdown_source(element: &ElementRef) -> String {
+    element.text().collect::<String>().trim().to_string()
+}
 
-    let document = Html::parse_document(&data);
+pub fn parse_document(context: &ParserContext, url: &str, document: &Html) -> Result<View> {
+    let layout = &context.profile().view;
+    let selectors = &context.view;
 
-    let download_selector = Selector::parse("a[href^='/download/']").unwrap();
     let id = document
-        .select(&download_selector)
+        .select(&selectors.download)
         .next()
         .and_then(|el| el.value().attr("href"))
         .ok_or_else(|| Error::HtmlMissingAttribute("href".into()))?;
@@ -31,18 +48,15 @@ pub fn parse(url: &str, data: &str) -> Result<View> {
         .parse::<usize>()
         .map_err(|_| Error::ParseInteger(id.clone()))?;
 
-    let title_selector = Selector::parse(".panel-title").unwrap();
     let title = document
-        .select(&title_selector)
+        .select(&selectors.title)
         .next()
         .and_then(|el| el.text().next())
-        .ok_or_else(|| Error::HtmlMissingElement(".panel-title".into()))?
-        .trim()
-        .to_string();
+        .ok_or_else(|| Error::HtmlMissingElement(layout.title.clone()))?;
+    let title = flatten(title.trim());
 
-    let date_selector = Selector::parse("[data-timestamp]").unwrap();
     let timestamp = document
-        .select(&date_selector)
+        .select(&selectors.timestamp)
         .next()
         .and_then(|el| el.value().attr("data-timestamp"))
         .ok_or_else(|| Error::HtmlMissingAttribute("data-timestamp".into()))?
@@ -50,16 +64,16 @@ pub fn parse(url: &str, data: &str) -> Result<View> {
 
     let date = chrono::Utc.timestamp_opt(timestamp, 0).unwrap();
 
-    let seeders = parse_number(&document, "Seeders:")?;
-    let leechers = parse_number(&document, "Leechers:")?;
-    let downloads = parse_number(&document, "Completed:")?;
+    let labels = &layout.labels;
+    let seeders = parse_number(document, context, &labels.seeders)?;
+    let leechers = parse_number(document, context, &labels.leechers)?;
+    let downloads = parse_number(document, context, &labels.completed)?;
 
-    let submitter = parse_string(&document, "Submitter:")?;
-    let info_hash = parse_string(&document, "Info Hash:")?;
+    let submitter = parse_string(document, context, &labels.submitter)?;
+    let info_hash = parse_string(document, context, &labels.info_hash)?;
 
-    let category_selector = Selector::parse(".col-md-5 a[href^='/?c=']").unwrap();
     let category_value = document
-        .select(&category_selector)
+        .select(&selectors.category)
         .last()
         .and_then(|el| el.value().attr("href"))
         .ok_or_else(|| Error::HtmlMissingAttribute("href".into()))?;
@@ -73,42 +87,34 @@ pub fn parse(url: &str, data: &str) -> Result<View> {
 
     let category = category_value.to_string();
 
-    let file_size = parse_file_size(&document)?;
+    let file_size = parse_file_size(document, context)?;
 
-    let magnet_selector = Selector::parse("a[href^='magnet:']").unwrap();
     let magnet_link = document
-        .select(&magnet_selector)
+        .select(&selectors.magnet)
         .next()
         .and_then(|el| el.value().attr("href"))
         .ok_or_else(|| Error::HtmlMissingAttribute("href".into()))?
         .to_string();
 
-    let description_selector = Selector::parse("#torrent-description").unwrap();
     let description = document
-        .select(&description_selector)
+        .select(&selectors.description)
         .next()
-        .map(|el| el.inner_html())
+        .map(|el| markdown_source(&el))
         .unwrap_or_default();
 
-    let files = parse_files(&document)?;
+    let information = parse_information(document, context);
+    let mut external_ids = ExternalIds::default();
+    if let Some(information) = &information {
+        external_ids.extend_from(information);
+    }
+    external_ids.extend_from(&description);
 
-    let comments = parse_comments(&document)?;
+    let file_tree = parse_files(document, context)?;
 
-    let panel_danger_selector = Selector::parse(".panel-danger").unwrap();
-    let panel_success_selector = Selector::parse(".panel-success").unwrap();
+    let comments = parse_comments(document, context)?;
 
-    let trusted = document
-        .select(&panel_success_selector)
-        .next()
-        .iter()
-        .count()
-        > 0;
-    let remake = document
-        .select(&panel_danger_selector)
-        .next()
-        .iter()
-        .count()
-        > 0;
+    let trusted = document.select(&selectors.trusted).next().is_some();
+    let remake = document.select(&selectors.remake).next().is_some();
 
     let guid = format!("{}/view/{}", url.trim_end_matches("/"), id);
     let download_link = format!("{}/download/{}.torrent", url.trim_end_matches("/"), id);
@@ -129,19 +135,24 @@ pub fn parse(url: &str, data: &str) -> Result<View> {
         magnet_link: Some(magnet_link),
         download_link: Some(download_link),
         description_md: description,
-        files,
+        file_tree,
         comments,
         submitter,
         info_hash,
+        information,
+        external_ids,
     })
 }
 
-fn find_value(document: &Html, label: &str) -> Result<String> {
+/// Finds the value cell following the cell containing `label`.
+fn find_cell<'a>(
+    document: &'a Html,
+    context: &ParserContext,
+    label: &str,
+) -> Result<ElementRef<'a>> {
     let label = label.to_lowercase();
-    let selector = Selector::parse(".row").unwrap();
-    let child_selector = Selector::parse("div").unwrap();
-    for row in document.select(&selector) {
-        let mut children = row.select(&child_selector);
+    for row in document.select(&context.view.info_row) {
+        let mut children = row.select(&context.view.info_cell);
         while let Some(child) = children.next() {
             let text = child
                 .text()
@@ -150,15 +161,9 @@ fn find_value(document: &Html, label: &str) -> Result<String> {
                 .to_lowercase()
                 .to_string();
             if text.contains(&label) {
-                let next_child = children.next();
-                if let Some(next) = next_child {
-                    let number_text = next.text().collect::<String>();
-                    return Ok(number_text.trim().to_string());
-                }
-                return Err(Error::HtmlMissingElement(format!(
-                    "No number found after label: {}",
-                    label
-                )));
+                return children.next().ok_or_else(|| {
+                    Error::HtmlMissingElement(format!("No number found after label: {}", label))
+                });
             }
         }
     }
@@ -168,53 +173,113 @@ fn find_value(document: &Html, label: &str) -> Result<String> {
     )))
 }
 
-fn parse_number(document: &Html, label: &str) -> Result<usize> {
-    find_value(document, label).and_then(|value| {
+pub(crate) fn find_value(document: &Html, context: &ParserContext, label: &str) -> Result<String> {
+    find_cell(document, context, label).map(|cell| flatten(cell.text().collect::<String>().trim()))
+}
+
+/// The information field holds a link, or a placeholder text when the
+/// uploader left it empty.
+fn parse_information(document: &Html, context: &ParserContext) -> Option<String> {
+    let label = &context.profile().view.labels.information;
+    let cell = find_cell(document, context, label).ok()?;
+    cell.select(&context.list.a)
+        .find_map(|a| a.value().attr("href"))
+        .map(|href| href.trim().to_string())
+        .filter(|href| href.starts_with("http://") || href.starts_with("https://"))
+}
+
+fn parse_number(document: &Html, context: &ParserContext, label: &str) -> Result<usize> {
+    find_value(document, context, label).and_then(|value| {
         value
             .parse::<usize>()
             .map_err(|_| Error::ParseInteger(value.clone()))
     })
 }
 
-fn parse_string(document: &Html, label: &str) -> Result<String> {
-    find_value(document, label)
+fn parse_string(document: &Html, context: &ParserContext, label: &str) -> Result<String> {
+    find_value(document, context, label)
 }
 
-fn parse_file_size(document: &Html) -> Result<u64> {
-    find_value(document, "File size:").and_then(|value| parse_size(&value))
+fn parse_file_size(document: &Html, context: &ParserContext) -> Result<u64> {
+    let label = &context.profile().view.labels.file_size;
+    find_value(document, context, label).and_then(|value| parse_size(&value))
 }
 
-fn parse_files(document: &Html) -> Result<Vec<ViewFile>> {
-    let mut files = Vec::new();
-    let file_selector = Selector::parse(".torrent-file-list li:not(:has(ul))").unwrap();
-
-    for (index, file_element) in document.select(&file_selector).enumerate() {
-        let file_text = file_element.text().collect::<String>();
-        let parts: Vec<&str> = file_text.split('(').collect();
-
-        if parts.len() >= 2 {
-            let name = parts[0].trim().to_string();
-            let size_part = parts.last().unwrap().trim();
-            let size_text = size_part.trim_end_matches(')');
+fn parse_files(document: &Html, context: &ParserContext) -> Result<Vec<FileNode>> {
+    let Some(file_list) = document.select(&context.view.file_list).next() else {
+        return Ok(Vec::new());
+    };
+    let mut nodes = Vec::new();
+    for ul in child_elements(file_list, "ul") {
+        nodes.extend(parse_file_nodes(ul, context)?);
+    }
+    Ok(nodes)
+}
 
-            let size = parse_size(size_text)?;
+fn child_elements<'a>(
+    element: ElementRef<'a>,
+    name: &'a str,
+) -> impl Iterator<Item = ElementRef<'a>> + 'a {
+    element
+        .children()
+        .filter_map(ElementRef::wrap)
+        .filter(move |child| child.value().name() == name)
+}
 
-            files.push(ViewFile {
-                id: index,
-                name,
-                size,
+/// Parses the `li` entries of a `ul`. Entries holding a nested `ul` are
+/// folders, all others are files. Files listed without a size are recorded
+/// with size 0, and entries without a name are skipped.
+fn parse_file_nodes(ul: ElementRef, context: &ParserContext) -> Result<Vec<FileNode>> {
+    let selectors = &context.view;
+    let mut nodes = Vec::new();
+    for li in child_elements(ul, "li") {
+        if let Some(children_ul) = child_elements(li, "ul").next() {
+            let name = li
+                .children()
+                .filter_map(ElementRef::wrap)
+                .find(|child| selectors.folder.matches(child))
+                .map(|folder| folder.text().collect::<String>())
+                .unwrap_or_default();
+            nodes.push(FileNode::Folder {
+                name: flatten(name.trim()),
+                children: parse_file_nodes(children_ul, context)?,
             });
+            continue;
         }
-    }
 
-    Ok(files)
+        let mut name = String::new();
+        let mut size = None;
+        for child in li.children() {
+            match ElementRef::wrap(child) {
+                Some(element) if selectors.file_size.matches(&element) => {
+                    size = Some(element.text().collect::<String>());
+                }
+                Some(element) => name.extend(element.text()),
+                None => {
+                    if let Some(text) = child.value().as_text() {
+                        name.push_str(text);
+                    }
+                }
+            }
+        }
+        let name = flatten(name.trim());
+        if name.is_empty() {
+            continue;
+        }
+        let size = match size {
+            Some(size) => parse_size(size.trim().trim_start_matches('(').trim_end_matches(')'))?,
+            None => 0,
+        };
+        nodes.push(FileNode::File { name, size });
+    }
+    Ok(nodes)
 }
 
-fn parse_comments(document: &Html) -> Result<Vec<ViewComment>> {
+fn parse_comments(document: &Html, context: &ParserContext) -> Result<Vec<ViewComment>> {
+    let selectors = &context.view;
     let mut comments = Vec::new();
-    let comment_selector = Selector::parse(".comment-panel").unwrap();
 
-    for comment_element in document.select(&comment_selector) {
+    for comment_element in document.select(&selectors.comment) {
         let id = comment_element
             .value()
             .attr("id")
@@ -225,17 +290,14 @@ fn parse_comments(document: &Html) -> Result<Vec<ViewComment>> {
             .parse::<usize>()
             .map_err(|_| Error::ParseInteger(id.clone()))?;
 
-        let user_selector = Selector::parse(".col-md-2 a").unwrap();
         let user = comment_element
-            .select(&user_selector)
+            .select(&selectors.comment_user)
             .next()
             .and_then(|el| el.text().next())
-            .unwrap_or("Anonymous")
-            .trim()
-            .to_string();
+            .unwrap_or("Anonymous");
+        let user = flatten(user.trim());
 
-        let timestamp_selector = Selector::parse("[data-timestamp]").unwrap();
-        let mut timestamp_it = comment_element.select(&timestamp_selector);
+        let mut timestamp_it = comment_element.select(&selectors.timestamp);
         let timestamp = timestamp_it
             .next()
             .and_then(|el| el.value().attr("data-timestamp"))
@@ -269,16 +331,14 @@ fn parse_comments(document: &Html) -> Result<Vec<ViewComment>> {
             None
         };
 
-        let content_selector = Selector::parse(".comment-content").unwrap();
         let content = comment_element
-            .select(&content_selector)
+            .select(&selectors.comment_content)
             .next()
-            .map(|el| el.inner_html())
+            .map(|el| markdown_source(&el))
             .unwrap_or_default();
 
-        let avatar_selector = Selector::parse(".avatar").unwrap();
         let avatar = comment_element
-            .select(&avatar_selector)
+            .select(&selectors.comment_avatar)
             .next()
             .and_then(|el| el.value().attr("src"))
             .unwrap_or("https://nyaa.si/static/img/avatars/default.png")
@@ -302,3 +362,97 @@ fn parse_comments(document: &Html) -> Result<Vec<ViewComment>> {
 
     Ok(comments)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_files() {
+        let html = r#"
+<div class="torrent-file-list panel-body">
+    <ul>
+        <li><a href="" class="folder"><i class="fa fa-folder-open"></i>[Group] Show (2025)</a>
+            <ul data-show="yes">
+                <li><a href="" class="folder"><i class="fa fa-folder-open"></i>Extras</a>
+                    <ul data-show="yes">
+                        <li><i class="fa fa-file"></i>NCOP (Creditless).mkv <span class="file-size">(95.3 MiB)</span></li>
+                    </ul>
+                </li>
+                <li><i class="fa fa-file"></i>[Group] Show - 01 (1080p).mkv <span class="file-size">(1.4 GiB)</span></li>
+            </ul>
+        </li>
+        <li><i class="fa fa-file"></i>readme.txt <span class="file-size">(512 Bytes)</span></li>
+    </ul>
+</div>
+"#;
+        let document = Html::parse_document(html);
+        let tree = parse_files(&document, ParserContext::nyaa()).unwrap();
+        assert_eq!(
+            tree,
+            vec![
+                FileNode::Folder {
+                    name: "[Group] Show (2025)".into(),
+                    children: vec![
+                        FileNode::Folder {
+                            name: "Extras".into(),
+                            children: vec![FileNode::File {
+                                name: "NCOP (Creditless).mkv".into(),
+                                size: 99929293,
+                            }],
+                        },
+                        FileNode::File {
+                            name: "[Group] Show - 01 (1080p).mkv".into(),
+                            size: 1503238554,
+                        },
+                    ],
+                },
+                FileNode::File {
+                    name: "readme.txt".into(),
+                    size: 512,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_files_without_size() {
+        let html = r#"
+<div class="torrent-file-list panel-body">
+    <ul>
+        <li><i class="fa fa-file"></i>empty.txt</li>
+        <li></li>
+        <li><i class="fa fa-file"></i>readme.txt <span class="file-size">(512 Bytes)</span></li>
+    </ul>
+</div>
+"#;
+        let document = Html::parse_document(html);
+        let tree = parse_files(&document, ParserContext::nyaa()).unwrap();
+        assert_eq!(
+            tree,
+            vec![
+                FileNode::File {
+                    name: "empty.txt".into(),
+                    size: 0,
+                },
+                FileNode::File {
+                    name: "readme.txt".into(),
+                    size: 512,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_markdown_source() {
+        let html = r#"<div markdown-text class="panel-body" id="torrent-description">&gt; Quote &amp; **bold**
+1. [link](https://example.org/?a=1&amp;b=2)</div>"#;
+        let document = Html::parse_document(html);
+        let selector = &ParserContext::nyaa().view.description;
+        let element = document.select(selector).next().unwrap();
+        assert_eq!(
+            markdown_source(&element),
+            "> Quote & **bold**\n1. [link](https://example.org/?a=1&b=2)"
+        );
+    }
+}